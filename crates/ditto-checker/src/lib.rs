@@ -10,4 +10,7 @@ mod supply;
 mod typechecker;
 
 pub use module::{check_module, Everything, Modules};
-pub use result::{Result, TypeError, TypeErrorReport, Warning, WarningReport, Warnings};
+pub use result::{
+    explain, explained_codes, warning_code, Result, TypeError, TypeErrorReport, Warning,
+    WarningReport, Warnings, WARNINGS,
+};
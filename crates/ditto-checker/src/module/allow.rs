@@ -0,0 +1,106 @@
+use crate::result::{warning_code, Warning};
+use ditto_ast::Span;
+use ditto_cst as cst;
+
+/// Comments starting with this prefix silence warnings for the
+/// import line or declaration that follows.
+///
+/// ```ditto
+/// -- ditto-allow: unused-value-declaration, W0008
+/// five = 5
+/// ```
+static ALLOW_PREFIX: &str = "ditto-allow:";
+
+/// Warnings that have been silenced (by code) for regions of a module.
+pub struct Allowed(Vec<(Span, Vec<&'static str>)>);
+
+impl Allowed {
+    /// Collect `-- ditto-allow:` comments from the leading comments
+    /// of import lines and top-level declarations.
+    pub fn from_module(cst_module: &cst::Module) -> Self {
+        let import_lines = cst_module.imports.iter().map(|import_line| {
+            (
+                import_line.get_span(),
+                &import_line.import_keyword.0.leading_comments,
+            )
+        });
+        let declarations = cst_module.declarations.iter().map(|declaration| {
+            let leading_comments = match declaration {
+                cst::Declaration::Value(value_declaration) => {
+                    &value_declaration.name.0.leading_comments
+                }
+                cst::Declaration::Type(type_declaration) => {
+                    &type_declaration.type_keyword().0.leading_comments
+                }
+                cst::Declaration::TypeAlias(type_alias_declaration) => {
+                    &type_alias_declaration.type_keyword.0.leading_comments
+                }
+                cst::Declaration::ForeignValue(foreign_value_declaration) => {
+                    &foreign_value_declaration.foreign_keyword.0.leading_comments
+                }
+            };
+            (declaration.get_span(), leading_comments)
+        });
+        Self(
+            import_lines
+                .chain(declarations)
+                .filter_map(|(span, comments)| {
+                    let codes = comments
+                        .iter()
+                        .flat_map(parse_allow_comment)
+                        .collect::<Vec<_>>();
+                    if codes.is_empty() {
+                        None
+                    } else {
+                        Some((span, codes))
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Has this warning been silenced?
+    pub fn allows(&self, warning: &Warning) -> bool {
+        let code = warning.code();
+        let span = warning.span();
+        self.0.iter().any(|(allowed_span, codes)| {
+            allowed_span.start_offset <= span.start_offset
+                && span.end_offset <= allowed_span.end_offset
+                && codes.contains(&code)
+        })
+    }
+}
+
+/// Returns the codes mentioned in a `-- ditto-allow:` comment.
+///
+/// Unrecognised warning names are ignored.
+fn parse_allow_comment(comment: &cst::Comment) -> Vec<&'static str> {
+    comment
+        .0
+        .trim_start_matches('-')
+        .trim_start()
+        .strip_prefix(ALLOW_PREFIX)
+        .map_or_else(Vec::new, |names| {
+            names
+                .split(',')
+                .filter_map(|name| warning_code(name.trim()))
+                .collect()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_allow_comments() {
+        let parse = |comment: &str| parse_allow_comment(&cst::Comment(comment.to_string()));
+        assert_eq!(parse("-- ditto-allow: unused-import"), vec!["W0013"]);
+        assert_eq!(
+            parse("--ditto-allow: W0009, unused-let-binder"),
+            vec!["W0009", "W0008"]
+        );
+        assert_eq!(parse("-- ditto-allow: not-a-warning"), Vec::<&str>::new());
+        assert_eq!(parse("-- unused-import"), Vec::<&str>::new());
+    }
+}
@@ -139,19 +139,16 @@ pub fn extract_imports(
             let package_name_span = parens.value.get_span();
             let package_name = PackageName::from(parens.value.clone());
 
-            let modules = everything
-                .packages
-                .get(&package_name)
-                .ok_or_else(|| TypeError::PackageNotFound {
+            let modules = everything.packages.get(&package_name).ok_or_else(|| {
+                TypeError::PackageNotFound {
                     span: package_name_span,
                     package_name: package_name.clone(),
-                })?
-                .clone();
+                }
+            })?;
 
             (Some(package_name), modules)
         } else {
-            let modules = everything.modules.clone();
-            (None, modules)
+            (None, &everything.modules)
         };
 
         let module_name_span = cst_module_name.get_span();
@@ -1,3 +1,4 @@
+mod allow;
 mod common;
 mod exports;
 mod foreign_value_declarations;
@@ -5,6 +6,7 @@ mod imports;
 mod type_declarations;
 mod value_declarations;
 
+use allow::Allowed;
 use exports::*;
 use foreign_value_declarations::*;
 use imports::*;
@@ -31,6 +33,8 @@ pub fn check_module(
 ) -> Result<(Module, Warnings)> {
     let mut warnings = Warnings::new();
 
+    let allowed = Allowed::from_module(&cst_module);
+
     let module_name = ModuleName::from(cst_module.header.module_name);
 
     let (imported_types, imported_constructors, imported_values, more_warnings) =
@@ -323,5 +327,8 @@ pub fn check_module(
         }
     }));
 
+    // Drop any warnings that have been explicitly silenced
+    warnings.retain(|warning| !allowed.allows(warning));
+
     Ok((module, warnings))
 }
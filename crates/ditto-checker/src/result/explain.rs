@@ -0,0 +1,374 @@
+use super::{TypeErrorReport, WarningReport};
+
+/// Build a table of explanations from the markdown files in `explanations/`.
+macro_rules! explanations {
+    ($($code:literal),* $(,)?) => {
+        &[$(($code, include_str!(concat!("../../explanations/", $code, ".md")))),*]
+    };
+}
+
+/// Long-form explanations, keyed by diagnostic code.
+static EXPLANATIONS: &[(&str, &str)] = explanations![
+    "E0101", "E0102", "E0103", "E0104", "E0105", "E0106", "E0107", "E0108", "E0109", "E0110",
+    "E0111", "E0112", "E0113", "E0114", "E0115", "E0116", "E0117", "E0118", "E0119", "E0120",
+    "E0121", "E0122", "E0123", "E0124", "E0125", "E0126", "E0127", "E0128", "E0129", "E0130",
+    "E0131", "E0132", "W0001", "W0002", "W0003", "W0004", "W0005", "W0006", "W0007", "W0008",
+    "W0009", "W0010", "W0011", "W0012", "W0013", "W0014",
+];
+
+/// Get the long-form explanation (as markdown) for the code of a
+/// [TypeErrorReport] or [WarningReport], e.g. `"E0101"`.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(explained, _)| explained.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
+
+/// All the codes that can be passed to [explain].
+pub fn explained_codes() -> impl Iterator<Item = &'static str> {
+    EXPLANATIONS.iter().map(|(code, _)| *code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miette::{Diagnostic, NamedSource, SourceSpan};
+    use std::collections::HashSet;
+
+    fn input() -> NamedSource {
+        NamedSource::new("test", String::new())
+    }
+
+    fn span() -> SourceSpan {
+        SourceSpan::from((0, 0))
+    }
+
+    /// One of every [TypeErrorReport] variant.
+    fn all_type_error_reports() -> Vec<TypeErrorReport> {
+        vec![
+            TypeErrorReport::UnknownVariable {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnknownVariableWithSuggestion {
+                input: input(),
+                location: span(),
+                suggestion: String::new(),
+            },
+            TypeErrorReport::UnknownConstructor {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnknownConstructorWithSuggestion {
+                input: input(),
+                location: span(),
+                suggestion: String::new(),
+            },
+            TypeErrorReport::UnknownTypeVariable {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnknownTypeConstructor {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnificationError {
+                input: input(),
+                location: span(),
+                expected: String::new(),
+                actual: String::new(),
+            },
+            TypeErrorReport::KindUnificationError {
+                input: input(),
+                location: span(),
+                expected: String::new(),
+                actual: String::new(),
+            },
+            TypeErrorReport::InfiniteType {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::InfiniteKind {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::ModuleNotFound {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::ModuleNotFoundInPackage {
+                input: input(),
+                location: span(),
+                package_name: String::new(),
+            },
+            TypeErrorReport::PackageNotFound {
+                input: input(),
+                location: span(),
+                package_name: String::new(),
+            },
+            TypeErrorReport::DuplicateValueDeclaration {
+                input: input(),
+                previous_definition: span(),
+                duplicate_definition: span(),
+            },
+            TypeErrorReport::NotAFunction {
+                input: input(),
+                location: span(),
+                expression_type: String::new(),
+            },
+            TypeErrorReport::TypeNotAFunction {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::ArgumentLengthMismatch {
+                input: input(),
+                function_location: span(),
+                wanted_arguments: String::new(),
+            },
+            TypeErrorReport::TypeArgumentLengthMismatch {
+                input: input(),
+                function_location: span(),
+                wanted_parameters: String::new(),
+            },
+            TypeErrorReport::UnknownValueExport {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnknownTypeExport {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnknownValueImport {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::UnknownTypeImport {
+                input: input(),
+                location: span(),
+            },
+            TypeErrorReport::NoVisibleConstructors {
+                input: input(),
+                location: span(),
+                type_name: String::new(),
+            },
+            TypeErrorReport::DuplicateFunctionBinder {
+                input: input(),
+                previous_parameter: span(),
+                shadowing_parameter: span(),
+            },
+            TypeErrorReport::DuplicatePatternBinder {
+                input: input(),
+                previous_parameter: span(),
+                shadowing_parameter: span(),
+            },
+            TypeErrorReport::DuplicateTypeDeclaration {
+                input: input(),
+                previous_type: span(),
+                duplicate_type: span(),
+            },
+            TypeErrorReport::DuplicateTypeConstructor {
+                input: input(),
+                previous_constructor: span(),
+                duplicate_constructor: span(),
+            },
+            TypeErrorReport::DuplicateTypeDeclarationVariable {
+                input: input(),
+                previous_variable: span(),
+                duplicate_variable: span(),
+            },
+            TypeErrorReport::DuplicateImportLine {
+                input: input(),
+                previous_line: span(),
+                duplicate_line: span(),
+            },
+            TypeErrorReport::DuplicateImportModule {
+                input: input(),
+                previous_import: span(),
+                duplicate_import: span(),
+                module_name: String::new(),
+            },
+            TypeErrorReport::ReboundImportValue {
+                input: input(),
+                previous_binding: span(),
+                new_binding: span(),
+                value_name: String::new(),
+            },
+            TypeErrorReport::ReboundImportType {
+                input: input(),
+                previous_binding: span(),
+                new_binding: span(),
+                type_name: String::new(),
+            },
+            TypeErrorReport::ReboundImportConstructor {
+                input: input(),
+                previous_binding: span(),
+                new_binding: span(),
+                constructor_name: String::new(),
+            },
+            TypeErrorReport::MatchNotExhaustive {
+                input: input(),
+                location: span(),
+                missing_patterns: String::new(),
+            },
+            TypeErrorReport::RefutableFunctionBinder {
+                input: input(),
+                location: span(),
+                missing_patterns: String::new(),
+            },
+        ]
+    }
+
+    /// Adding a [TypeErrorReport] variant without adding it to
+    /// [all_type_error_reports] will fail `explanations_are_complete`.
+    fn type_error_report_index(report: &TypeErrorReport) -> usize {
+        match report {
+            TypeErrorReport::UnknownVariable { .. } => 0,
+            TypeErrorReport::UnknownVariableWithSuggestion { .. } => 1,
+            TypeErrorReport::UnknownConstructor { .. } => 2,
+            TypeErrorReport::UnknownConstructorWithSuggestion { .. } => 3,
+            TypeErrorReport::UnknownTypeVariable { .. } => 4,
+            TypeErrorReport::UnknownTypeConstructor { .. } => 5,
+            TypeErrorReport::UnificationError { .. } => 6,
+            TypeErrorReport::KindUnificationError { .. } => 7,
+            TypeErrorReport::InfiniteType { .. } => 8,
+            TypeErrorReport::InfiniteKind { .. } => 9,
+            TypeErrorReport::ModuleNotFound { .. } => 10,
+            TypeErrorReport::ModuleNotFoundInPackage { .. } => 11,
+            TypeErrorReport::PackageNotFound { .. } => 12,
+            TypeErrorReport::DuplicateValueDeclaration { .. } => 13,
+            TypeErrorReport::NotAFunction { .. } => 14,
+            TypeErrorReport::TypeNotAFunction { .. } => 15,
+            TypeErrorReport::ArgumentLengthMismatch { .. } => 16,
+            TypeErrorReport::TypeArgumentLengthMismatch { .. } => 17,
+            TypeErrorReport::UnknownValueExport { .. } => 18,
+            TypeErrorReport::UnknownTypeExport { .. } => 19,
+            TypeErrorReport::UnknownValueImport { .. } => 20,
+            TypeErrorReport::UnknownTypeImport { .. } => 21,
+            TypeErrorReport::NoVisibleConstructors { .. } => 22,
+            TypeErrorReport::DuplicateFunctionBinder { .. } => 23,
+            TypeErrorReport::DuplicatePatternBinder { .. } => 24,
+            TypeErrorReport::DuplicateTypeDeclaration { .. } => 25,
+            TypeErrorReport::DuplicateTypeConstructor { .. } => 26,
+            TypeErrorReport::DuplicateTypeDeclarationVariable { .. } => 27,
+            TypeErrorReport::DuplicateImportLine { .. } => 28,
+            TypeErrorReport::DuplicateImportModule { .. } => 29,
+            TypeErrorReport::ReboundImportValue { .. } => 30,
+            TypeErrorReport::ReboundImportType { .. } => 31,
+            TypeErrorReport::ReboundImportConstructor { .. } => 32,
+            TypeErrorReport::MatchNotExhaustive { .. } => 33,
+            TypeErrorReport::RefutableFunctionBinder { .. } => 34,
+        }
+    }
+    const TYPE_ERROR_REPORT_VARIANTS: usize = 35;
+
+    /// One of every [WarningReport] variant.
+    fn all_warning_reports() -> Vec<WarningReport> {
+        vec![
+            WarningReport::DuplicateValueExport {
+                previous_export: span(),
+                duplicate_export: span(),
+            },
+            WarningReport::DuplicateTypeExport {
+                previous_export: span(),
+                duplicate_export: span(),
+            },
+            WarningReport::DuplicateValueImport {
+                previous_import: span(),
+                duplicate_import: span(),
+            },
+            WarningReport::DuplicateTypeImport {
+                previous_import: span(),
+                duplicate_import: span(),
+            },
+            WarningReport::UnusedFunctionBinder { location: span() },
+            WarningReport::UnusedPatternBinder { location: span() },
+            WarningReport::UnusedEffectBinder { location: span() },
+            WarningReport::UnusedLetBinder { location: span() },
+            WarningReport::UnusedValueDeclaration { location: span() },
+            WarningReport::UnusedForeignValue { location: span() },
+            WarningReport::UnusedTypeDeclaration { location: span() },
+            WarningReport::UnusedTypeConstructors { location: span() },
+            WarningReport::UnusedImport { location: span() },
+            WarningReport::RedundantMatchPattern { location: span() },
+        ]
+    }
+
+    /// See [type_error_report_index].
+    fn warning_report_index(report: &WarningReport) -> usize {
+        match report {
+            WarningReport::DuplicateValueExport { .. } => 0,
+            WarningReport::DuplicateTypeExport { .. } => 1,
+            WarningReport::DuplicateValueImport { .. } => 2,
+            WarningReport::DuplicateTypeImport { .. } => 3,
+            WarningReport::UnusedFunctionBinder { .. } => 4,
+            WarningReport::UnusedPatternBinder { .. } => 5,
+            WarningReport::UnusedEffectBinder { .. } => 6,
+            WarningReport::UnusedLetBinder { .. } => 7,
+            WarningReport::UnusedValueDeclaration { .. } => 8,
+            WarningReport::UnusedForeignValue { .. } => 9,
+            WarningReport::UnusedTypeDeclaration { .. } => 10,
+            WarningReport::UnusedTypeConstructors { .. } => 11,
+            WarningReport::UnusedImport { .. } => 12,
+            WarningReport::RedundantMatchPattern { .. } => 13,
+        }
+    }
+    const WARNING_REPORT_VARIANTS: usize = 14;
+
+    #[test]
+    fn explanations_are_complete() {
+        let type_error_reports = all_type_error_reports();
+        let indices = type_error_reports
+            .iter()
+            .map(type_error_report_index)
+            .collect::<HashSet<_>>();
+        assert_eq!(indices, (0..TYPE_ERROR_REPORT_VARIANTS).collect());
+
+        let warning_reports = all_warning_reports();
+        let indices = warning_reports
+            .iter()
+            .map(warning_report_index)
+            .collect::<HashSet<_>>();
+        assert_eq!(indices, (0..WARNING_REPORT_VARIANTS).collect());
+
+        let diagnostics = type_error_reports
+            .iter()
+            .map(|report| report as &dyn Diagnostic)
+            .chain(
+                warning_reports
+                    .iter()
+                    .map(|report| report as &dyn Diagnostic),
+            );
+
+        let mut used_codes = HashSet::new();
+        for diagnostic in diagnostics {
+            let code = diagnostic
+                .code()
+                .unwrap_or_else(|| panic!("no code for {:?}", diagnostic.to_string()))
+                .to_string();
+            let explanation =
+                explain(&code).unwrap_or_else(|| panic!("no explanation for {}", code));
+            assert!(
+                explanation.starts_with(&format!("# {}: ", code)),
+                "bad heading for {}",
+                code
+            );
+            used_codes.insert(code);
+        }
+
+        // No stale explanations
+        for code in explained_codes() {
+            assert!(used_codes.contains(code), "{} isn't used", code);
+        }
+    }
+
+    #[test]
+    fn error_and_warning_codes_are_distinct() {
+        for report in all_type_error_reports() {
+            assert!(report.code().unwrap().to_string().starts_with('E'));
+        }
+        for report in all_warning_reports() {
+            assert!(report.code().unwrap().to_string().starts_with('W'));
+        }
+    }
+}
@@ -1,8 +1,10 @@
+mod explain;
 mod type_error;
 mod warnings;
 
+pub use explain::{explain, explained_codes};
 pub use type_error::{TypeError, TypeErrorReport};
-pub use warnings::{Warning, WarningReport, Warnings};
+pub use warnings::{warning_code, Warning, WarningReport, Warnings, WARNINGS};
 
 /// Typechecking result.
 pub type Result<T> = std::result::Result<T, TypeError>;
@@ -434,7 +434,7 @@ impl TypeError {
 //     - backtick anything referring to code.
 pub enum TypeErrorReport {
     #[error("unknown variable")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0101), severity(Error))]
     UnknownVariable {
         #[source_code]
         input: NamedSource,
@@ -442,7 +442,7 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("unknown variable")]
-    #[diagnostic(severity(Error), help("did you mean `{suggestion}`?"))]
+    #[diagnostic(code(E0101), severity(Error), help("did you mean `{suggestion}`?"))]
     UnknownVariableWithSuggestion {
         #[source_code]
         input: NamedSource,
@@ -451,7 +451,7 @@ pub enum TypeErrorReport {
         suggestion: String,
     },
     #[error("unknown constructor")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0102), severity(Error))]
     UnknownConstructor {
         #[source_code]
         input: NamedSource,
@@ -459,7 +459,7 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("unknown constructor")]
-    #[diagnostic(severity(Error), help("did you mean `{suggestion}`?"))]
+    #[diagnostic(code(E0102), severity(Error), help("did you mean `{suggestion}`?"))]
     UnknownConstructorWithSuggestion {
         #[source_code]
         input: NamedSource,
@@ -468,7 +468,7 @@ pub enum TypeErrorReport {
         suggestion: String,
     },
     #[error("unknown type variable")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0103), severity(Error))]
     UnknownTypeVariable {
         #[source_code]
         input: NamedSource,
@@ -477,7 +477,7 @@ pub enum TypeErrorReport {
         // TODO suggestions?
     },
     #[error("unknown type constructor")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0104), severity(Error))]
     UnknownTypeConstructor {
         #[source_code]
         input: NamedSource,
@@ -485,7 +485,11 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("types don't unify")]
-    #[diagnostic(severity(Error), help("expected {expected}\ngot {actual}"))]
+    #[diagnostic(
+        code(E0105),
+        severity(Error),
+        help("expected {expected}\ngot {actual}")
+    )]
     UnificationError {
         #[source_code]
         input: NamedSource,
@@ -495,7 +499,11 @@ pub enum TypeErrorReport {
         actual: String,
     },
     #[error("kinds don't unify")]
-    #[diagnostic(severity(Error), help("expected {expected}\ngot {actual}"))]
+    #[diagnostic(
+        code(E0106),
+        severity(Error),
+        help("expected {expected}\ngot {actual}")
+    )]
     KindUnificationError {
         #[source_code]
         input: NamedSource,
@@ -505,7 +513,7 @@ pub enum TypeErrorReport {
         actual: String,
     },
     #[error("infinite type")]
-    #[diagnostic(severity(Error), help("try adding type annotations?"))]
+    #[diagnostic(code(E0107), severity(Error), help("try adding type annotations?"))]
     InfiniteType {
         #[source_code]
         input: NamedSource,
@@ -513,7 +521,7 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("infinite kind")]
-    #[diagnostic(severity(Error), help("please report how you did this"))]
+    #[diagnostic(code(E0108), severity(Error), help("please report how you did this"))]
     InfiniteKind {
         #[source_code]
         input: NamedSource,
@@ -521,7 +529,7 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("module not found")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0109), severity(Error))]
     ModuleNotFound {
         #[source_code]
         input: NamedSource,
@@ -529,7 +537,7 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("module not found")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0109), severity(Error))]
     ModuleNotFoundInPackage {
         #[source_code]
         input: NamedSource,
@@ -539,6 +547,7 @@ pub enum TypeErrorReport {
     },
     #[error("package not found")]
     #[diagnostic(
+        code(E0110),
         severity(Error),
         help("try adding `{package_name}` to your dependencies?")
     )]
@@ -550,7 +559,7 @@ pub enum TypeErrorReport {
         package_name: String,
     },
     #[error("duplicate top-level name")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0111), severity(Error))]
     DuplicateValueDeclaration {
         #[source_code]
         input: NamedSource,
@@ -560,7 +569,11 @@ pub enum TypeErrorReport {
         duplicate_definition: SourceSpan,
     },
     #[error("expression isn't callable")]
-    #[diagnostic(severity(Error), help("expression has type: {expression_type}"))]
+    #[diagnostic(
+        code(E0112),
+        severity(Error),
+        help("expression has type: {expression_type}")
+    )]
     NotAFunction {
         #[source_code]
         input: NamedSource,
@@ -569,7 +582,7 @@ pub enum TypeErrorReport {
         expression_type: String,
     },
     #[error("type isn't callable")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0113), severity(Error))]
     TypeNotAFunction {
         #[source_code]
         input: NamedSource,
@@ -577,7 +590,7 @@ pub enum TypeErrorReport {
         location: SourceSpan,
     },
     #[error("wrong number of arguments")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0114), severity(Error))]
     ArgumentLengthMismatch {
         #[source_code]
         input: NamedSource,
@@ -586,7 +599,7 @@ pub enum TypeErrorReport {
         wanted_arguments: String,
     },
     #[error("wrong number of type parameters")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0115), severity(Error))]
     TypeArgumentLengthMismatch {
         #[source_code]
         input: NamedSource,
@@ -595,7 +608,7 @@ pub enum TypeErrorReport {
         wanted_parameters: String,
     },
     #[error("unknown value export")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0116), severity(Error))]
     UnknownValueExport {
         #[source_code]
         input: NamedSource,
@@ -604,7 +617,7 @@ pub enum TypeErrorReport {
         // TODO suggestions?
     },
     #[error("unknown type export")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0117), severity(Error))]
     UnknownTypeExport {
         #[source_code]
         input: NamedSource,
@@ -613,7 +626,7 @@ pub enum TypeErrorReport {
         // TODO suggestions?
     },
     #[error("unknown value import")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0118), severity(Error))]
     UnknownValueImport {
         #[source_code]
         input: NamedSource,
@@ -622,7 +635,7 @@ pub enum TypeErrorReport {
         // TODO suggestions?
     },
     #[error("unknown type import")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0119), severity(Error))]
     UnknownTypeImport {
         #[source_code]
         input: NamedSource,
@@ -631,7 +644,7 @@ pub enum TypeErrorReport {
         // TODO suggestions?
     },
     #[error("no visible constructors")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0120), severity(Error))]
     NoVisibleConstructors {
         #[source_code]
         input: NamedSource,
@@ -640,7 +653,7 @@ pub enum TypeErrorReport {
         type_name: String,
     },
     #[error("duplicate function parameter")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0121), severity(Error))]
     DuplicateFunctionBinder {
         #[source_code]
         input: NamedSource,
@@ -650,7 +663,7 @@ pub enum TypeErrorReport {
         shadowing_parameter: SourceSpan,
     },
     #[error("duplicate pattern variable")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0122), severity(Error))]
     DuplicatePatternBinder {
         #[source_code]
         input: NamedSource,
@@ -660,7 +673,7 @@ pub enum TypeErrorReport {
         shadowing_parameter: SourceSpan,
     },
     #[error("duplicate type declaration")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0123), severity(Error))]
     DuplicateTypeDeclaration {
         #[source_code]
         input: NamedSource,
@@ -670,7 +683,7 @@ pub enum TypeErrorReport {
         duplicate_type: SourceSpan,
     },
     #[error("duplicate constructor")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0124), severity(Error))]
     DuplicateTypeConstructor {
         #[source_code]
         input: NamedSource,
@@ -680,7 +693,7 @@ pub enum TypeErrorReport {
         duplicate_constructor: SourceSpan,
     },
     #[error("duplicate type variable")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0125), severity(Error))]
     DuplicateTypeDeclarationVariable {
         #[source_code]
         input: NamedSource,
@@ -690,7 +703,7 @@ pub enum TypeErrorReport {
         duplicate_variable: SourceSpan,
     },
     #[error("duplicate import")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0126), severity(Error))]
     DuplicateImportLine {
         #[source_code]
         input: NamedSource,
@@ -700,7 +713,7 @@ pub enum TypeErrorReport {
         duplicate_line: SourceSpan,
     },
     #[error("duplicate imports for module `{module_name}`")]
-    #[diagnostic(severity(Error), help("try aliasing one of the imports?"))]
+    #[diagnostic(code(E0127), severity(Error), help("try aliasing one of the imports?"))]
     DuplicateImportModule {
         #[source_code]
         input: NamedSource,
@@ -711,7 +724,7 @@ pub enum TypeErrorReport {
         module_name: String,
     },
     #[error("value `{value_name}` imported multiple times")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0128), severity(Error))]
     ReboundImportValue {
         #[source_code]
         input: NamedSource,
@@ -722,7 +735,7 @@ pub enum TypeErrorReport {
         value_name: String,
     },
     #[error("type `{type_name}` imported multiple times")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0129), severity(Error))]
     ReboundImportType {
         #[source_code]
         input: NamedSource,
@@ -733,7 +746,7 @@ pub enum TypeErrorReport {
         type_name: String,
     },
     #[error("constructor `{constructor_name}` imported multiple times")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0130), severity(Error))]
     ReboundImportConstructor {
         #[source_code]
         input: NamedSource,
@@ -744,7 +757,11 @@ pub enum TypeErrorReport {
         constructor_name: String,
     },
     #[error("match not exhaustive")]
-    #[diagnostic(severity(Error), help("patterns not covered:\n{missing_patterns}"))]
+    #[diagnostic(
+        code(E0131),
+        severity(Error),
+        help("patterns not covered:\n{missing_patterns}")
+    )]
     MatchNotExhaustive {
         #[source_code]
         input: NamedSource,
@@ -753,7 +770,11 @@ pub enum TypeErrorReport {
         missing_patterns: String,
     },
     #[error("refutable function binder")]
-    #[diagnostic(severity(Error), help("patterns here must cover all cases"))]
+    #[diagnostic(
+        code(E0132),
+        severity(Error),
+        help("patterns here must cover all cases")
+    )]
     RefutableFunctionBinder {
         #[source_code]
         input: NamedSource,
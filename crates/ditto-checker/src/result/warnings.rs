@@ -58,7 +58,86 @@ pub enum Warning {
     },
 }
 
+/// Every warning `(code, name)` pair.
+///
+/// Either can be used to refer to a warning when configuring warning levels, or when
+/// silencing a warning with a `-- ditto-allow:` comment.
+pub static WARNINGS: &[(&str, &str)] = &[
+    ("W0001", "duplicate-value-export"),
+    ("W0002", "duplicate-type-export"),
+    ("W0003", "duplicate-value-import"),
+    ("W0004", "duplicate-type-import"),
+    ("W0005", "unused-function-binder"),
+    ("W0006", "unused-pattern-binder"),
+    ("W0007", "unused-effect-binder"),
+    ("W0008", "unused-let-binder"),
+    ("W0009", "unused-value-declaration"),
+    ("W0010", "unused-foreign-value"),
+    ("W0011", "unused-type-declaration"),
+    ("W0012", "unused-type-constructors"),
+    ("W0013", "unused-import"),
+    ("W0014", "redundant-match-pattern"),
+];
+
+/// Resolve a warning name (e.g. `"unused-import"`) or code (e.g. `"W0013"`) to a code.
+pub fn warning_code(name_or_code: &str) -> Option<&'static str> {
+    WARNINGS
+        .iter()
+        .find(|(code, name)| code.eq_ignore_ascii_case(name_or_code) || *name == name_or_code)
+        .map(|(code, _)| *code)
+}
+
 impl Warning {
+    /// The stable code for this warning, e.g. `"W0013"`.
+    ///
+    /// Matches the code of the corresponding [WarningReport].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DuplicateValueExport { .. } => "W0001",
+            Self::DuplicateTypeExport { .. } => "W0002",
+            Self::DuplicateValueImport { .. } => "W0003",
+            Self::DuplicateTypeImport { .. } => "W0004",
+            Self::UnusedFunctionBinder { .. } => "W0005",
+            Self::UnusedPatternBinder { .. } => "W0006",
+            Self::UnusedEffectBinder { .. } => "W0007",
+            Self::UnusedLetBinder { .. } => "W0008",
+            Self::UnusedValueDeclaration { .. } => "W0009",
+            Self::UnusedForeignValue { .. } => "W0010",
+            Self::UnusedTypeDeclaration { .. } => "W0011",
+            Self::UnusedTypeConstructors { .. } => "W0012",
+            Self::UnusedImport { .. } => "W0013",
+            Self::RedundantMatchPattern { .. } => "W0014",
+        }
+    }
+
+    /// The location this warning is primarily about.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::DuplicateValueExport {
+                duplicate_export, ..
+            }
+            | Self::DuplicateTypeExport {
+                duplicate_export, ..
+            } => *duplicate_export,
+            Self::DuplicateValueImport {
+                duplicate_import, ..
+            }
+            | Self::DuplicateTypeImport {
+                duplicate_import, ..
+            } => *duplicate_import,
+            Self::UnusedFunctionBinder { span }
+            | Self::UnusedPatternBinder { span }
+            | Self::UnusedEffectBinder { span }
+            | Self::UnusedLetBinder { span }
+            | Self::UnusedValueDeclaration { span }
+            | Self::UnusedForeignValue { span }
+            | Self::UnusedTypeDeclaration { span }
+            | Self::UnusedTypeConstructors { span }
+            | Self::UnusedImport { span }
+            | Self::RedundantMatchPattern { span } => *span,
+        }
+    }
+
     /// Convert a warning to a pretty report.
     pub fn into_report(self) -> WarningReport {
         match self {
@@ -132,7 +211,7 @@ impl Warning {
 //     - backtick anything referring to code.
 pub enum WarningReport {
     #[error("duplicate value export")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0001), severity(Warning))]
     DuplicateValueExport {
         #[label("previously exported here")]
         #[serde(with = "SourceSpanDef")]
@@ -142,7 +221,7 @@ pub enum WarningReport {
         duplicate_export: SourceSpan,
     },
     #[error("duplicate type export")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0002), severity(Warning))]
     DuplicateTypeExport {
         #[label("previously exported here")]
         #[serde(with = "SourceSpanDef")]
@@ -152,7 +231,7 @@ pub enum WarningReport {
         duplicate_export: SourceSpan,
     },
     #[error("duplicate value import")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0003), severity(Warning))]
     DuplicateValueImport {
         #[label("previously imported here")]
         #[serde(with = "SourceSpanDef")]
@@ -162,7 +241,7 @@ pub enum WarningReport {
         duplicate_import: SourceSpan,
     },
     #[error("duplicate type import")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0004), severity(Warning))]
     DuplicateTypeImport {
         #[label("previously imported here")]
         #[serde(with = "SourceSpanDef")]
@@ -172,70 +251,70 @@ pub enum WarningReport {
         duplicate_import: SourceSpan,
     },
     #[error("unused function binder")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0005), severity(Warning))]
     UnusedFunctionBinder {
         #[label("this isn't used")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused patter binder")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0006), severity(Warning))]
     UnusedPatternBinder {
         #[label("this isn't used")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused effect binder")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0007), severity(Warning))]
     UnusedEffectBinder {
         #[label("this isn't used")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused let binder")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0008), severity(Warning))]
     UnusedLetBinder {
         #[label("this isn't used")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused top-level value")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0009), severity(Warning))]
     UnusedValueDeclaration {
         #[label("this isn't referenced or exported")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused foreign value")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0010), severity(Warning))]
     UnusedForeignValue {
         #[label("this isn't being used")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused type declaration")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0011), severity(Warning))]
     UnusedTypeDeclaration {
         #[label("this isn't referenced or exported")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused type constructors")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0012), severity(Warning))]
     UnusedTypeConstructors {
         #[label("type is never constructed")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("unused import")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0013), severity(Warning))]
     UnusedImport {
         #[label("not needed")]
         #[serde(with = "SourceSpanDef")]
         location: SourceSpan,
     },
     #[error("redundant match pattern")]
-    #[diagnostic(severity(Warning))]
+    #[diagnostic(code(W0014), severity(Warning))]
     RedundantMatchPattern {
         #[label("unreachable")]
         #[serde(with = "SourceSpanDef")]
@@ -262,3 +341,56 @@ impl From<SourceSpanDef> for SourceSpan {
         (def.start, def.length).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miette::Diagnostic;
+
+    #[test]
+    fn codes_match_reports() {
+        let span = Span {
+            start_offset: 0,
+            end_offset: 0,
+        };
+        let warnings = vec![
+            Warning::DuplicateValueExport {
+                previous_export: span,
+                duplicate_export: span,
+            },
+            Warning::DuplicateTypeExport {
+                previous_export: span,
+                duplicate_export: span,
+            },
+            Warning::DuplicateValueImport {
+                previous_import: span,
+                duplicate_import: span,
+            },
+            Warning::DuplicateTypeImport {
+                previous_import: span,
+                duplicate_import: span,
+            },
+            Warning::UnusedFunctionBinder { span },
+            Warning::UnusedPatternBinder { span },
+            Warning::UnusedEffectBinder { span },
+            Warning::UnusedLetBinder { span },
+            Warning::UnusedValueDeclaration { span },
+            Warning::UnusedForeignValue { span },
+            Warning::UnusedTypeDeclaration { span },
+            Warning::UnusedTypeConstructors { span },
+            Warning::UnusedImport { span },
+            Warning::RedundantMatchPattern { span },
+        ];
+        assert_eq!(warnings.len(), WARNINGS.len());
+        for (warning, (code, name)) in warnings.into_iter().zip(WARNINGS) {
+            assert_eq!(warning.code(), *code);
+            assert_eq!(warning_code(name), Some(*code));
+            assert_eq!(warning_code(code), Some(*code));
+            let report = warning.into_report();
+            assert_eq!(
+                report.code().map(|code| code.to_string()).as_deref(),
+                Some(*code)
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{common, version::Version};
+use clap::{arg, value_parser, ArgMatches, Command};
+use miette::Result;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    time::Duration,
+};
+
+static SUBCOMMAND_GC: &str = "gc";
+
+pub fn command(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Manage the build cache")
+        .disable_help_subcommand(true)
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(SUBCOMMAND_GC)
+                .about("Remove build cache entries that haven't been used recently")
+                .arg(
+                    arg!(--"max-age" <DAYS> "Remove entries unused for this many days")
+                        .value_parser(value_parser!(u64))
+                        .default_value("30"),
+                ),
+        )
+}
+
+#[test]
+fn verify_cmd() {
+    command("cache").debug_assert();
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_GC) {
+        let max_age_days = *matches.get_one::<u64>("max-age").unwrap();
+        let max_age = Duration::from_secs(max_age_days * 24 * 60 * 60);
+        let stats = ditto_make::gc_build_cache(&get_build_cache_root()?, max_age)?;
+        println!(
+            "Removed {} build cache entries, kept {}",
+            stats.removed, stats.kept
+        );
+        Ok(())
+    } else {
+        unreachable!()
+    }
+}
+
+/// ~/.cache/ditto/build
+fn get_build_cache_root() -> Result<PathBuf> {
+    let mut build_cache_dir = common::get_ditto_cache_dir()?;
+    build_cache_dir.push("build");
+    Ok(build_cache_dir)
+}
+
+/// ~/.cache/ditto/build/0.0.1-5f2b7c9e1d3a4b60
+///
+/// Cache entries are only valid for the exact `ditto` build that produced them,
+/// hence the hash of the full version information and build profile.
+///
+/// Can be overridden by setting `DITTO_BUILD_CACHE_DIR`, in which case it's
+/// up to the caller to keep it specific to the current ditto version.
+pub fn get_build_cache_dir(ditto_version: &Version) -> Result<PathBuf> {
+    if let Some(build_cache_dir) = std::env::var_os(ditto_make::ENV_BUILD_CACHE_DIR)
+        .filter(|build_cache_dir| !build_cache_dir.is_empty())
+    {
+        return Ok(PathBuf::from(build_cache_dir));
+    }
+    let mut hasher = DefaultHasher::new();
+    // NOTE debug builds serialize artifacts as JSON and release builds as CBOR,
+    // so they can't share entries even if they're built from the same commit
+    let is_debug_build = cfg!(debug_assertions);
+    (
+        ditto_version.render_long(),
+        &ditto_version.git_rev,
+        is_debug_build,
+    )
+        .hash(&mut hasher);
+    let mut build_cache_dir = get_build_cache_root()?;
+    build_cache_dir.push(format!(
+        "{}-{:016x}",
+        ditto_version.semversion,
+        hasher.finish()
+    ));
+    Ok(build_cache_dir)
+}
@@ -0,0 +1,383 @@
+use crate::{make, pkg};
+use clap::{arg, ArgMatches, Command};
+use ditto_config::{
+    read_config, read_package_index, Config, PackageName, CONFIG_FILE_NAME, PACKAGE_NAME_REGEX,
+};
+use fs2::FileExt;
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+use std::{collections::HashSet, fs, path::Path};
+
+static KEY_DEPENDENCIES: &str = "dependencies";
+static KEY_TEST_DEPENDENCIES: &str = "test-dependencies";
+
+pub fn command_add(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Add dependencies to a project")
+        .arg(arg!(<PACKAGES> ... "Packages to add"))
+        .arg(arg!(--test "Add as test dependencies"))
+}
+
+#[test]
+fn verify_cmd_add() {
+    command_add("add").debug_assert();
+}
+
+pub fn command_remove(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Remove dependencies from a project")
+        .arg(arg!(<PACKAGES> ... "Packages to remove"))
+}
+
+#[test]
+fn verify_cmd_remove() {
+    command_remove("remove").debug_assert();
+}
+
+pub async fn run_add(matches: &ArgMatches) -> Result<()> {
+    let package_names = matches_to_package_names(matches)?;
+    let key = if matches.get_flag("test") {
+        KEY_TEST_DEPENDENCIES
+    } else {
+        KEY_DEPENDENCIES
+    };
+
+    let config = read_config(CONFIG_FILE_NAME)?;
+    let mut available_packages = pkg::resolve_packages(&config, false)
+        .await?
+        .into_keys()
+        .collect::<HashSet<_>>();
+    if let Some(package_index) = config.package_index.as_ref() {
+        let index = read_package_index(pkg::package_index_dir(package_index)?)?;
+        available_packages.extend(index.into_keys());
+    }
+    for package_name in package_names.iter() {
+        if !available_packages.contains(package_name) {
+            return Err(unknown_package(
+                package_name,
+                if config.package_index.is_some() {
+                    "in the package set or package index"
+                } else {
+                    "in the package set"
+                },
+                available_packages.iter(),
+            ));
+        }
+    }
+
+    let mut to_add = Vec::new();
+    for package_name in package_names {
+        if config.dependencies.contains(&package_name)
+            || config.test_dependencies.contains(&package_name)
+        {
+            println!("{} is already a dependency", package_name.as_str());
+        } else if !to_add.contains(&package_name) {
+            to_add.push(package_name);
+        }
+    }
+    if to_add.is_empty() {
+        return Ok(());
+    }
+
+    edit_config(|contents| add_dependencies(contents, key, &to_add)).await?;
+    for package_name in to_add {
+        println!("Added {} to {}", package_name.as_str(), key);
+    }
+    Ok(())
+}
+
+pub async fn run_remove(matches: &ArgMatches) -> Result<()> {
+    let package_names = matches_to_package_names(matches)?;
+
+    let config = read_config(CONFIG_FILE_NAME)?;
+    for package_name in package_names.iter() {
+        if !config.dependencies.contains(package_name)
+            && !config.test_dependencies.contains(package_name)
+        {
+            return Err(unknown_package(
+                package_name,
+                "a dependency",
+                config
+                    .dependencies
+                    .iter()
+                    .chain(config.test_dependencies.iter()),
+            ));
+        }
+    }
+
+    edit_config(|contents| remove_dependencies(contents, &package_names)).await?;
+    for package_name in package_names {
+        println!("Removed {}", package_name.as_str());
+    }
+    Ok(())
+}
+
+fn matches_to_package_names(matches: &ArgMatches) -> Result<Vec<PackageName>> {
+    matches
+        .get_many::<String>("PACKAGES")
+        .unwrap_or_default()
+        .map(|package_name| {
+            if PACKAGE_NAME_REGEX.is_match(package_name) {
+                Ok(PackageName::new_unchecked(package_name.to_owned()))
+            } else {
+                Err(miette!("{:?} is not a valid package name", package_name))
+            }
+        })
+        .collect()
+}
+
+/// Edit the config file, then bring packages up to date with the new config.
+///
+/// If that fails then the original config file is restored.
+async fn edit_config(edit: impl FnOnce(&str) -> Result<String>) -> Result<()> {
+    let contents = fs::read_to_string(CONFIG_FILE_NAME)
+        .into_diagnostic()
+        .wrap_err(format!("error reading {}", CONFIG_FILE_NAME))?;
+    let new_contents = edit(&contents)?;
+    // Make sure we haven't broken anything
+    Config::parse(CONFIG_FILE_NAME, &new_contents).wrap_err("error editing config")?;
+    fs::write(CONFIG_FILE_NAME, new_contents)
+        .into_diagnostic()
+        .wrap_err(format!("error writing {}", CONFIG_FILE_NAME))?;
+
+    let result = async {
+        let config = pkg::read_project_config(CONFIG_FILE_NAME, None)?;
+        let lock = make::acquire_lock(&config)?;
+        let result = update_packages(&config).await;
+        lock.unlock()
+            .expect("Error releasing lock on build directory");
+        result
+    }
+    .await;
+    if result.is_err() {
+        fs::write(CONFIG_FILE_NAME, contents)
+            .into_diagnostic()
+            .wrap_err(format!("error restoring {}", CONFIG_FILE_NAME))?;
+    }
+    result
+}
+
+async fn update_packages(config: &Config) -> Result<()> {
+    pkg::check_packages_up_to_date(
+        Path::new(CONFIG_FILE_NAME),
+        config,
+        true,
+        pkg::LockMode::Normal,
+        false,
+    )
+    .await
+    .wrap_err("error updating packages")?;
+    pkg::remove_unused_packages(config)
+}
+
+/// Error for a package name that isn't one of `candidates`,
+/// listing similar names if there are any.
+fn unknown_package<'a>(
+    package_name: &PackageName,
+    expected: &str,
+    candidates: impl Iterator<Item = &'a PackageName>,
+) -> miette::Report {
+    let mut similar =
+        ditto_config::similar_names(package_name.as_str(), candidates.map(PackageName::as_str));
+    similar.truncate(3);
+    if similar.is_empty() {
+        miette!("{:?} isn't {}", package_name.as_str(), expected)
+    } else {
+        miette!(
+            help = format!("similar names: {}", similar.join(", ")),
+            "{:?} isn't {}",
+            package_name.as_str(),
+            expected
+        )
+    }
+}
+
+/// Add packages to the dependencies array at `key`, preserving formatting.
+fn add_dependencies(contents: &str, key: &str, package_names: &[PackageName]) -> Result<String> {
+    let mut document = contents
+        .parse::<toml_edit::Document>()
+        .into_diagnostic()
+        .wrap_err(format!("error parsing {}", CONFIG_FILE_NAME))?;
+
+    if document.get(key).is_none() {
+        document[key] = toml_edit::value(toml_edit::Array::new());
+    }
+    let array = document[key]
+        .as_array_mut()
+        .ok_or_else(|| miette!("expected `{}` to be an array", key))?;
+    for package_name in package_names {
+        push_formatted(array, package_name.as_str());
+    }
+    Ok(document.to_string())
+}
+
+/// Remove packages from the dependencies arrays, preserving formatting.
+fn remove_dependencies(contents: &str, package_names: &[PackageName]) -> Result<String> {
+    let mut document = contents
+        .parse::<toml_edit::Document>()
+        .into_diagnostic()
+        .wrap_err(format!("error parsing {}", CONFIG_FILE_NAME))?;
+
+    for key in [KEY_DEPENDENCIES, KEY_TEST_DEPENDENCIES] {
+        if let Some(array) = document.get_mut(key).and_then(|item| item.as_array_mut()) {
+            let mut index = 0;
+            while index < array.len() {
+                let is_removed = array
+                    .get(index)
+                    .and_then(dependency_name)
+                    .map_or(false, |string| {
+                        package_names.iter().any(|name| name.as_str() == string)
+                    });
+                if is_removed {
+                    remove_formatted(array, index);
+                } else {
+                    index += 1;
+                }
+            }
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// The package name of a dependency, which is either a string or a table with a `name`.
+fn dependency_name(value: &toml_edit::Value) -> Option<&str> {
+    value.as_str().or_else(|| {
+        value
+            .as_inline_table()
+            .and_then(|table| table.get("name"))
+            .and_then(|name| name.as_str())
+    })
+}
+
+/// Append to an array, following the layout of the existing items.
+fn push_formatted(array: &mut toml_edit::Array, string: &str) {
+    let mut value = toml_edit::Value::from(string);
+    if let Some(last) = array.iter_mut().last() {
+        let prefix = last.decor().prefix().unwrap_or_default().to_owned();
+        let suffix = last.decor().suffix().unwrap_or_default().to_owned();
+        last.decor_mut().set_suffix("");
+        let decor = value.decor_mut();
+        if prefix.contains('\n') {
+            // One item per line, but a comment after the last item belongs to
+            // that item, so it needs to stay on the same line
+            let trailing = array.trailing().to_owned();
+            match trailing.rfind('\n') {
+                Some(newline) => {
+                    let indent = &prefix[prefix.rfind('\n').unwrap() + 1..];
+                    decor.set_prefix(format!("{}{}", &trailing[..=newline], indent));
+                    array.set_trailing(&format!("\n{}", &trailing[newline + 1..]));
+                }
+                None => decor.set_prefix(prefix),
+            }
+        } else {
+            decor.set_prefix(" ");
+        }
+        decor.set_suffix(suffix);
+        array.push_formatted(value);
+    } else {
+        array.push(value);
+    }
+}
+
+/// Remove an item from an array, without disturbing the layout of the remaining items.
+fn remove_formatted(array: &mut toml_edit::Array, index: usize) {
+    let removed = array.remove(index);
+    if index == 0 {
+        if let Some(first) = array.get_mut(0) {
+            let prefix = removed.decor().prefix().unwrap_or_default().to_owned();
+            first.decor_mut().set_prefix(prefix);
+        }
+    }
+    if index == array.len() {
+        if let Some(last) = array.iter_mut().last() {
+            let suffix = removed.decor().suffix().unwrap_or_default().to_owned();
+            last.decor_mut().set_suffix(suffix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_names(names: &[&str]) -> Vec<PackageName> {
+        names
+            .iter()
+            .map(|name| PackageName::new_unchecked(name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn it_adds_dependencies() {
+        let add = |contents: &str, key: &str, names: &[&str]| {
+            add_dependencies(contents, key, &package_names(names)).unwrap()
+        };
+        similar_asserts::assert_eq!(
+            add(
+                "name = \"test\" # the name\ndependencies = [\"a\"]\n",
+                KEY_DEPENDENCIES,
+                &["b", "c"]
+            ),
+            "name = \"test\" # the name\ndependencies = [\"a\", \"b\", \"c\"]\n"
+        );
+        similar_asserts::assert_eq!(
+            add(
+                "name = \"test\"\ndependencies = [\n  \"a\", # needed\n]\n",
+                KEY_DEPENDENCIES,
+                &["b"]
+            ),
+            "name = \"test\"\ndependencies = [\n  \"a\", # needed\n  \"b\",\n]\n"
+        );
+        similar_asserts::assert_eq!(
+            add(
+                "name = \"test\"\ndependencies = [\n  \"a\",\n]\n",
+                KEY_DEPENDENCIES,
+                &["b"]
+            ),
+            "name = \"test\"\ndependencies = [\n  \"a\",\n  \"b\",\n]\n"
+        );
+        similar_asserts::assert_eq!(
+            add(
+                "name = \"test\"\ndependencies = [\n  \"a\"\n]\n",
+                KEY_DEPENDENCIES,
+                &["b"]
+            ),
+            "name = \"test\"\ndependencies = [\n  \"a\",\n  \"b\"\n]\n"
+        );
+        similar_asserts::assert_eq!(
+            add(
+                "name = \"test\"\n\n[package-set.packages]\na = { path = \"a\" }\n",
+                KEY_TEST_DEPENDENCIES,
+                &["a"]
+            ),
+            "name = \"test\"\ntest-dependencies = [\"a\"]\n\n[package-set.packages]\na = { path = \"a\" }\n"
+        );
+    }
+
+    #[test]
+    fn it_removes_dependencies() {
+        let remove = |contents: &str, names: &[&str]| {
+            remove_dependencies(contents, &package_names(names)).unwrap()
+        };
+        similar_asserts::assert_eq!(
+            remove(
+                "name = \"test\"\ndependencies = [\"a\", \"b\", \"c\"]\ntest-dependencies = [\"d\"]\n",
+                &["a", "c", "d"]
+            ),
+            "name = \"test\"\ndependencies = [\"b\"]\ntest-dependencies = []\n"
+        );
+        similar_asserts::assert_eq!(
+            remove(
+                "name = \"test\"\ndependencies = [\n  \"a\",\n  \"b\"\n]\n",
+                &["b"]
+            ),
+            "name = \"test\"\ndependencies = [\n  \"a\"\n]\n"
+        );
+        similar_asserts::assert_eq!(
+            remove(
+                "name = \"test\"\ndependencies = [\"a\", { name = \"b\", version = \"^1\" }]\n",
+                &["b"]
+            ),
+            "name = \"test\"\ndependencies = [\"a\"]\n"
+        );
+    }
+}
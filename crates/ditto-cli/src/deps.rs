@@ -0,0 +1,349 @@
+use crate::{make, pkg};
+use clap::{arg, ArgMatches, Command};
+use ditto_config::{Config, PackageName, CONFIG_FILE_NAME, PACKAGE_NAME_REGEX};
+use fs2::FileExt;
+use miette::{miette, Result, WrapErr};
+use std::{collections::HashSet, path::Path};
+
+static SUBCOMMAND_TREE: &str = "tree";
+static SUBCOMMAND_WHY: &str = "why";
+static SUBCOMMAND_CHECK: &str = "check";
+
+pub fn command(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Inspect dependencies")
+        .disable_help_subcommand(true)
+        .subcommand_required(true)
+        .subcommand(Command::new(SUBCOMMAND_TREE).about("Print the dependency tree"))
+        .subcommand(
+            Command::new(SUBCOMMAND_WHY)
+                .about("Print every path from this package to a dependency")
+                .arg(arg!(<PACKAGE> "The package to explain")),
+        )
+        .subcommand(
+            Command::new(SUBCOMMAND_CHECK).about("Check for installed packages that aren't needed"),
+        )
+}
+
+#[test]
+fn verify_cmd() {
+    command("deps").debug_assert();
+}
+
+pub async fn run(matches: &ArgMatches) -> Result<()> {
+    let config = pkg::read_project_config(CONFIG_FILE_NAME, None)?;
+    let installed = install_packages(&config).await?;
+    let unused = pkg::find_unused_packages(&config, &installed);
+
+    if matches.subcommand_matches(SUBCOMMAND_TREE).is_some() {
+        for line in render_tree(&config, &installed) {
+            println!("{}", line);
+        }
+        if !unused.is_empty() {
+            println!();
+            println!("{}", describe_unused(&unused));
+        }
+        Ok(())
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_WHY) {
+        let package_name = matches.get_one::<String>("PACKAGE").unwrap();
+        if !PACKAGE_NAME_REGEX.is_match(package_name) {
+            return Err(miette!("{:?} is not a valid package name", package_name));
+        }
+        let package_name = PackageName::new_unchecked(package_name.to_owned());
+        if unused.contains(&package_name) {
+            return Err(miette!(
+                help = "run `ditto update` to remove it",
+                "{} is installed, but nothing depends on it",
+                package_name.as_str()
+            ));
+        }
+        let paths = find_paths(&config, &installed, &package_name);
+        if paths.is_empty() {
+            return Err(miette!(
+                "{} isn't a dependency of {}",
+                package_name.as_str(),
+                config.name.as_str()
+            ));
+        }
+        for path in paths {
+            println!("{}", path.join(" -> "));
+        }
+        Ok(())
+    } else if matches.subcommand_matches(SUBCOMMAND_CHECK).is_some() {
+        if unused.is_empty() {
+            println!("All installed packages are needed");
+            Ok(())
+        } else {
+            Err(miette!(
+                help = "run `ditto update` to remove them",
+                "{}",
+                describe_unused(&unused)
+            ))
+        }
+    } else {
+        unreachable!()
+    }
+}
+
+/// Make sure packages are installed, so that there's something to inspect.
+async fn install_packages(config: &Config) -> Result<pkg::InstalledPackages> {
+    let lock = make::acquire_lock(config)?;
+    let result = pkg::check_packages_up_to_date(
+        Path::new(CONFIG_FILE_NAME),
+        config,
+        true,
+        pkg::LockMode::Normal,
+        false,
+    )
+    .await
+    .wrap_err("error updating packages")
+    .and_then(|_| pkg::read_installed_package_configs(config));
+    lock.unlock()
+        .expect("Error releasing lock on build directory");
+    result
+}
+
+fn describe_unused(unused: &[PackageName]) -> String {
+    format!(
+        "installed packages that nothing depends on: {}",
+        unused
+            .iter()
+            .map(|package_name| package_name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// The direct dependencies of the root package, flagging those that are test dependencies.
+fn root_dependencies(config: &Config) -> Vec<(&PackageName, bool)> {
+    let mut dependencies = config
+        .dependencies
+        .iter()
+        .map(|package_name| (package_name, false))
+        .collect::<Vec<_>>();
+    dependencies.extend(
+        config
+            .test_dependencies
+            .iter()
+            .filter(|package_name| !config.dependencies.contains(package_name))
+            .map(|package_name| (package_name, true)),
+    );
+    dependencies
+}
+
+/// e.g. `foo 1.2.0 (test)`
+fn describe_package(
+    package_name: &PackageName,
+    installed: &pkg::InstalledPackages,
+    is_test: bool,
+) -> String {
+    let mut description = package_name.as_str().to_string();
+    match installed.get(package_name) {
+        Some(package_config) => {
+            if let Some(version) = package_config.version.as_ref() {
+                description.push_str(&format!(" {}", version));
+            }
+        }
+        None => description.push_str(" (not installed)"),
+    }
+    if is_test {
+        description.push_str(" (test)");
+    }
+    description
+}
+
+/// Renders the dependency tree, one line per entry.
+///
+/// Packages that have already been expanded are marked with `(*)`.
+fn render_tree(config: &Config, installed: &pkg::InstalledPackages) -> Vec<String> {
+    let mut root = config.name.as_str().to_string();
+    if let Some(version) = config.version.as_ref() {
+        root.push_str(&format!(" {}", version));
+    }
+    let mut lines = vec![root];
+    let mut expanded = HashSet::new();
+    render_dependencies(
+        &root_dependencies(config),
+        installed,
+        "",
+        &mut expanded,
+        &mut lines,
+    );
+    return lines;
+
+    fn render_dependencies<'a>(
+        dependencies: &[(&'a PackageName, bool)],
+        installed: &'a pkg::InstalledPackages,
+        prefix: &str,
+        expanded: &mut HashSet<&'a PackageName>,
+        lines: &mut Vec<String>,
+    ) {
+        for (i, (package_name, is_test)) in dependencies.iter().enumerate() {
+            let is_last = i + 1 == dependencies.len();
+            let (branch, indent) = if is_last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let mut line = format!(
+                "{}{}{}",
+                prefix,
+                branch,
+                describe_package(package_name, installed, *is_test)
+            );
+            let package_config = installed.get(*package_name);
+            let has_dependencies = package_config.map_or(false, |package_config| {
+                !package_config.dependencies.is_empty()
+            });
+            if has_dependencies && !expanded.insert(*package_name) {
+                line.push_str(" (*)");
+                lines.push(line);
+                continue;
+            }
+            lines.push(line);
+            if let Some(package_config) = package_config {
+                let dependencies = package_config
+                    .dependencies
+                    .iter()
+                    .map(|package_name| (package_name, false))
+                    .collect::<Vec<_>>();
+                render_dependencies(
+                    &dependencies,
+                    installed,
+                    &format!("{}{}", prefix, indent),
+                    expanded,
+                    lines,
+                );
+            }
+        }
+    }
+}
+
+/// Every path from the root package to `target`, described package by package.
+fn find_paths(
+    config: &Config,
+    installed: &pkg::InstalledPackages,
+    target: &PackageName,
+) -> Vec<Vec<String>> {
+    let mut paths = Vec::new();
+    let mut path = vec![config.name.as_str().to_string()];
+    let mut on_path = HashSet::new();
+    for (package_name, is_test) in root_dependencies(config) {
+        walk(
+            package_name,
+            is_test,
+            installed,
+            target,
+            &mut path,
+            &mut on_path,
+            &mut paths,
+        );
+    }
+    return paths;
+
+    fn walk<'a>(
+        package_name: &'a PackageName,
+        is_test: bool,
+        installed: &'a pkg::InstalledPackages,
+        target: &PackageName,
+        path: &mut Vec<String>,
+        on_path: &mut HashSet<&'a PackageName>,
+        paths: &mut Vec<Vec<String>>,
+    ) {
+        // Don't go round in circles
+        if !on_path.insert(package_name) {
+            return;
+        }
+        path.push(describe_package(package_name, installed, is_test));
+        if package_name == target {
+            paths.push(path.clone());
+        } else if let Some(package_config) = installed.get(package_name) {
+            for dependency in package_config.dependencies.iter() {
+                walk(dependency, false, installed, target, path, on_path, paths);
+            }
+        }
+        path.pop();
+        on_path.remove(package_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_name(name: &str) -> PackageName {
+        PackageName::new_unchecked(name.to_string())
+    }
+
+    fn mk_config(name: &str, dependencies: &[&str], test_dependencies: &[&str]) -> Config {
+        let mut config = Config::new(package_name(name));
+        config.dependencies = dependencies.iter().map(|name| package_name(name)).collect();
+        config.test_dependencies = test_dependencies
+            .iter()
+            .map(|name| package_name(name))
+            .collect();
+        config
+    }
+
+    /// root -> a -> c, root -> b -> c -> d, root -(test)-> e, plus unused f
+    fn mk_project() -> (Config, pkg::InstalledPackages) {
+        let root = mk_config("root", &["a", "b"], &["e"]);
+        let mut installed = pkg::InstalledPackages::new();
+        for (name, dependencies) in [
+            ("a", vec!["c"]),
+            ("b", vec!["c"]),
+            ("c", vec!["d"]),
+            ("d", vec![]),
+            ("e", vec![]),
+            ("f", vec![]),
+        ] {
+            let mut config = mk_config(name, &dependencies, &[]);
+            if name == "a" {
+                config.version = Some(semver::Version::new(1, 0, 0));
+            }
+            installed.insert(package_name(name), config);
+        }
+        (root, installed)
+    }
+
+    #[test]
+    fn it_renders_dependency_trees() {
+        let (root, installed) = mk_project();
+        similar_asserts::assert_eq!(
+            render_tree(&root, &installed).join("\n"),
+            [
+                "root",
+                "├── a 1.0.0",
+                "│   └── c",
+                "│       └── d",
+                "├── b",
+                "│   └── c (*)",
+                "└── e (test)",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_finds_dependency_paths() {
+        let (root, installed) = mk_project();
+        let paths = find_paths(&root, &installed, &package_name("d"))
+            .into_iter()
+            .map(|path| path.join(" -> "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec!["root -> a 1.0.0 -> c -> d", "root -> b -> c -> d"]
+        );
+        assert!(find_paths(&root, &installed, &package_name("f")).is_empty());
+    }
+
+    #[test]
+    fn it_finds_unused_packages() {
+        let (root, installed) = mk_project();
+        assert_eq!(
+            pkg::find_unused_packages(&root, &installed),
+            vec![package_name("f")]
+        );
+    }
+}
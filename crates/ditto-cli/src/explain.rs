@@ -0,0 +1,28 @@
+use clap::{arg, ArgMatches, Command};
+use miette::{miette, Result};
+
+pub fn command(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Explain an error or warning code")
+        .arg(arg!(<CODE> "Code to explain, e.g. E0101"))
+}
+
+#[test]
+fn verify_cmd() {
+    command("explain").debug_assert();
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let code = matches.get_one::<String>("CODE").unwrap();
+    let explanation = ditto_cst::explain(code)
+        .or_else(|| ditto_checker::explain(code))
+        .ok_or_else(|| {
+            miette!(
+                help = "codes look like E0101 (errors) or W0001 (warnings)",
+                "unknown code: {}",
+                code
+            )
+        })?;
+    print!("{}", explanation);
+    Ok(())
+}
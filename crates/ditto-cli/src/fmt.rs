@@ -1,9 +1,11 @@
 use clap::{arg, error::ErrorKind, ArgMatches, Command};
+use ditto_config::{read_config, CONFIG_FILE_NAME};
+use ditto_make::find_ditto_files;
 use miette::{bail, IntoDiagnostic, Result, WrapErr};
 use std::{
     fs,
     io::{self, Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 pub fn command(name: impl Into<clap::builder::Str>) -> Command {
@@ -11,7 +13,8 @@ pub fn command(name: impl Into<clap::builder::Str>) -> Command {
         .about("Format ditto code")
         .arg(arg!(--check "Error if input(s) aren't formatted"))
         .arg(arg!(--stdin "Format stdin"))
-        .arg(arg!(paths: [PATH]... "Files to format")) // TODO: support globbing
+        .arg(arg!(paths: [PATH]... "Files to format (defaults to all project files)"))
+    // TODO: support globbing
 }
 
 #[test]
@@ -77,6 +80,11 @@ pub fn run(cmd: &mut Command, matches: &ArgMatches) -> Result<()> {
             }
         }
         Source::Paths(paths) => {
+            let paths = if paths.is_empty() {
+                project_paths()?
+            } else {
+                paths
+            };
             let mut exit_error = false;
             for path in paths {
                 if check {
@@ -109,6 +117,43 @@ pub fn run(cmd: &mut Command, matches: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Ditto files in the configured source and test directories of the current
+/// project, including workspace members.
+fn project_paths() -> Result<Vec<String>> {
+    let config = read_config(CONFIG_FILE_NAME)?;
+    let mut packages = match config.workspace {
+        Some(ref workspace) => workspace
+            .read_members(Path::new(""), &config)?
+            .into_iter()
+            .map(|member| (member.path, member.config))
+            .collect(),
+        None => Vec::new(),
+    };
+    packages.push((PathBuf::new(), config));
+
+    let mut paths = Vec::new();
+    for (dir, config) in packages {
+        for ditto_dir in [dir.join(config.src_dir), dir.join(config.test_dir)] {
+            if ditto_dir.exists() {
+                let ditto_files =
+                    find_ditto_files(&ditto_dir)
+                        .into_diagnostic()
+                        .wrap_err(format!(
+                            "error finding ditto files in {}",
+                            ditto_dir.to_string_lossy()
+                        ))?;
+                paths.extend(
+                    ditto_files
+                        .into_iter()
+                        .map(|path| path.to_string_lossy().into_owned()),
+                );
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
 fn fmt_inplace<P: AsRef<Path>>(path: P) -> Result<()> {
     let (formatted, unformatted) = fmt_path(&path)?;
 
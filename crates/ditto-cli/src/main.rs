@@ -1,11 +1,18 @@
 mod bootstrap;
+mod cache;
 mod common;
+mod dependencies;
+mod deps;
+mod explain;
 mod fmt;
 mod lsp;
 mod make;
 mod ninja;
 mod pkg;
+mod schema;
 mod spinner;
+mod update;
+mod vendor;
 mod version;
 
 use clap::{
@@ -21,9 +28,18 @@ use version::Version;
 
 static SUBCOMMAND_BOOTSTRAP: &str = "bootstrap";
 static SUBCOMMAND_MAKE: &str = "make";
+static SUBCOMMAND_CHECK: &str = "check";
 static SUBCOMMAND_FMT: &str = "fmt";
 static SUBCOMMAND_LSP: &str = "lsp";
 static SUBCOMMAND_NINJA: &str = "ninja";
+static SUBCOMMAND_CACHE: &str = "cache";
+static SUBCOMMAND_EXPLAIN: &str = "explain";
+static SUBCOMMAND_UPDATE: &str = "update";
+static SUBCOMMAND_ADD: &str = "add";
+static SUBCOMMAND_REMOVE: &str = "remove";
+static SUBCOMMAND_DEPS: &str = "deps";
+static SUBCOMMAND_VENDOR: &str = "vendor";
+static SUBCOMMAND_SCHEMA: &str = "schema";
 
 fn command(
     version_short: impl IntoResettable<Str>,
@@ -38,8 +54,18 @@ fn command(
         .about("putting the fun in functional")
         .subcommand(bootstrap::command(SUBCOMMAND_BOOTSTRAP).display_order(0))
         .subcommand(make::command(SUBCOMMAND_MAKE).display_order(1))
-        .subcommand(fmt::command(SUBCOMMAND_FMT).display_order(2))
-        .subcommand(lsp::command(SUBCOMMAND_LSP).display_order(3))
+        .subcommand(make::command_check(SUBCOMMAND_CHECK).display_order(2))
+        .subcommand(fmt::command(SUBCOMMAND_FMT).display_order(3))
+        .subcommand(lsp::command(SUBCOMMAND_LSP).display_order(4))
+        .subcommand(cache::command(SUBCOMMAND_CACHE).display_order(5))
+        .subcommand(explain::command(SUBCOMMAND_EXPLAIN).display_order(6))
+        .subcommand(update::command(SUBCOMMAND_UPDATE).display_order(7))
+        .subcommand(dependencies::command_add(SUBCOMMAND_ADD).display_order(8))
+        .subcommand(dependencies::command_remove(SUBCOMMAND_REMOVE).display_order(9))
+        .subcommand(deps::command(SUBCOMMAND_DEPS).display_order(10))
+        .subcommand(vendor::command(SUBCOMMAND_VENDOR).display_order(11))
+        // for editor integrations
+        .subcommand(schema::command(SUBCOMMAND_SCHEMA).hide(true))
         // internals!
         .subcommand(ninja::command(SUBCOMMAND_NINJA).hide(true))
         .subcommand(ditto_make::command_compile(make::COMPILE_SUBCOMMAND).hide(true))
@@ -50,8 +76,26 @@ async fn run(mut cmd: Command, matches: &ArgMatches, version: &Version) -> Resul
         ditto_make::run_compile(matches)
     } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_MAKE) {
         make::run(matches, version).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_CHECK) {
+        make::run_check(matches, version).await
     } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_LSP) {
         lsp::run(matches, version).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_CACHE) {
+        cache::run(matches)
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_EXPLAIN) {
+        explain::run(matches)
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_UPDATE) {
+        update::run(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_ADD) {
+        dependencies::run_add(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_REMOVE) {
+        dependencies::run_remove(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_DEPS) {
+        deps::run(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_VENDOR) {
+        vendor::run(matches).await
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_SCHEMA) {
+        schema::run(matches)
     } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_NINJA) {
         ninja::run(matches).await
     } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_FMT) {
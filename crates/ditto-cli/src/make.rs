@@ -1,10 +1,11 @@
-use crate::{common, ninja::get_ninja_exe, pkg, spinner::Spinner, version::Version};
+use crate::{cache, common, ninja::get_ninja_exe, pkg, spinner::Spinner, version::Version};
 use clap::{arg, ArgMatches, Command};
 use console::{Emoji, Style};
 use ditto_config::{read_config, Config, PackageName, CONFIG_FILE_NAME};
+use ditto_cst as cst;
 use ditto_make::{self as make, BuildNinja, GetWarnings, PackageSources, Sources};
 use fs2::FileExt;
-use miette::{IntoDiagnostic, Result, WrapErr};
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
 use notify::Watcher;
 use std::{
     collections::HashMap,
@@ -12,19 +13,51 @@ use std::{
     fs,
     io::{BufRead, BufReader, Write},
     path::{Path, PathBuf},
-    process::{self, ExitStatus, Stdio},
+    process::{self, Stdio},
     time::{Duration, Instant},
 };
 use tracing::{debug, trace};
 
 pub static COMPILE_SUBCOMMAND: &str = "compile";
 
+static EXECUTOR_NINJA: &str = "ninja";
+static EXECUTOR_BUILTIN: &str = "builtin";
+
 pub fn command(name: impl Into<clap::builder::Str>) -> Command {
     Command::new(name)
         .about("Build a project")
         .arg(arg!(-w --watch "Watch files for changes"))
         .arg(arg!(--"no-tests" "Ignore test modules and dependencies"))
+        .arg(arg!(--locked "Fail if ditto.lock is missing or out of date"))
+        .arg(arg!(--offline "Don't download packages, use cached or vendored ones"))
         .arg(arg!(execs: --exec <CMD> ... "Shell command to run on success"))
+        .arg(
+            arg!(--executor <EXECUTOR> "Build executor")
+                .value_parser([EXECUTOR_NINJA, EXECUTOR_BUILTIN])
+                .default_value(EXECUTOR_NINJA),
+        )
+        .arg(arg!(--batch "Check each package in a single process"))
+        .arg(arg!(--"no-cache" "Don't use the shared build cache"))
+        .arg(arg!(--"deny-warnings" "Fail the build if there are any warnings"))
+        .arg(arg!(--timings "Write a report of how long each module took to build"))
+        .arg(
+            arg!(--graph <FMT> "Print the module graph (dot or json) instead of building")
+                .value_parser(["dot", "json"])
+                .hide_possible_values(true),
+        )
+        .arg(
+            arg!(--"graph-package" <NAME> "Only graph modules from this package").requires("graph"),
+        )
+        .arg(
+            arg!(--"graph-module" <NAME> "Only graph this module and its dependencies")
+                .requires("graph"),
+        )
+        .arg(
+            arg!(--"message-format" <FMT> "Diagnostics format: human, json or sarif")
+                .value_parser(make::MessageFormat::VALUES)
+                .hide_possible_values(true)
+                .default_value("human"),
+        )
         // Useful for debugging why watches are/aren't triggering.
         // Should remove it eventually.
         .arg(arg!(--"debug-watcher").hide(true))
@@ -35,40 +68,176 @@ fn verify_cmd() {
     command("make").debug_assert();
 }
 
+pub fn command_check(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Check a project for errors, without generating code")
+        .arg(arg!(--"no-tests" "Ignore test modules and dependencies"))
+        .arg(arg!(--locked "Fail if ditto.lock is missing or out of date"))
+        .arg(arg!(--offline "Don't download packages, use cached or vendored ones"))
+        .arg(
+            arg!(--executor <EXECUTOR> "Build executor")
+                .value_parser([EXECUTOR_NINJA, EXECUTOR_BUILTIN])
+                .default_value(EXECUTOR_NINJA),
+        )
+        .arg(arg!(--batch "Check each package in a single process"))
+        .arg(arg!(--"no-cache" "Don't use the shared build cache"))
+        .arg(arg!(--"deny-warnings" "Fail if there are any warnings"))
+        .arg(
+            arg!(--"message-format" <FMT> "Diagnostics format: human, json or sarif")
+                .value_parser(make::MessageFormat::VALUES)
+                .hide_possible_values(true)
+                .default_value("human"),
+        )
+}
+
+#[test]
+fn verify_cmd_check() {
+    command_check("check").debug_assert();
+}
+
 #[derive(Clone)]
 struct Args {
     watch: bool,
     no_tests: bool,
+    locked: bool,
+    offline: bool,
     debug_watcher: bool,
     execs: Execs,
+    executor: Executor,
+    batch: bool,
+    no_cache: bool,
+    deny_warnings: bool,
+    timings: bool,
+    graph: Option<GraphArgs>,
+    /// Only run the `ast` builds, see [ditto_make::BuildNinja::check_only].
+    check_only: bool,
+    message_format: make::MessageFormat,
 }
 type Execs = Vec<String>;
 
+impl Args {
+    fn lock_mode(&self) -> pkg::LockMode {
+        if self.locked {
+            pkg::LockMode::Locked
+        } else {
+            pkg::LockMode::Normal
+        }
+    }
+}
+
+/// `--graph` options.
+#[derive(Clone)]
+struct GraphArgs {
+    /// `"dot"` or `"json"`.
+    format: String,
+    package: Option<String>,
+    module: Option<String>,
+}
+
+/// What runs the generated build graph.
+#[derive(Clone, Copy)]
+enum Executor {
+    /// Write a `build.ninja` file and run `ninja`.
+    Ninja,
+    /// Run the build graph ourselves, see [make::execute_build_ninja].
+    Builtin,
+}
+
 fn matches_to_args(matches: &ArgMatches) -> Args {
     let watch = matches.get_flag("watch");
     let no_tests = matches.get_flag("no-tests");
+    let locked = matches.get_flag("locked");
+    let offline = matches.get_flag("offline");
     let debug_watcher = matches.get_flag("debug-watcher");
     let execs = matches
         .get_many::<String>("execs")
         .unwrap_or_default()
         .cloned()
         .collect::<Vec<_>>();
+    let batch = matches.get_flag("batch");
+    let no_cache = matches.get_flag("no-cache");
+    let deny_warnings = matches.get_flag("deny-warnings");
+    let timings = matches.get_flag("timings");
+    let graph = matches.get_one::<String>("graph").map(|format| GraphArgs {
+        format: format.to_owned(),
+        package: matches.get_one::<String>("graph-package").cloned(),
+        module: matches.get_one::<String>("graph-module").cloned(),
+    });
+    let executor = matches_to_executor(matches);
+    let message_format = matches_to_message_format(matches);
 
     Args {
         watch,
         no_tests,
+        locked,
+        offline,
         debug_watcher,
         execs,
+        executor,
+        batch,
+        no_cache,
+        deny_warnings,
+        timings,
+        graph,
+        check_only: false,
+        message_format,
+    }
+}
+
+fn matches_to_check_args(matches: &ArgMatches) -> Args {
+    Args {
+        watch: false,
+        no_tests: matches.get_flag("no-tests"),
+        locked: matches.get_flag("locked"),
+        offline: matches.get_flag("offline"),
+        debug_watcher: false,
+        execs: Vec::new(),
+        executor: matches_to_executor(matches),
+        batch: matches.get_flag("batch"),
+        no_cache: matches.get_flag("no-cache"),
+        deny_warnings: matches.get_flag("deny-warnings"),
+        timings: false,
+        graph: None,
+        check_only: true,
+        message_format: matches_to_message_format(matches),
+    }
+}
+
+fn matches_to_executor(matches: &ArgMatches) -> Executor {
+    if matches.get_one::<String>("executor").map(String::as_str) == Some(EXECUTOR_BUILTIN) {
+        Executor::Builtin
+    } else {
+        Executor::Ninja
     }
 }
 
+fn matches_to_message_format(matches: &ArgMatches) -> make::MessageFormat {
+    matches
+        .get_one::<String>("message-format")
+        .and_then(|value| make::MessageFormat::parse(value))
+        .unwrap_or_default()
+}
+
 pub async fn run(matches: &ArgMatches, ditto_version: &Version) -> Result<()> {
     let args = matches_to_args(matches);
 
     // Read the ditto.toml immediately,
     // failing early if it's not present.
     let config_path: PathBuf = [".", CONFIG_FILE_NAME].iter().collect();
-    let config = read_config(&config_path)?;
+    let config = pkg::read_project_config(&config_path, Some(&ditto_version.semversion))?;
+
+    if let Some(ref graph_args) = args.graph {
+        return print_module_graph(
+            graph_args,
+            !args.no_tests,
+            args.lock_mode(),
+            args.offline,
+            ditto_version,
+            &config_path,
+            &config,
+        )
+        .await;
+    }
 
     if args.watch {
         run_watch(&args, ditto_version, &config_path, config).await
@@ -78,7 +247,10 @@ pub async fn run(matches: &ArgMatches, ditto_version: &Version) -> Result<()> {
         if !what_happened.is_error() {
             // Print how long this run took.
             // Unless this is a test (because the timing isn't deterministic)
-            if std::env::var("DITTO_TEST_VERSION").is_err() {
+            // or someone is parsing our output
+            if std::env::var("DITTO_TEST_VERSION").is_err()
+                && !args.message_format.is_machine_readable()
+            {
                 println!(
                     "{}{}",
                     Emoji::new("✨ ", ""),
@@ -94,6 +266,21 @@ pub async fn run(matches: &ArgMatches, ditto_version: &Version) -> Result<()> {
     }
 }
 
+/// Like [run] but without code generation.
+///
+/// Shares build artifacts with `ditto make`, so a subsequent build only
+/// needs to generate code.
+pub async fn run_check(matches: &ArgMatches, ditto_version: &Version) -> Result<()> {
+    let args = matches_to_check_args(matches);
+
+    let config_path: PathBuf = [".", CONFIG_FILE_NAME].iter().collect();
+    let config = pkg::read_project_config(&config_path, Some(&ditto_version.semversion))?;
+
+    let (what_happened, _duration) =
+        run_once(&args, ditto_version, &config_path, &config, true).await?;
+    what_happened.exit()
+}
+
 async fn run_watch(
     args: &Args,
     ditto_version: &Version,
@@ -118,10 +305,12 @@ async fn run_watch(
         )
         .into_diagnostic()?;
 
-    // watch `./src` (should really be present)
-    watcher
-        .watch(&config.src_dir, notify::RecursiveMode::Recursive)
-        .into_diagnostic()?;
+    // watch `./src` (should really be present, unless this is a workspace root)
+    if config.workspace.is_none() || config.src_dir.exists() {
+        watcher
+            .watch(&config.src_dir, notify::RecursiveMode::Recursive)
+            .into_diagnostic()?;
+    }
 
     // watch `./tests` (if it's present)
     if config.test_dir.exists() {
@@ -130,6 +319,24 @@ async fn run_watch(
             .into_diagnostic()?;
     }
 
+    // watch the `ditto.toml` and `src` of workspace members
+    if let Some(ref workspace) = config.workspace {
+        for member in workspace.read_members(Path::new(""), &config)? {
+            watcher
+                .watch(
+                    &member.path.join(CONFIG_FILE_NAME),
+                    notify::RecursiveMode::NonRecursive,
+                )
+                .into_diagnostic()?;
+            watcher
+                .watch(
+                    &member.path.join(&member.config.src_dir),
+                    notify::RecursiveMode::Recursive,
+                )
+                .into_diagnostic()?;
+        }
+    }
+
     // TODO: allow watching more files via config or a flag?
 
     let (run_sender, run_receiver) = crossbeam_channel::bounded::<(Config, bool)>(1);
@@ -183,7 +390,7 @@ async fn run_watch(
                 // If the config file was touched,
                 // then update the `config` value..
                 if config_file_changed {
-                    match read_config(config_path) {
+                    match pkg::read_project_config(config_path, Some(&ditto_version.semversion)) {
                         Ok(latest_config) => {
                             config = latest_config;
                         }
@@ -306,26 +513,22 @@ enum WhatHappened {
     ///
     /// Warnings may have been printed, though.
     Nothing {
-        ninja_exit_status: ExitStatus,
+        exit_code: i32,
         warnings_printed: bool,
     },
-    /// Ninja had a non-zero exit status.
+    /// The build failed, e.g. ninja had a non-zero exit status.
     ///
     /// Errors will have been printed.
-    Error { ninja_exit_status: ExitStatus },
-    /// Ninja ran successfully. Warnings may have been printed.
+    Error { exit_code: i32 },
+    /// The build ran successfully. Warnings may have been printed.
     Success { warnings_printed: bool },
 }
 
 impl WhatHappened {
     fn exit(self) -> ! {
         match self {
-            Self::Nothing {
-                ninja_exit_status, ..
-            } => process::exit(ninja_exit_status.code().unwrap_or(0)),
-            Self::Error {
-                ninja_exit_status, ..
-            } => process::exit(ninja_exit_status.code().unwrap_or(0)),
+            Self::Nothing { exit_code, .. } => process::exit(exit_code),
+            Self::Error { exit_code, .. } => process::exit(exit_code),
             Self::Success { .. } => process::exit(0),
         }
     }
@@ -352,15 +555,21 @@ async fn run_once(
     // Install/remove packages as needed
     // (this is a nicer pattern than requiring a run of a separate CLI command, IMO)
     if install_packages && !config.dependencies.is_empty() {
-        pkg::check_packages_up_to_date(config, include_test_stuff)
-            .await
-            .wrap_err("error checking packages are up to date")?;
+        pkg::check_packages_up_to_date(
+            config_path,
+            config,
+            include_test_stuff,
+            args.lock_mode(),
+            args.offline,
+        )
+        .await
+        .wrap_err("error checking packages are up to date")?;
     }
 
     let now = Instant::now(); // for timing
 
     // Do the thing
-    let result = make(config_path, config, ditto_version, include_test_stuff).await;
+    let result = make(args, config_path, config, ditto_version).await;
 
     let duration = now.elapsed();
 
@@ -371,6 +580,58 @@ async fn run_once(
     result.map(|what_happened| (what_happened, duration))
 }
 
+/// Print the module graph rather than building.
+async fn print_module_graph(
+    graph_args: &GraphArgs,
+    include_test_stuff: bool,
+    lock_mode: pkg::LockMode,
+    offline: bool,
+    ditto_version: &Version,
+    config_path: &Path,
+    config: &Config,
+) -> Result<()> {
+    let (sources, package_sources) = {
+        let lock = acquire_lock(config)?;
+        if !config.dependencies.is_empty() {
+            pkg::check_packages_up_to_date(
+                config_path,
+                config,
+                include_test_stuff,
+                lock_mode,
+                offline,
+            )
+            .await
+            .wrap_err("error checking packages are up to date")?;
+        }
+        let result = get_sources(config_path, config, include_test_stuff);
+        lock.unlock()
+            .expect("Error releasing lock on build directory");
+        result?
+    };
+
+    let mut graph =
+        make::generate_module_graph(&ditto_version.semversion, sources, package_sources)?;
+    if let Some(ref module) = graph_args.module {
+        graph = graph.filter_closure(module).ok_or_else(|| {
+            miette!(
+                help = "modules from packages are written as `package-name:Module.Name`",
+                "module `{}` not found",
+                module
+            )
+        })?;
+    }
+    if let Some(ref package) = graph_args.package {
+        graph = graph.filter_package(package);
+    }
+
+    if graph_args.format == "json" {
+        println!("{}", graph.to_json());
+    } else {
+        print!("{}", graph.to_dot());
+    }
+    Ok(())
+}
+
 fn run_execs(execs: &Execs) {
     let mut execs = execs.iter();
     while let Some(exec) = execs.next() {
@@ -427,30 +688,120 @@ fn run_execs(execs: &Execs) {
 
 /// If successful returns the exit status of `ninja` and whether anything actually happened.
 async fn make(
+    args: &Args,
     config_path: &Path,
     config: &Config,
     ditto_version: &Version,
-    include_test_sources: bool,
 ) -> Result<WhatHappened> {
-    let (build_ninja, get_warnings) =
-        generate_build_ninja(config_path, config, ditto_version, include_test_sources).map_err(
-            |err| {
-                // This is a bit brittle, but we want parse errors encountered during
-                // build planning to be indistinguishable from parse errors encountered
-                // during the actual build
-                if err.root_cause().to_string() == "syntax error" {
-                    //                                  ^^ BEWARE relying on this string is brittle!
-                    err
-                } else {
-                    err.wrap_err("error generating build.ninja")
-                }
-            },
-        )?;
+    let result = if !args.message_format.is_machine_readable() {
+        build(args, config_path, config, ditto_version, None).await
+    } else {
+        let mut diagnostics = Diagnostics::default();
+        let result = build(
+            args,
+            config_path,
+            config,
+            ditto_version,
+            Some(&mut diagnostics),
+        )
+        .await;
+        let what_happened = result.unwrap_or_else(|err| {
+            diagnostics.push_report(&err);
+            WhatHappened::Error { exit_code: 1 }
+        });
+        diagnostics.print(args.message_format, ditto_version);
+        Ok(what_happened)
+    };
+    if args.timings {
+        write_timings_report(config, !args.message_format.is_machine_readable())?;
+    }
+    result
+}
+
+/// Where `--timings` reports are written.
+fn get_timings_dir(config: &Config) -> PathBuf {
+    let mut timings_dir = config.ditto_dir.to_path_buf();
+    timings_dir.push("timings");
+    timings_dir
+}
+
+/// Where `ditto compile` processes record their timings, see [make::ENV_TIMINGS_DIR].
+fn get_timings_edges_dir(config: &Config) -> PathBuf {
+    let mut edges_dir = get_timings_dir(config);
+    edges_dir.push("edges");
+    edges_dir
+}
+
+/// Aggregate the timings recorded during a build into HTML and JSON reports.
+fn write_timings_report(config: &Config, print_location: bool) -> Result<()> {
+    let report = make::collect_timings(&get_timings_edges_dir(config))
+        .wrap_err("error collecting build timings")?;
+
+    let timings_dir = get_timings_dir(config);
+    fs::create_dir_all(&timings_dir)
+        .into_diagnostic()
+        .wrap_err(format!(
+            "error creating {:?}",
+            timings_dir.to_string_lossy()
+        ))?;
+    let html_path = timings_dir.join("ditto-timings.html");
+    fs::write(&html_path, report.to_html())
+        .into_diagnostic()
+        .wrap_err(format!("error writing {:?}", html_path.to_string_lossy()))?;
+
+    let json_path = timings_dir.join("ditto-timings.json");
+    let json = serde_json::to_string_pretty(&report).into_diagnostic()?;
+    fs::write(&json_path, json)
+        .into_diagnostic()
+        .wrap_err(format!("error writing {:?}", json_path.to_string_lossy()))?;
+
+    if print_location {
+        println!("Timing report saved to {}", html_path.to_string_lossy());
+    }
+    Ok(())
+}
+
+/// Does the actual work for [make].
+///
+/// If `diagnostics` are given then errors and warnings are collected there
+/// rather than being printed.
+async fn build(
+    args: &Args,
+    config_path: &Path,
+    config: &Config,
+    ditto_version: &Version,
+    mut diagnostics: Option<&mut Diagnostics>,
+) -> Result<WhatHappened> {
+    let (build_ninja, get_warnings) = generate_build_ninja(
+        config_path,
+        config,
+        ditto_version,
+        !args.no_tests,
+        args.batch,
+        args.deny_warnings,
+    )
+    .map_err(|err| {
+        // We want parse errors encountered during build planning to be
+        // indistinguishable from parse errors encountered during the actual build
+        if err.downcast_ref::<cst::ParseErrorReport>().is_some() {
+            err
+        } else {
+            err.wrap_err("error generating build.ninja")
+        }
+    })?;
+    let build_ninja = if args.check_only {
+        build_ninja.check_only()
+    } else {
+        build_ninja
+    };
 
     trace!("build.ninja generated");
 
+    // NOTE: a separate file for `ditto check` so that switching back and forth
+    // doesn't look like a change to the build graph. Both files share the same
+    // `builddir`, and hence the same `.ninja_log`.
     let mut build_ninja_path = config.ditto_dir.to_path_buf();
-    build_ninja_path.push("build");
+    build_ninja_path.push(if args.check_only { "check" } else { "build" });
     build_ninja_path.set_extension("ninja");
 
     {
@@ -471,7 +822,7 @@ async fn make(
             ))?;
 
         handle
-            .write_all(build_ninja.into_syntax().as_bytes())
+            .write_all(build_ninja.clone().into_syntax().as_bytes())
             .into_diagnostic()
             .wrap_err(format!(
                 "error writing {:?}",
@@ -484,6 +835,50 @@ async fn make(
         );
     }
 
+    // Environment for the `ditto compile` calls
+    let mut envs = vec![
+        // Pass `is_plain` logic down
+        ("DITTO_PLAIN".to_string(), common::is_plain().to_string()),
+    ];
+    // Pass the log file location down too
+    if let Ok(log_file) = std::env::var("DITTO_LOG_FILE") {
+        envs.push(("DITTO_LOG_FILE".to_string(), log_file));
+    }
+    envs.push((
+        make::ENV_MESSAGE_FORMAT.to_string(),
+        args.message_format.as_str().to_string(),
+    ));
+    // NOTE: an empty directory disables the cache, rather than leaving
+    // `ditto compile` to pick up an inherited DITTO_BUILD_CACHE_DIR
+    let build_cache_dir = if args.no_cache {
+        String::new()
+    } else {
+        cache::get_build_cache_dir(ditto_version)?
+            .to_string_lossy()
+            .into_owned()
+    };
+    envs.push((make::ENV_BUILD_CACHE_DIR.to_string(), build_cache_dir));
+    if args.timings {
+        // Start afresh, we only want timings for this build
+        let edges_dir = get_timings_edges_dir(config);
+        if edges_dir.exists() {
+            fs::remove_dir_all(&edges_dir)
+                .into_diagnostic()
+                .wrap_err(format!("error removing {:?}", edges_dir.to_string_lossy()))?;
+        }
+        fs::create_dir_all(&edges_dir)
+            .into_diagnostic()
+            .wrap_err(format!("error creating {:?}", edges_dir.to_string_lossy()))?;
+        envs.push((
+            make::ENV_TIMINGS_DIR.to_string(),
+            edges_dir.to_string_lossy().into_owned(),
+        ));
+    }
+
+    if let Executor::Builtin = args.executor {
+        return execute_build_ninja(config, &build_ninja, &envs, get_warnings, diagnostics);
+    }
+
     static NINJA_STATUS_MESSAGE: &str = "__NINJA";
 
     let ninja_exe = get_ninja_exe().await?;
@@ -496,14 +891,7 @@ async fn make(
         // Don't strip color codes, we'll handle that
         // https://github.com/ninja-build/ninja/commit/bf7107bb864d0383028202e3f4a4228c02302961
         .env("CLICOLOR_FORCE", "1")
-        // Pass `is_plain` logic down to CLI calls made by ninja
-        .env("DITTO_PLAIN", common::is_plain().to_string())
-        // Pass the log file location down too
-        .envs(if let Ok(log_file) = std::env::var("DITTO_LOG_FILE") {
-            vec![("DITTO_LOG_FILE", log_file)]
-        } else {
-            vec![]
-        })
+        .envs(envs)
         .spawn()
         .into_diagnostic()
         .wrap_err(format!(
@@ -522,7 +910,7 @@ async fn make(
             // Nothing to do,
             // still need to print warnings though
             let warnings = get_warnings()?;
-            let warnings_printed = print_warnings(warnings);
+            let warnings_printed = report_warnings(warnings, diagnostics);
 
             let ninja_exit_status = child
                 .wait()
@@ -530,16 +918,20 @@ async fn make(
                 .wrap_err("ninja wasn't running?")?;
 
             return Ok(WhatHappened::Nothing {
-                ninja_exit_status,
+                exit_code: ninja_exit_status.code().unwrap_or(0),
                 warnings_printed,
             });
         } else {
-            let mut spinner = Spinner::new();
-            spinner.set_message(
-                first_line
-                    .trim_start_matches(NINJA_STATUS_MESSAGE)
-                    .to_owned(),
-            );
+            // No spinner if we're collecting diagnostics, as it would get
+            // mixed up with the structured output
+            let mut spinner = diagnostics.is_none().then(Spinner::new);
+            if let Some(ref mut spinner) = spinner {
+                spinner.set_message(
+                    first_line
+                        .trim_start_matches(NINJA_STATUS_MESSAGE)
+                        .to_owned(),
+                );
+            }
 
             // Our error/warning reports generally start with a blank line,
             // so we need to replicate that behavior when forwarding ninja
@@ -547,49 +939,186 @@ async fn make(
             let mut printed_initial_newline = false;
             while let Some(Ok(line)) = stdout_lines.next() {
                 if line.starts_with(NINJA_STATUS_MESSAGE) {
-                    spinner.set_message(line.trim_start_matches(NINJA_STATUS_MESSAGE).to_owned());
+                    if let Some(ref mut spinner) = spinner {
+                        spinner
+                            .set_message(line.trim_start_matches(NINJA_STATUS_MESSAGE).to_owned());
+                    }
                 } else if line.starts_with("ninja: build stopped: subcommand failed") {
                 } else if console::strip_ansi_codes(&line).starts_with("FAILED") {
                     // The following line prints the command that was run (and failed)
                     // so swallow it
                     stdout_lines.next();
-                } else {
+                } else if let Some(ref mut spinner) = spinner {
                     if !printed_initial_newline {
                         spinner.println("\n");
                         printed_initial_newline = true
                     }
                     spinner.println(line);
+                } else if let Some(diagnostics) = diagnostics.as_deref_mut() {
+                    diagnostics.push_line(&line);
                 }
             }
 
             let ninja_exit_status = child.wait().expect("error waiting for ninja to exit");
-            spinner.finish();
+            if let Some(spinner) = spinner {
+                spinner.finish();
+            }
             if ninja_exit_status.success() {
                 // Only print warnings if there wasn't an error
                 let warnings = get_warnings()?;
-                let warnings_printed = print_warnings(warnings);
+                let warnings_printed = report_warnings(warnings, diagnostics);
                 return Ok(WhatHappened::Success { warnings_printed });
             } else {
-                return Ok(WhatHappened::Error { ninja_exit_status });
+                return Ok(WhatHappened::Error {
+                    exit_code: ninja_exit_status.code().unwrap_or(0),
+                });
             }
         }
     } else {
         unreachable!()
     }
+}
 
-    fn print_warnings(warnings: Vec<miette::Report>) -> bool {
-        if !warnings.is_empty() {
-            let warnings_len = warnings.len();
-            for (i, warning) in warnings.into_iter().enumerate() {
-                if i == warnings_len - 1 {
-                    eprintln!("{:?}", warning);
-                } else {
-                    eprint!("{:?}", warning);
+/// Like [make] but using the builtin executor rather than `ninja`.
+fn execute_build_ninja(
+    config: &Config,
+    build_ninja: &BuildNinja,
+    envs: &[(String, String)],
+    get_warnings: GetWarnings,
+    mut diagnostics: Option<&mut Diagnostics>,
+) -> Result<WhatHappened> {
+    let mut build_log_path = config.ditto_dir.to_path_buf();
+    build_log_path.push(BUILD_LOG_FILE);
+
+    let mut spinner: Option<Spinner> = None;
+    // See the equivalent in `build`
+    let mut printed_initial_newline = false;
+    let outcome = make::execute_build_ninja(build_ninja, &build_log_path, envs, |event| {
+        if let Some(diagnostics) = diagnostics.as_deref_mut() {
+            if let make::Event::Output { output } = event {
+                for line in output.lines() {
+                    diagnostics.push_line(line);
+                }
+            }
+            return;
+        }
+        let spinner = spinner.get_or_insert_with(Spinner::new);
+        match event {
+            make::Event::Started { description, .. } => spinner.set_message(description.to_owned()),
+            make::Event::Output { output } => {
+                if !printed_initial_newline {
+                    spinner.println("\n");
+                    printed_initial_newline = true
+                }
+                for line in output.trim_end().lines() {
+                    spinner.println(line);
                 }
             }
-            true
+        }
+    })?;
+    if let Some(spinner) = spinner {
+        spinner.finish();
+    }
+
+    match outcome {
+        make::Outcome::NoWorkToDo => {
+            let warnings = get_warnings()?;
+            let warnings_printed = report_warnings(warnings, diagnostics);
+            Ok(WhatHappened::Nothing {
+                exit_code: 0,
+                warnings_printed,
+            })
+        }
+        make::Outcome::Success => {
+            let warnings = get_warnings()?;
+            let warnings_printed = report_warnings(warnings, diagnostics);
+            Ok(WhatHappened::Success { warnings_printed })
+        }
+        make::Outcome::Failed => Ok(WhatHappened::Error { exit_code: 1 }),
+    }
+}
+
+static BUILD_LOG_FILE: &str = "build_log";
+
+/// Print warnings, or collect them if `diagnostics` are given.
+fn report_warnings(warnings: Vec<miette::Report>, diagnostics: Option<&mut Diagnostics>) -> bool {
+    if let Some(diagnostics) = diagnostics {
+        let warnings_collected = !warnings.is_empty();
+        for warning in warnings {
+            diagnostics.push_report(&warning);
+        }
+        return warnings_collected;
+    }
+    print_warnings(warnings)
+}
+
+fn print_warnings(warnings: Vec<miette::Report>) -> bool {
+    if !warnings.is_empty() {
+        let warnings_len = warnings.len();
+        for (i, warning) in warnings.into_iter().enumerate() {
+            if i == warnings_len - 1 {
+                eprintln!("{:?}", warning);
+            } else {
+                eprint!("{:?}", warning);
+            }
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// Diagnostics collected for a machine readable [make::MessageFormat].
+#[derive(Default)]
+struct Diagnostics {
+    records: Vec<make::DiagnosticRecord>,
+    /// Output that wasn't a [make::DiagnosticRecord], e.g. a panic message.
+    unstructured: Vec<String>,
+}
+
+impl Diagnostics {
+    /// Handle a line of output from a build command.
+    fn push_line(&mut self, line: &str) {
+        if let Some(record) = make::DiagnosticRecord::from_json(line) {
+            self.flush_unstructured();
+            self.records.push(record);
         } else {
-            false
+            let line = console::strip_ansi_codes(line);
+            if !line.trim().is_empty() {
+                self.unstructured.push(line.into_owned());
+            }
+        }
+    }
+
+    fn push_report(&mut self, report: &miette::Report) {
+        self.flush_unstructured();
+        self.records
+            .push(make::DiagnosticRecord::from_diagnostic(&**report));
+    }
+
+    /// Consecutive unstructured lines are treated as a single error.
+    fn flush_unstructured(&mut self) {
+        if !self.unstructured.is_empty() {
+            let message = self.unstructured.join("\n");
+            self.unstructured.clear();
+            self.records
+                .push(make::DiagnosticRecord::from_message(message));
+        }
+    }
+
+    fn print(mut self, message_format: make::MessageFormat, ditto_version: &Version) {
+        self.flush_unstructured();
+        match message_format {
+            make::MessageFormat::Human => unreachable!(),
+            make::MessageFormat::Json => {
+                for record in self.records {
+                    println!("{}", record.to_json());
+                }
+            }
+            make::MessageFormat::Sarif => {
+                let sarif = make::to_sarif(&self.records, &ditto_version.semversion.to_string());
+                println!("{}", serde_json::to_string_pretty(&sarif).unwrap());
+            }
         }
     }
 }
@@ -600,6 +1129,8 @@ fn generate_build_ninja(
     config: &Config,
     ditto_version: &Version,
     include_test_sources: bool,
+    batch: bool,
+    deny_warnings: bool,
 ) -> Result<(BuildNinja, GetWarnings)> {
     let mut build_dir = config.ditto_dir.to_path_buf();
     build_dir.push("build");
@@ -613,7 +1144,36 @@ fn generate_build_ninja(
             .wrap_err("error getting current executable")?
     };
 
-    let mut ditto_files = find_ditto_files(&config.src_dir)?; // ditto-src
+    let (sources, package_sources) = get_sources(config_path, config, include_test_sources)?;
+
+    make::generate_build_ninja(
+        build_dir,
+        ditto_bin,
+        &ditto_version.semversion,
+        COMPILE_SUBCOMMAND,
+        if batch {
+            make::CompileMode::Batch
+        } else {
+            make::CompileMode::PerModule
+        },
+        deny_warnings,
+        sources,
+        package_sources,
+    )
+}
+
+/// Find the ditto files for the current package and its installed dependencies.
+fn get_sources(
+    config_path: &Path,
+    config: &Config,
+    include_test_sources: bool,
+) -> Result<(Sources, PackageSources)> {
+    // A workspace root doesn't need any modules of its own
+    let mut ditto_files = if config.workspace.is_some() && !config.src_dir.exists() {
+        Vec::new()
+    } else {
+        find_ditto_files(&config.src_dir)? // ditto-src
+    };
     if include_test_sources && config.test_dir.exists() {
         ditto_files.extend(find_ditto_files(&config.test_dir)?); // ditto-test
     }
@@ -626,14 +1186,7 @@ fn generate_build_ninja(
     let package_sources =
         get_package_sources(config).wrap_err("error finding ditto files in packages")?;
 
-    make::generate_build_ninja(
-        build_dir,
-        ditto_bin,
-        &ditto_version.semversion,
-        COMPILE_SUBCOMMAND,
-        sources,
-        package_sources,
-    )
+    Ok((sources, package_sources))
 }
 
 fn get_package_sources(config: &Config) -> Result<PackageSources> {
@@ -673,7 +1226,7 @@ fn find_ditto_files<P: AsRef<Path>>(root: P) -> Result<Vec<PathBuf>> {
 
 static LOCK_FILE: &str = "_lock";
 
-fn acquire_lock(config: &Config) -> Result<impl FileExt> {
+pub fn acquire_lock(config: &Config) -> Result<impl FileExt> {
     if !config.ditto_dir.exists() {
         debug!(
             "{} doesn't exist, creating",
@@ -5,35 +5,165 @@ use crate::{
 };
 use console::{Emoji, Style};
 use ditto_config::{
-    read_config, Config, Dependencies, GithubPackageSpec, PackageName, PackageSetExtension,
-    PackageSetPackages as Packages, PackageSpec, CONFIG_FILE_NAME,
+    read_config, read_lockfile, read_package_index, Config, Dependencies, GithubPackageSpec,
+    IndexedVersion, LockedPackage, Lockfile, PackageIndex, PackageName, PackageSetExtension,
+    PackageSetPackages as Packages, PackageSpec, CONFIG_FILE_NAME, LOCKFILE_NAME,
 };
 use indicatif::MultiProgress;
 use miette::{miette, IntoDiagnostic, Result, WrapErr};
 use std::{
-    collections::{hash_map::DefaultHasher, HashSet},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet, VecDeque},
     ffi::OsStr,
     fs,
     hash::{Hash, Hasher},
     io::BufReader,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    process,
 };
 use tracing::{debug, warn};
 
+/// How `ditto.lock` should be treated when checking packages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LockMode {
+    /// Install the locked packages, updating the lockfile if it's out of date.
+    #[default]
+    Normal,
+    /// Fail if the lockfile is missing or out of date.
+    Locked,
+    /// Ignore the lockfile, resolving packages afresh.
+    Update,
+}
+
+/// Read the config for the current project.
+///
+/// The members of a workspace are added to its package set, as local packages,
+/// and depended on by the workspace root. Everything is then resolved against the
+/// root package set, so any package sets of the members are ignored.
+///
+/// The config is checked more strictly than those of dependencies,
+/// see [Config::check].
+pub fn read_project_config<P: AsRef<Path>>(
+    config_path: P,
+    ditto_version: Option<&semver::Version>,
+) -> Result<Config> {
+    let config_path = config_path.as_ref();
+    let contents = fs::read_to_string(config_path)
+        .into_diagnostic()
+        .wrap_err(format!(
+            "error reading config at {:?}",
+            config_path.as_os_str()
+        ))?;
+    let name = config_path.to_string_lossy();
+    let mut config = Config::parse(&name, &contents)
+        .and_then(|config| {
+            config.check(&name, &contents, ditto_version)?;
+            Ok(config)
+        })
+        .wrap_err(format!(
+            "error reading config at {:?}",
+            config_path.as_os_str()
+        ))?;
+    if let Some(workspace) = config.workspace.clone() {
+        let root_dir = config_dir(config_path);
+        for member in workspace.read_members(root_dir, &config)? {
+            config.dependencies.insert(member.config.name.clone(), None);
+            config
+                .package_set
+                .packages
+                .insert(member.config.name, PackageSpec::Path { path: member.path });
+        }
+    }
+    Ok(config)
+}
+
+/// Directory that `ditto vendor` copies packages into,
+/// relative to the [CONFIG_FILE_NAME].
+pub static VENDOR_DIR: &str = "vendor";
+
+/// Records which packages are vendored, lives in the [VENDOR_DIR].
+static VENDOR_LOCKFILE_NAME: &str = "vendor.lock";
+
+static VENDOR_LOCKFILE_HEADER: &str = "\
+# This file is generated by ditto, it is not intended for manual editing.
+# Run `ditto vendor` to update it.
+";
+
+/// If `offline` is set then nothing is downloaded, packages must
+/// be vendored or already in the cache.
+///
+/// The lockfile and [VENDOR_DIR] are found alongside the `config_path`.
 pub async fn check_packages_up_to_date(
+    config_path: &Path,
     config: &Config,
     include_test_dependencies: bool,
+    lock_mode: LockMode,
+    offline: bool,
 ) -> Result<()> {
     debug!("Checking if packages are up to date");
 
-    let available_packages = resolve_packages(config).await?;
-    let want_hash = hash_packages_inputs(&config.dependencies, &available_packages);
+    let mut available_packages = resolve_packages(config, offline).await?;
+
+    let mut dependencies = config.dependencies.clone();
+    if include_test_dependencies {
+        dependencies.extend(config.test_dependencies.clone());
+    }
+
+    let root_dir = config_dir(config_path);
+    let lockfile_path = root_dir.join(LOCKFILE_NAME);
+    let lockfile = if lock_mode == LockMode::Update {
+        None
+    } else {
+        read_lockfile(&lockfile_path)?
+    };
+    let vendor_dir = root_dir.join(VENDOR_DIR);
+    let vendored = read_lockfile(mk_vendor_lockfile_path(&vendor_dir))?;
+
+    if let Some(package_index) = config.package_index.as_ref() {
+        let index = read_package_index(package_index_dir(package_index)?)?;
+        let resolved = resolve_versions(
+            config,
+            &dependencies,
+            &index,
+            &available_packages,
+            lockfile.as_ref(),
+        )?;
+        available_packages.extend(resolved);
+    }
+
+    if lock_mode == LockMode::Locked {
+        let lockfile = lockfile.as_ref().ok_or_else(|| {
+            miette!(
+                help = "run `ditto update` to create it",
+                "{} is missing",
+                LOCKFILE_NAME
+            )
+        })?;
+        let diff = diff_lockfile(
+            lockfile,
+            &dependencies,
+            &available_packages,
+            include_test_dependencies,
+        );
+        if !diff.is_empty() {
+            return Err(lockfile_out_of_date(diff));
+        }
+    }
+
+    let want_hash = hash_packages_inputs(
+        &config.dependencies,
+        &available_packages,
+        &lockfile,
+        &vendored,
+    );
     debug!("Current hash is: {}", want_hash);
 
     let packages_dir = get_or_create_packages_dir(config)?;
     let hash_file = mk_hash_file(&packages_dir);
 
-    if hash_file.exists() {
+    // If there's no lockfile then we need to go through the motions to write one
+    let need_lockfile = lockfile.is_none() && include_test_dependencies;
+
+    if hash_file.exists() && !need_lockfile {
         let got_hash_string = fs::read_to_string(&hash_file)
             .into_diagnostic()
             .wrap_err("error reading packages hash file")?;
@@ -67,22 +197,58 @@ pub async fn check_packages_up_to_date(
 
     let installed_packages = get_installed_packages(&packages_dir)?;
 
-    let mut dependencies = config.dependencies.clone();
-    if include_test_dependencies {
-        dependencies.extend(config.test_dependencies.clone());
-    }
-
     let mut multi_progress = MultiProgress::new();
+    let mut locked_packages = Vec::new();
+    let mut missing = Vec::new();
     update_dependencies(
         &mut multi_progress,
         &packages_dir,
+        &config.name,
         &dependencies,
-        &mut Dependencies::new(),
+        &mut HashSet::new(),
         &installed_packages,
         &available_packages,
+        &Locking {
+            lockfile: lockfile.as_ref(),
+            lock_mode,
+            vendored: vendored.as_ref(),
+            vendor_dir: &vendor_dir,
+            offline,
+        },
+        &mut locked_packages,
+        &mut missing,
     )
     .await?;
+    if !missing.is_empty() {
+        return Err(missing_offline(missing));
+    }
+
+    // NOTE: the lockfile covers test dependencies too,
+    // so we can only write it if they were included
+    if include_test_dependencies {
+        locked_packages.sort_by(|a, b| a.name.cmp(&b.name));
+        let new_lockfile = Lockfile {
+            packages: locked_packages,
+        };
+        if lockfile.as_ref() != Some(&new_lockfile) {
+            if lock_mode == LockMode::Locked {
+                let diff = diff_lockfiles(lockfile.as_ref().unwrap(), &new_lockfile);
+                return Err(lockfile_out_of_date(diff));
+            }
+            debug!("Writing {}", lockfile_path.to_string_lossy());
+            fs::write(&lockfile_path, new_lockfile.to_toml_string())
+                .into_diagnostic()
+                .wrap_err(format!("error writing {}", LOCKFILE_NAME))?;
+        }
+    }
 
+    // Hash again, as the lockfile might have changed
+    let want_hash = hash_packages_inputs(
+        &config.dependencies,
+        &available_packages,
+        &read_lockfile(&lockfile_path)?,
+        &vendored,
+    );
     debug!(
         "Updating {} with {}",
         hash_file.to_string_lossy(),
@@ -92,8 +258,150 @@ pub async fn check_packages_up_to_date(
     Ok(())
 }
 
-async fn resolve_packages(config: &Config) -> Result<Packages> {
+/// Error for when `--offline` is set and things aren't in the cache.
+fn missing_offline(missing: Vec<String>) -> miette::Report {
+    miette!(
+        help = "run without --offline to download them, or `ditto vendor` to vendor them",
+        "can't resolve packages offline, missing from the cache:\n{}",
+        missing.join("\n")
+    )
+}
+
+fn lockfile_out_of_date(diff: Vec<String>) -> miette::Report {
+    miette!(
+        help = "run `ditto update` to update it",
+        "{} is out of date:\n{}",
+        LOCKFILE_NAME,
+        diff.join("\n")
+    )
+}
+
+/// Describes how `lockfile` differs from what would be installed for `dependencies`.
+///
+/// Returns an empty `Vec` if the lockfile is up to date.
+fn diff_lockfile(
+    lockfile: &Lockfile,
+    dependencies: &Dependencies,
+    available_packages: &Packages,
+    include_test_dependencies: bool,
+) -> Vec<String> {
+    let mut diff = Vec::new();
+    let mut reachable = HashSet::new();
+    let mut stack = dependencies.iter().collect::<Vec<_>>();
+    stack.sort();
+    stack.reverse();
+    while let Some(package_name) = stack.pop() {
+        if !reachable.insert(package_name) {
+            continue;
+        }
+        match (
+            lockfile.get(package_name),
+            available_packages.get(package_name),
+        ) {
+            (None, _) => {
+                diff.push(format!("  + {} (not locked)", package_name.as_str()));
+            }
+            (Some(_), None) => {
+                diff.push(format!(
+                    "  - {} (not in the package set)",
+                    package_name.as_str()
+                ));
+            }
+            (Some(locked), Some(available_spec)) => {
+                if locked.spec != *available_spec {
+                    diff.push(format!(
+                        "  ~ {} ({} -> {})",
+                        package_name.as_str(),
+                        describe_spec(&locked.spec),
+                        describe_spec(available_spec)
+                    ));
+                }
+                stack.extend(locked.dependencies.iter().rev());
+            }
+        }
+    }
+    // Test dependencies (and their dependencies) look unreachable otherwise
+    if include_test_dependencies {
+        for locked in lockfile.packages.iter() {
+            if !reachable.contains(&locked.name) {
+                diff.push(format!("  - {} (no longer needed)", locked.name.as_str()));
+            }
+        }
+    }
+    diff
+}
+
+/// Describes how two lockfiles differ.
+fn diff_lockfiles(old: &Lockfile, new: &Lockfile) -> Vec<String> {
+    let mut diff = Vec::new();
+    for old_package in old.packages.iter() {
+        match new.get(&old_package.name) {
+            None => diff.push(format!("  - {}", old_package.name.as_str())),
+            Some(new_package) if new_package != old_package => {
+                let mut changes = Vec::new();
+                if new_package.spec != old_package.spec {
+                    changes.push(format!(
+                        "{} -> {}",
+                        describe_spec(&old_package.spec),
+                        describe_spec(&new_package.spec)
+                    ));
+                }
+                if new_package.version != old_package.version {
+                    changes.push(format!(
+                        "{} -> {}",
+                        describe_version(&old_package.version),
+                        describe_version(&new_package.version)
+                    ));
+                }
+                if new_package.revision != old_package.revision {
+                    changes.push(String::from("revision changed"));
+                }
+                if new_package.content_hash != old_package.content_hash {
+                    changes.push(String::from("contents changed"));
+                }
+                if new_package.dependencies != old_package.dependencies {
+                    changes.push(String::from("dependencies changed"));
+                }
+                diff.push(format!(
+                    "  ~ {} ({})",
+                    old_package.name.as_str(),
+                    changes.join(", ")
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+    for new_package in new.packages.iter() {
+        if old.get(&new_package.name).is_none() {
+            diff.push(format!("  + {}", new_package.name.as_str()));
+        }
+    }
+    diff
+}
+
+/// A short description of an optional version, for messages.
+fn describe_version(version: &Option<semver::Version>) -> String {
+    version.as_ref().map_or_else(
+        || String::from("unversioned"),
+        |version| version.to_string(),
+    )
+}
+
+/// A short description of a [PackageSpec], for messages.
+fn describe_spec(spec: &PackageSpec) -> String {
+    match spec {
+        PackageSpec::Path { path } => path.to_string_lossy().into_owned(),
+        PackageSpec::Github {
+            github, revision, ..
+        } => format!("github:{}/{}@{}", github.owner, github.repo, revision),
+        PackageSpec::Git { url, rev } => format!("{}#{}", url, rev),
+        PackageSpec::Tarball { url, .. } => url.clone(),
+    }
+}
+
+pub async fn resolve_packages(config: &Config, offline: bool) -> Result<Packages> {
     let mut packages = Packages::new();
+    let mut missing = Vec::new();
     for extension in config.package_set.extends.iter() {
         match extension {
             PackageSetExtension::Path { path } => {
@@ -105,21 +413,30 @@ async fn resolve_packages(config: &Config) -> Result<Packages> {
                 packages.extend(more_packages);
             }
             PackageSetExtension::Url { url, sha256 } => {
-                let more_packages = fetch_packages(url, sha256).await?;
-                packages.extend(more_packages);
+                match fetch_packages(url, sha256, offline).await? {
+                    Some(more_packages) => packages.extend(more_packages),
+                    None => missing.push(format!("  package set {}", url)),
+                }
             }
         }
     }
+    if !missing.is_empty() {
+        return Err(missing_offline(missing));
+    }
     packages.extend(config.package_set.packages.clone());
     return Ok(packages);
 
-    async fn fetch_packages(url: &str, sha256: &str) -> Result<Packages> {
+    /// Returns `None` if `offline` and the packages aren't cached.
+    async fn fetch_packages(url: &str, sha256: &str, offline: bool) -> Result<Option<Packages>> {
         let mut cached = get_package_set_cache_dir()?;
         cached.push(sha256);
         cached.set_extension("toml");
         if cached.exists() {
             let cached_contents = fs::read_to_string(&cached).into_diagnostic()?;
-            return toml::from_str(&cached_contents).into_diagnostic();
+            return toml::from_str(&cached_contents).into_diagnostic().map(Some);
+        }
+        if offline {
+            return Ok(None);
         }
 
         let response = reqwest::get(url).await.into_diagnostic()?;
@@ -151,11 +468,296 @@ async fn resolve_packages(config: &Config) -> Result<Packages> {
 
         // All good, save to cache.
         fs::write(cached, contents).into_diagnostic()?;
-        Ok(packages)
+        Ok(Some(packages))
     }
 }
 
-fn hash_packages_inputs(dependencies: &Dependencies, packages: &Packages) -> u64 {
+/// Where a `package-index` lives, given as a directory or a `file://` URL.
+pub fn package_index_dir(package_index: &str) -> Result<PathBuf> {
+    if !package_index.contains("://") {
+        return Ok(PathBuf::from(package_index));
+    }
+    let url = reqwest::Url::parse(package_index)
+        .into_diagnostic()
+        .wrap_err(format!("invalid package index URL {:?}", package_index))?;
+    if url.scheme() != "file" {
+        return Err(miette!(
+            help = "only local package indexes are supported",
+            "unsupported URL scheme {:?} for package index {}",
+            url.scheme(),
+            package_index
+        ));
+    }
+    url.to_file_path()
+        .map_err(|_| miette!("invalid file URL {:?}", package_index))
+}
+
+/// Checks an installed package against the version required by its dependent.
+fn check_version_req(
+    dependent: &PackageName,
+    dependencies: &Dependencies,
+    package_name: &PackageName,
+    config: &Config,
+) -> Result<()> {
+    let version_req = if let Some(version_req) = dependencies.version_req(package_name) {
+        version_req
+    } else {
+        return Ok(());
+    };
+    match config.version.as_ref() {
+        Some(version) if version_req.matches(version) => Ok(()),
+        Some(version) => Err(miette!(
+            "{} requires {} {}, but {} is installed",
+            dependent.as_str(),
+            package_name.as_str(),
+            version_req,
+            version
+        )),
+        None => Err(miette!(
+            help = format!(
+                "add a `version` to the {} config of {}",
+                CONFIG_FILE_NAME,
+                package_name.as_str()
+            ),
+            "{} requires {} {}, but {} doesn't declare a version",
+            dependent.as_str(),
+            package_name.as_str(),
+            version_req,
+            package_name.as_str()
+        )),
+    }
+}
+
+/// Picks a version of each indexed package that satisfies every version requirement,
+/// returning the specs of the chosen versions.
+///
+/// Packages in the package set are pinned, so they're left alone here (but their
+/// versions are checked once they're installed). Locked versions are preferred, so
+/// that resolution is stable.
+fn resolve_versions(
+    config: &Config,
+    dependencies: &Dependencies,
+    index: &PackageIndex,
+    package_set: &Packages,
+    lockfile: Option<&Lockfile>,
+) -> Result<Packages> {
+    let root = (config.name.clone(), config.version.clone());
+    let solver = VersionSolver {
+        index,
+        package_set,
+        lockfile,
+    };
+    let pending = dependencies
+        .iter_with_version_reqs()
+        .map(|(package_name, version_req)| Requirement {
+            package_name: package_name.clone(),
+            version_req: version_req.cloned().unwrap_or(semver::VersionReq::STAR),
+            path: vec![root.clone()],
+        })
+        .collect();
+    let solution = solver
+        .solve(Solution::default(), pending)
+        .map_err(|conflict| conflict.into_report(index))?;
+    Ok(solution
+        .selected
+        .into_iter()
+        .map(|(package_name, indexed_version)| (package_name, indexed_version.spec.clone()))
+        .collect())
+}
+
+/// A version requirement on a package, along with how it came about.
+#[derive(Clone, Debug)]
+struct Requirement {
+    package_name: PackageName,
+    version_req: semver::VersionReq,
+    /// The chain of packages leading to this requirement, starting from the root.
+    path: Vec<(PackageName, Option<semver::Version>)>,
+}
+
+impl Requirement {
+    /// e.g. `my-package -> foo 1.2.0 requires bar ^2.0`
+    fn describe(&self) -> String {
+        let path = self
+            .path
+            .iter()
+            .map(|(package_name, version)| match version {
+                Some(version) => format!("{} {}", package_name.as_str(), version),
+                None => package_name.as_str().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        format!(
+            "{} requires {} {}",
+            path,
+            self.package_name.as_str(),
+            self.version_req
+        )
+    }
+}
+
+#[derive(Clone, Default)]
+struct Solution<'a> {
+    selected: BTreeMap<PackageName, &'a IndexedVersion>,
+    requirements: HashMap<PackageName, Vec<Requirement>>,
+}
+
+/// No version of a package satisfies all of its requirements.
+#[derive(Debug)]
+struct Conflict {
+    package_name: PackageName,
+    requirements: Vec<Requirement>,
+}
+
+impl Conflict {
+    fn into_report(self, index: &PackageIndex) -> miette::Report {
+        let mut available = index
+            .get(&self.package_name)
+            .map(|indexed| {
+                indexed
+                    .versions
+                    .iter()
+                    .map(|indexed_version| &indexed_version.version)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        available.sort();
+        available.reverse();
+        let available = available
+            .into_iter()
+            .map(|version| version.to_string())
+            .collect::<Vec<_>>();
+        let requirements = self
+            .requirements
+            .iter()
+            .map(|requirement| format!("  {}", requirement.describe()))
+            .collect::<Vec<_>>();
+        miette!(
+            help = if available.is_empty() {
+                format!(
+                    "no versions of {} are published",
+                    self.package_name.as_str()
+                )
+            } else {
+                format!(
+                    "available versions of {}: {}",
+                    self.package_name.as_str(),
+                    available.join(", ")
+                )
+            },
+            "no version of {} satisfies all requirements:\n{}",
+            self.package_name.as_str(),
+            requirements.join("\n")
+        )
+    }
+}
+
+/// A simple backtracking solver, which is plenty for a local index.
+struct VersionSolver<'a> {
+    index: &'a PackageIndex,
+    package_set: &'a Packages,
+    lockfile: Option<&'a Lockfile>,
+}
+
+impl<'a> VersionSolver<'a> {
+    fn solve(
+        &self,
+        mut solution: Solution<'a>,
+        mut pending: VecDeque<Requirement>,
+    ) -> std::result::Result<Solution<'a>, Conflict> {
+        while let Some(requirement) = pending.pop_front() {
+            let package_name = requirement.package_name.clone();
+            let indexed = match self.index.get(&package_name) {
+                Some(indexed) if !self.package_set.contains_key(&package_name) => indexed,
+                _ => continue,
+            };
+            let requirements = solution
+                .requirements
+                .entry(package_name.clone())
+                .or_default();
+            requirements.push(requirement.clone());
+
+            if let Some(selected) = solution.selected.get(&package_name) {
+                if requirement.version_req.matches(&selected.version) {
+                    continue;
+                }
+                return Err(Conflict {
+                    package_name,
+                    requirements: requirements.clone(),
+                });
+            }
+
+            let candidates = self.candidates(&package_name, &indexed.versions, requirements);
+            if candidates.is_empty() {
+                return Err(Conflict {
+                    package_name,
+                    requirements: requirements.clone(),
+                });
+            }
+
+            // Report the conflict for the preferred candidate if nothing works out
+            let mut first_conflict = None;
+            for candidate in candidates {
+                let mut solution = solution.clone();
+                solution.selected.insert(package_name.clone(), candidate);
+                let mut pending = pending.clone();
+                let mut path = requirement.path.clone();
+                path.push((package_name.clone(), Some(candidate.version.clone())));
+                for (dependency, version_req) in candidate.dependencies.iter_with_version_reqs() {
+                    pending.push_back(Requirement {
+                        package_name: dependency.clone(),
+                        version_req: version_req.cloned().unwrap_or(semver::VersionReq::STAR),
+                        path: path.clone(),
+                    });
+                }
+                match self.solve(solution, pending) {
+                    Ok(solution) => return Ok(solution),
+                    Err(conflict) => {
+                        first_conflict.get_or_insert(conflict);
+                    }
+                }
+            }
+            return Err(first_conflict.unwrap());
+        }
+        Ok(solution)
+    }
+
+    /// Versions that satisfy all the `requirements`, most preferred first.
+    fn candidates(
+        &self,
+        package_name: &PackageName,
+        versions: &'a [IndexedVersion],
+        requirements: &[Requirement],
+    ) -> Vec<&'a IndexedVersion> {
+        let locked_version = self
+            .lockfile
+            .and_then(|lockfile| lockfile.get(package_name))
+            .and_then(|locked| locked.version.as_ref());
+        let mut candidates = versions
+            .iter()
+            .filter(|indexed_version| {
+                requirements
+                    .iter()
+                    .all(|requirement| requirement.version_req.matches(&indexed_version.version))
+            })
+            .collect::<Vec<_>>();
+        // Newest first, but the locked version before anything else
+        candidates.sort_by(|a, b| {
+            let a_locked = Some(&a.version) == locked_version;
+            let b_locked = Some(&b.version) == locked_version;
+            b_locked
+                .cmp(&a_locked)
+                .then_with(|| b.version.cmp(&a.version))
+        });
+        candidates
+    }
+}
+
+fn hash_packages_inputs(
+    dependencies: &Dependencies,
+    packages: &Packages,
+    lockfile: &Option<Lockfile>,
+    vendored: &Option<Lockfile>,
+) -> u64 {
     let mut dependencies = dependencies.iter().cloned().collect::<Vec<_>>();
 
     let mut packages = packages
@@ -169,66 +771,253 @@ fn hash_packages_inputs(dependencies: &Dependencies, packages: &Packages) -> u64
 
     let mut hasher = DefaultHasher::new();
 
-    (dependencies, packages).hash(&mut hasher);
+    (dependencies, packages, lockfile, vendored).hash(&mut hasher);
     hasher.finish()
 }
 
+/// Lockfile state for [update_dependencies].
+struct Locking<'a> {
+    lockfile: Option<&'a Lockfile>,
+    lock_mode: LockMode,
+    /// Packages in the [VENDOR_DIR], which are installed from there instead.
+    vendored: Option<&'a Lockfile>,
+    /// Where the [VENDOR_DIR] is.
+    vendor_dir: &'a Path,
+    /// Don't fetch anything, see [check_packages_up_to_date].
+    offline: bool,
+}
+
+impl Locking<'_> {
+    /// The lockfile entry for a package, if it matches the package set.
+    fn get(&self, package_name: &PackageName, spec: &PackageSpec) -> Option<&LockedPackage> {
+        self.lockfile
+            .and_then(|lockfile| lockfile.get(package_name))
+            .filter(|locked| locked.spec == *spec)
+    }
+
+    /// Where the vendored copy of a package is, if it matches the package set.
+    fn get_vendored(
+        &self,
+        package_name: &PackageName,
+        spec: &PackageSpec,
+    ) -> Option<(PathBuf, &LockedPackage)> {
+        let vendored = self
+            .vendored
+            .and_then(|vendored| vendored.get(package_name))
+            .filter(|vendored| vendored.spec == *spec)?;
+        let path = self.vendor_dir.join(package_name.as_str());
+        if path.is_dir() {
+            Some((path, vendored))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the spec that should actually be installed for a package,
+    /// along with the revision to be locked.
+    ///
+    /// Vendored packages are installed from the [VENDOR_DIR].
+    /// Git revisions are pinned to the locked commit, or resolved if not locked.
+    /// When offline, git revisions that can't be resolved from the cache are left as is.
+    fn pin(
+        &self,
+        package_name: &PackageName,
+        spec: &PackageSpec,
+    ) -> Result<(PackageSpec, Option<String>)> {
+        if let Some((path, vendored)) = self.get_vendored(package_name, spec) {
+            return Ok((PackageSpec::Path { path }, vendored.revision.clone()));
+        }
+        match spec {
+            PackageSpec::Git { url, rev } => {
+                let locked_commit = self
+                    .get(package_name, spec)
+                    .and_then(|locked| locked.revision.clone());
+                let commit = if let Some(commit) = locked_commit {
+                    commit
+                } else if self.offline {
+                    match resolve_cached_git_rev(url, rev)? {
+                        Some(commit) => commit,
+                        None => return Ok((spec.clone(), None)),
+                    }
+                } else {
+                    let mut spinner = Spinner::new_with_prefix(package_name.as_str().to_string());
+                    spinner.set_message(format!("Resolving {}", rev));
+                    let refresh = self.lock_mode == LockMode::Update;
+                    let (_, commit) = fetch_cached_git_repo(&mut spinner, url, rev, refresh)?;
+                    spinner.finish();
+                    commit
+                };
+                let pinned_spec = PackageSpec::Git {
+                    url: url.clone(),
+                    rev: commit.clone(),
+                };
+                Ok((pinned_spec, Some(commit)))
+            }
+            PackageSpec::Github { revision, .. } => Ok((spec.clone(), Some(revision.clone()))),
+            PackageSpec::Path { .. } | PackageSpec::Tarball { .. } => Ok((spec.clone(), None)),
+        }
+    }
+
+    /// Construct the lockfile entry for an installed package,
+    /// checking the contents haven't changed since they were locked.
+    fn lock(
+        &self,
+        packages_dir: &Path,
+        package_name: &PackageName,
+        spec: &PackageSpec,
+        revision: Option<String>,
+        config: &Config,
+    ) -> Result<LockedPackage> {
+        let content_hash = if let PackageSpec::Path { .. } = spec {
+            None
+        } else {
+            let mut package_dir = packages_dir.to_path_buf();
+            package_dir.push(package_name.as_str());
+            Some(hash_package_contents(&package_dir)?)
+        };
+        if let Some(locked) = self.get(package_name, spec) {
+            if locked.revision == revision
+                && locked.content_hash.is_some()
+                && locked.content_hash != content_hash
+            {
+                return Err(miette!(
+                    help = "run `ditto update` if this is expected",
+                    "contents of {:?} don't match {}",
+                    package_name.as_str(),
+                    LOCKFILE_NAME
+                ));
+            }
+        }
+        let mut dependencies = config.dependencies.iter().cloned().collect::<Vec<_>>();
+        dependencies.sort();
+        Ok(LockedPackage {
+            name: package_name.clone(),
+            version: config.version.clone(),
+            revision,
+            content_hash,
+            dependencies,
+            spec: spec.clone(),
+        })
+    }
+}
+
+/// Hash the files of an installed package.
+fn hash_package_contents(package_dir: &Path) -> Result<String> {
+    let mut files = Vec::new();
+    collect_files(package_dir, package_dir, &mut files)?;
+    files.sort();
+
+    let mut bytes = Vec::new();
+    for (relative_path, path) in files {
+        let contents = fs::read(&path)
+            .into_diagnostic()
+            .wrap_err(format!("error reading {:?}", path))?;
+        bytes.extend(relative_path.as_bytes());
+        bytes.push(0);
+        bytes.extend((contents.len() as u64).to_le_bytes());
+        bytes.extend(contents);
+    }
+    return Ok(sha256::digest(bytes.as_slice()));
+
+    fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> Result<()> {
+        for entry in fs::read_dir(dir).into_diagnostic()? {
+            let path = entry.into_diagnostic()?.path();
+            if path.is_dir() {
+                collect_files(root, &path, files)?;
+            } else {
+                let relative_path = path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push((relative_path, path));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[async_recursion::async_recursion]
+#[allow(clippy::too_many_arguments)]
 async fn update_dependencies(
     _multi_progress: &mut MultiProgress,
     packages_dir: &Path,
+    dependent: &PackageName,
     dependencies: &Dependencies,
-    updated_dependencies: &mut Dependencies,
+    updated_dependencies: &mut HashSet<PackageName>,
     installed_packages: &Packages,
     available_packages: &Packages,
+    locking: &Locking<'_>,
+    locked_packages: &mut Vec<LockedPackage>,
+    missing: &mut Vec<String>,
 ) -> Result<()> {
-    for dependency in dependencies {
+    // NOTE: dependencies are iterated in order, so installs are deterministic
+    for dependency in dependencies.iter() {
         if updated_dependencies.contains(dependency) {
+            let config = read_package_config(packages_dir, dependency)?;
+            check_version_req(dependent, dependencies, dependency, &config)?;
             continue;
         }
         match (
             installed_packages.get(dependency),
             available_packages.get(dependency),
         ) {
-            (Some(installed_spec), Some(available_spec)) => {
-                if *installed_spec != *available_spec {
-                    // Specs differ, update
-
-                    //let mut progress = multi_progress.add(ProgressBar::new_spinner());
-                    let mut spinner = Spinner::new_with_prefix(dependency.as_str().to_string());
-                    debug!("Removing existing install of {}", dependency.as_str());
-                    spinner.set_message("removing existing install");
-                    remove_package(packages_dir, dependency)?;
-                    install_package(spinner, packages_dir, dependency, available_spec).await?;
+            (installed_spec, Some(available_spec)) => {
+                let (want_spec, revision) = locking.pin(dependency, available_spec)?;
+                if locking.offline && installed_spec != Some(&want_spec) && !is_cached(&want_spec)?
+                {
+                    missing.push(format!(
+                        "  {} ({})",
+                        dependency.as_str(),
+                        describe_spec(&want_spec)
+                    ));
+                    continue;
                 }
-                updated_dependencies.insert(dependency.clone());
-                let config = read_package_config(packages_dir, dependency)?;
-                update_dependencies(
-                    _multi_progress,
-                    packages_dir,
-                    &config.dependencies,
-                    updated_dependencies,
-                    installed_packages,
-                    available_packages,
-                )
-                .await?
-            }
-            (None, Some(available_spec)) => {
-                // Not installed
+                match installed_spec {
+                    Some(installed_spec) if *installed_spec == want_spec => {
+                        // Already installed
+                    }
+                    Some(_) => {
+                        // Specs differ, update
 
-                //let mut progress = multi_progress.add(ProgressBar::new_spinner());
-                let spinner = Spinner::new_with_prefix(dependency.as_str().to_string());
+                        //let mut progress = multi_progress.add(ProgressBar::new_spinner());
+                        let mut spinner = Spinner::new_with_prefix(dependency.as_str().to_string());
+                        debug!("Removing existing install of {}", dependency.as_str());
+                        spinner.set_message("removing existing install");
+                        remove_package(packages_dir, dependency)?;
+                        install_package(spinner, packages_dir, dependency, &want_spec).await?;
+                    }
+                    None => {
+                        // Not installed
 
-                install_package(spinner, packages_dir, dependency, available_spec).await?;
+                        //let mut progress = multi_progress.add(ProgressBar::new_spinner());
+                        let spinner = Spinner::new_with_prefix(dependency.as_str().to_string());
+                        install_package(spinner, packages_dir, dependency, &want_spec).await?;
+                    }
+                }
                 updated_dependencies.insert(dependency.clone());
                 let config = read_package_config(packages_dir, dependency)?;
+                check_version_req(dependent, dependencies, dependency, &config)?;
+                locked_packages.push(locking.lock(
+                    packages_dir,
+                    dependency,
+                    available_spec,
+                    revision,
+                    &config,
+                )?);
                 update_dependencies(
                     _multi_progress,
                     packages_dir,
+                    dependency,
                     &config.dependencies,
                     updated_dependencies,
                     installed_packages,
                     available_packages,
+                    locking,
+                    locked_packages,
+                    missing,
                 )
                 .await?
             }
@@ -274,6 +1063,12 @@ async fn install_package(
             )
             .await?;
         }
+        PackageSpec::Git { url, rev } => {
+            install_package_from_git(spinner, packages_dir, package_name, url, rev)?;
+        }
+        PackageSpec::Tarball { url, sha256 } => {
+            install_package_from_tarball(spinner, packages_dir, package_name, url, sha256).await?;
+        }
     }
 
     let mut spec_path = packages_dir.to_path_buf();
@@ -410,55 +1205,497 @@ async fn fetch_cached_github_archive(
     Ok(zip_archive)
 }
 
-fn get_package_set_cache_dir() -> Result<PathBuf> {
-    let mut cache_dir = get_ditto_cache_dir()?;
-    cache_dir.push("package-sets");
-    if !cache_dir.exists() {
-        debug!(
-            "Package set cache directory doesn't exist, creating {:?}",
-            cache_dir
+fn install_package_from_git(
+    mut spinner: Spinner,
+    packages_dir: &Path,
+    package_name: &str,
+    url: &str,
+    rev: &str,
+) -> Result<()> {
+    let (repo, commit) = fetch_cached_git_repo(&mut spinner, url, rev, false)?;
+
+    let mut dst = packages_dir.to_path_buf();
+    dst.push(package_name);
+
+    debug!("Checking out {} ({}) to {:?}", rev, commit, dst);
+    spinner.set_message(format!("Checking out {}", rev));
+    run_git(
+        git()
+            .arg("clone")
+            .arg("--quiet")
+            .arg("--shared")
+            .arg("--no-checkout")
+            .arg(&repo)
+            .arg(&dst),
+    )?;
+    run_git(
+        git()
+            .arg("-C")
+            .arg(&dst)
+            .args(["-c", "advice.detachedHead=false", "checkout", "--quiet"])
+            .arg(&commit),
+    )?;
+
+    // The checkout shares objects with the cached repository,
+    // so the git directory is only going to cause trouble.
+    let mut git_dir = dst;
+    git_dir.push(".git");
+    fs::remove_dir_all(&git_dir).into_diagnostic()?;
+
+    spinner.success("installed");
+    Ok(())
+}
+
+/// Returns the path to a bare clone of `url` containing `rev`,
+/// along with the commit that `rev` refers to.
+///
+/// If `refresh` is set, or `rev` isn't a full commit hash, then the cached
+/// clone is always updated so that branch names resolve to their latest commit.
+fn fetch_cached_git_repo(
+    spinner: &mut Spinner,
+    url: &str,
+    rev: &str,
+    refresh: bool,
+) -> Result<(PathBuf, String)> {
+    // Wherever the url came from, `git` would read this as an option
+    if url.starts_with('-') {
+        return Err(miette!("invalid git url: {:?}", url));
+    }
+
+    let mut repo = get_git_cache_dir()?;
+    repo.push(sha256::digest(url));
+
+    if repo.exists() {
+        // Is the revision already there?
+        if !refresh && is_full_commit_hash(rev) {
+            if let Some(commit) = resolve_git_rev(&repo, rev)? {
+                return Ok((repo, commit));
+            }
+        }
+        // Nope, fetch it...
+        debug!("Fetching {} into {:?}", url, repo);
+        spinner.set_message(format!("Fetching {}", url));
+        run_git(
+            git()
+                .arg("--git-dir")
+                .arg(&repo)
+                .args(["fetch", "--quiet", "origin"]),
+        )?;
+    } else {
+        debug!("Cloning {} into {:?}", url, repo);
+        spinner.set_message(format!("Cloning {}", url));
+        run_git(
+            git()
+                .args(["clone", "--quiet", "--mirror", "--", url])
+                .arg(&repo),
+        )?;
+    }
+
+    let commit = resolve_git_rev(&repo, rev)?
+        .ok_or_else(|| miette!("revision {:?} not found in {}", rev, url))?;
+    Ok((repo, commit))
+}
+
+/// Is `rev` a full (SHA-1 or SHA-256) commit hash, rather than a branch, tag or
+/// abbreviated hash? Only full hashes can't change what they refer to.
+fn is_full_commit_hash(rev: &str) -> bool {
+    (rev.len() == 40 || rev.len() == 64) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Returns the commit for `rev` if it's already in the cached clone of `url`.
+fn resolve_cached_git_rev(url: &str, rev: &str) -> Result<Option<String>> {
+    let mut repo = get_git_cache_dir()?;
+    repo.push(sha256::digest(url));
+    if !repo.exists() {
+        return Ok(None);
+    }
+    resolve_git_rev(&repo, rev)
+}
+
+/// Can the package be installed without going online?
+fn is_cached(spec: &PackageSpec) -> Result<bool> {
+    match spec {
+        PackageSpec::Path { .. } => Ok(true),
+        PackageSpec::Github { sha256, .. } => {
+            let mut cached_zip = get_github_cache_dir()?;
+            cached_zip.push(sha256);
+            cached_zip.set_extension("zip");
+            Ok(cached_zip.exists())
+        }
+        PackageSpec::Git { url, rev } => Ok(resolve_cached_git_rev(url, rev)?.is_some()),
+        PackageSpec::Tarball { url, sha256 } => {
+            let mut cached_tarball = get_tarball_cache_dir()?;
+            cached_tarball.push(sha256);
+            cached_tarball.set_extension("tar.gz");
+            if cached_tarball.exists() {
+                return Ok(true);
+            }
+            // Local files don't need fetching, so long as they're there
+            let local_path = reqwest::Url::parse(url)
+                .ok()
+                .filter(|url| url.scheme() == "file")
+                .and_then(|url| url.to_file_path().ok());
+            Ok(local_path.map_or(false, |path| path.exists()))
+        }
+    }
+}
+
+/// Returns the commit hash for `rev`, if it exists in the given repository.
+fn resolve_git_rev(repo: &Path, rev: &str) -> Result<Option<String>> {
+    let output = git()
+        .arg("--git-dir")
+        .arg(repo)
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", rev))
+        .output()
+        .into_diagnostic()
+        .wrap_err("error running git, is it installed?")?;
+    if output.status.success() {
+        Ok(Some(
+            String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+        ))
+    } else {
+        Ok(None)
+    }
+}
+
+fn git() -> process::Command {
+    process::Command::new("git")
+}
+
+/// Run a git command, returning its (trimmed) stdout.
+fn run_git(command: &mut process::Command) -> Result<String> {
+    let output = command
+        .output()
+        .into_diagnostic()
+        .wrap_err("error running git, is it installed?")?;
+    if !output.status.success() {
+        return Err(miette!(
+            "git exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+async fn install_package_from_tarball(
+    mut spinner: Spinner,
+    packages_dir: &Path,
+    package_name: &str,
+    url: &str,
+    sha256: &str,
+) -> Result<()> {
+    let tarball = fetch_cached_tarball(&mut spinner, url, sha256).await?;
+
+    let mut dst = packages_dir.to_path_buf();
+    dst.push(package_name);
+
+    let file = fs::File::open(&tarball).into_diagnostic()?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    for entry in archive.entries().into_diagnostic()? {
+        let mut entry = entry.into_diagnostic()?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let tar_path = entry.path().into_diagnostic()?.into_owned();
+        let components = tar_path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect::<Vec<_>>();
+        if components
+            .iter()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(miette!("encountered bad tarball path: {:?}", tar_path));
+        }
+        if components.len() < 2 {
+            // not within the root directory
+            continue;
+        }
+        let mut dst = dst.clone();
+        dst.extend(
+            // drop the root directory from the tarball
+            components.into_iter().skip(1),
         );
-        std::fs::create_dir_all(&cache_dir)
-            .into_diagnostic()
-            .wrap_err(format!(
-                "error initializing package set cache dir at {:?}",
-                cache_dir
-            ))?;
+
+        if let Some(parent) = dst.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent).into_diagnostic()?;
+            }
+        }
+        debug!("{:?} -> {:?}", tar_path, &dst);
+        entry.unpack(&dst).into_diagnostic()?;
     }
 
-    Ok(cache_dir)
+    spinner.success("installed");
+    Ok(())
+}
+
+async fn fetch_cached_tarball(spinner: &mut Spinner, url: &str, sha256: &str) -> Result<PathBuf> {
+    // Is it in the cache?
+    let mut cached_tarball = get_tarball_cache_dir()?;
+    cached_tarball.push(sha256);
+    cached_tarball.set_extension("tar.gz");
+    if cached_tarball.exists() {
+        return Ok(cached_tarball);
+    }
+
+    // Nope, fetch it...
+    spinner.set_message(format!("Downloading {}", url));
+    let content = fetch_url(url).await?;
+    let got_sha256 = sha256::digest(content.as_slice());
+    if got_sha256 != sha256 {
+        return Err(miette!(
+            "sha256 mismatch for {}, expected {:?} but got {:?}",
+            url,
+            sha256,
+            got_sha256
+        ));
+    }
+    debug!("Writing tarball to {:?}", cached_tarball);
+    fs::write(&cached_tarball, content).into_diagnostic()?;
+    Ok(cached_tarball)
+}
+
+/// Fetch the contents of a `file://` or `http(s)://` URL.
+async fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    let parsed_url = reqwest::Url::parse(url)
+        .into_diagnostic()
+        .wrap_err(format!("invalid URL {:?}", url))?;
+    match parsed_url.scheme() {
+        "file" => {
+            let path = parsed_url
+                .to_file_path()
+                .map_err(|_| miette!("invalid file URL {:?}", url))?;
+            debug!("Reading {:?}", path);
+            fs::read(&path)
+                .into_diagnostic()
+                .wrap_err(format!("error reading {:?}", path))
+        }
+        "http" | "https" => {
+            debug!("GET {}", url);
+            let response = reqwest::get(parsed_url).await.into_diagnostic()?;
+            if !response.status().is_success() {
+                return Err(miette!("{} {}", response.status(), url));
+            }
+            let content = response.bytes().await.into_diagnostic()?;
+            Ok(content.to_vec())
+        }
+        scheme => Err(miette!(
+            "unsupported URL scheme {:?} for {}, expected file, http or https",
+            scheme,
+            url
+        )),
+    }
+}
+
+fn get_package_set_cache_dir() -> Result<PathBuf> {
+    get_cache_subdir("package-sets", "package set")
 }
 
 fn get_github_cache_dir() -> Result<PathBuf> {
+    get_cache_subdir("github", "github")
+}
+
+fn get_git_cache_dir() -> Result<PathBuf> {
+    get_cache_subdir("git", "git")
+}
+
+fn get_tarball_cache_dir() -> Result<PathBuf> {
+    get_cache_subdir("tarballs", "tarball")
+}
+
+fn get_cache_subdir(name: &str, description: &str) -> Result<PathBuf> {
     let mut cache_dir = get_ditto_cache_dir()?;
-    cache_dir.push("github");
+    cache_dir.push(name);
     if !cache_dir.exists() {
         debug!(
-            "Github cache directory doesn't exist, creating {:?}",
-            cache_dir
+            "{} cache directory doesn't exist, creating {:?}",
+            description, cache_dir
         );
         std::fs::create_dir_all(&cache_dir)
             .into_diagnostic()
             .wrap_err(format!(
-                "error initializing github cache dir at {:?}",
-                cache_dir
+                "error initializing {} cache dir at {:?}",
+                description, cache_dir
             ))?;
     }
 
     Ok(cache_dir)
 }
 
+fn mk_vendor_lockfile_path(vendor_dir: &Path) -> PathBuf {
+    vendor_dir.join(VENDOR_LOCKFILE_NAME)
+}
+
+/// The directory containing a config file, which other paths are relative to.
+fn config_dir(config_path: &Path) -> &Path {
+    config_path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+/// Copy every locked package into the [VENDOR_DIR], so that they can be installed
+/// from there without going online.
+///
+/// Local packages aren't copied. Expects packages, including test dependencies,
+/// to be up to date. Returns the number of packages vendored.
+pub fn vendor_packages(config_path: &Path, config: &Config) -> Result<usize> {
+    let root_dir = config_dir(config_path);
+    let lockfile = read_lockfile(root_dir.join(LOCKFILE_NAME))?.ok_or_else(|| {
+        miette!(
+            help = "run `ditto update` to create it",
+            "{} is missing",
+            LOCKFILE_NAME
+        )
+    })?;
+
+    let vendor_dir = root_dir.join(VENDOR_DIR);
+    if vendor_dir.exists() && !mk_vendor_lockfile_path(&vendor_dir).exists() {
+        return Err(miette!(
+            help = "move it out of the way and try again",
+            "{} already exists, but wasn't created by `ditto vendor`",
+            VENDOR_DIR
+        ));
+    }
+
+    // Build the new vendor directory alongside the packages directory,
+    // as the packages being copied might be installed from the current one.
+    let mut tmp_dir = config.ditto_dir.to_path_buf();
+    tmp_dir.push(VENDOR_DIR);
+    if tmp_dir.exists() {
+        fs::remove_dir_all(&tmp_dir).into_diagnostic()?;
+    }
+    fs::create_dir_all(&tmp_dir)
+        .into_diagnostic()
+        .wrap_err(format!("error creating {:?}", tmp_dir))?;
+
+    let packages_dir = mk_packages_dir(config);
+    let mut vendored = Lockfile::default();
+    for locked in lockfile.packages {
+        if let PackageSpec::Path { .. } = locked.spec {
+            continue;
+        }
+        debug!("Vendoring {}", locked.name.as_str());
+        copy_dir(
+            &packages_dir.join(locked.name.as_str()),
+            &tmp_dir.join(locked.name.as_str()),
+        )?;
+        vendored.packages.push(locked);
+    }
+    fs::write(
+        tmp_dir.join(VENDOR_LOCKFILE_NAME),
+        vendored.to_toml_string_with_header(VENDOR_LOCKFILE_HEADER),
+    )
+    .into_diagnostic()
+    .wrap_err(format!("error writing {}", VENDOR_LOCKFILE_NAME))?;
+
+    if vendor_dir.exists() {
+        fs::remove_dir_all(&vendor_dir)
+            .into_diagnostic()
+            .wrap_err(format!("error removing {}", VENDOR_DIR))?;
+    }
+    fs::rename(&tmp_dir, &vendor_dir)
+        .into_diagnostic()
+        .wrap_err(format!("error moving {:?} to {}", tmp_dir, VENDOR_DIR))?;
+
+    Ok(vendored.packages.len())
+}
+
+/// Recursively copy a directory, following symlinks.
+fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst)
+        .into_diagnostic()
+        .wrap_err(format!("error creating {:?}", dst))?;
+    for entry in fs::read_dir(src)
+        .into_diagnostic()
+        .wrap_err(format!("error reading {:?}", src))?
+    {
+        let path = entry.into_diagnostic()?.path();
+        let mut dst = dst.to_path_buf();
+        dst.push(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_dir(&path, &dst)?;
+        } else {
+            fs::copy(&path, &dst)
+                .into_diagnostic()
+                .wrap_err(format!("error copying {:?}", path))?;
+        }
+    }
+    Ok(())
+}
+
 fn remove_package(packages_dir: &Path, package_name: &str) -> Result<()> {
     debug!("Removing package {:?}", package_name);
     for result in fs::read_dir(packages_dir).into_diagnostic()? {
         let entry = result.into_diagnostic()?;
-        if entry.path().starts_with(package_name) {
+        if entry.path().file_stem() == Some(OsStr::new(package_name)) {
             remove_dir_entry(entry)?;
         }
     }
     Ok(())
 }
 
+/// Remove installed packages that are no longer (transitively) depended on.
+pub fn remove_unused_packages(config: &Config) -> Result<()> {
+    let packages_dir = mk_packages_dir(config);
+    if !packages_dir.exists() {
+        return Ok(());
+    }
+    // Tidy up any half-installed packages first
+    get_installed_packages(&packages_dir)?;
+
+    let installed = read_installed_package_configs(config)?;
+    for package_name in find_unused_packages(config, &installed) {
+        remove_package(&packages_dir, package_name.as_str())?;
+    }
+    Ok(())
+}
+
+/// Installed packages, along with their configs.
+pub type InstalledPackages = BTreeMap<PackageName, Config>;
+
+/// Read the config of every installed package.
+pub fn read_installed_package_configs(config: &Config) -> Result<InstalledPackages> {
+    let packages_dir = mk_packages_dir(config);
+    let mut installed = InstalledPackages::new();
+    for path in list_installed_packages(&packages_dir)? {
+        let package_name = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .ok_or_else(|| miette!("unexpected package path {:?}", path))?;
+        let package_config = read_package_config(&packages_dir, package_name)?;
+        installed.insert(
+            PackageName::new_unchecked(package_name.to_string()),
+            package_config,
+        );
+    }
+    Ok(installed)
+}
+
+/// Installed packages that can't be reached from the dependencies
+/// (or test dependencies) of `config`.
+pub fn find_unused_packages(config: &Config, installed: &InstalledPackages) -> Vec<PackageName> {
+    let mut needed = HashSet::new();
+    let mut stack = config
+        .dependencies
+        .iter()
+        .chain(config.test_dependencies.iter())
+        .collect::<Vec<_>>();
+    while let Some(package_name) = stack.pop() {
+        if !needed.insert(package_name) {
+            continue;
+        }
+        if let Some(package_config) = installed.get(package_name) {
+            stack.extend(package_config.dependencies.iter());
+        }
+    }
+    installed
+        .keys()
+        .filter(|package_name| !needed.contains(package_name))
+        .cloned()
+        .collect()
+}
+
 fn read_package_config(packages_dir: &Path, package_name: &str) -> Result<Config> {
     let mut package_config_path = packages_dir.to_path_buf();
     package_config_path.push(package_name);
@@ -581,7 +1818,518 @@ fn get_or_create_packages_dir(config: &Config) -> Result<PathBuf> {
 }
 
 pub fn mk_packages_dir(config: &Config) -> PathBuf {
-    let mut path = config.ditto_dir.to_path_buf();
-    path.push("packages");
-    path
+    config.installed_packages_dir()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Once;
+
+    /// Point the ditto cache at a temporary directory.
+    ///
+    /// This is shared between tests, which is fine as everything in it is
+    /// content-addressed.
+    fn use_temp_cache_dir() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            let cache_dir = tempfile::tempdir().unwrap().into_path();
+            std::env::set_var("DITTO_CACHE_DIR", cache_dir);
+            std::env::set_var("DITTO_PLAIN", "true");
+        });
+    }
+
+    /// A package called `name`, as a tarball with a single root directory.
+    fn mk_tarball(name: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (path, contents) in mk_package_files(name) {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            builder
+                .append_data(
+                    &mut header,
+                    format!("{}-main/{}", name, path),
+                    contents.as_bytes(),
+                )
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn mk_package_files(name: &str) -> Vec<(&'static str, String)> {
+        vec![
+            ("ditto.toml", format!("name = {:?}\n", name)),
+            (
+                "ditto-src/Pkg.ditto",
+                String::from("module Pkg exports (..)\n"),
+            ),
+        ]
+    }
+
+    fn mk_packages_dir(dir: &Path) -> PathBuf {
+        let packages_dir = dir.join("packages");
+        fs::create_dir_all(&packages_dir).unwrap();
+        packages_dir
+    }
+
+    async fn install(packages_dir: &Path, name: &str, spec: &PackageSpec) -> Result<()> {
+        install_package(
+            Spinner::new_with_prefix(name.to_string()),
+            packages_dir,
+            name,
+            spec,
+        )
+        .await
+    }
+
+    fn assert_installed(packages_dir: &Path, name: &str) {
+        let package_dir = packages_dir.join(name);
+        for (path, contents) in mk_package_files(name) {
+            assert_eq!(
+                fs::read_to_string(package_dir.join(path)).unwrap(),
+                contents
+            );
+        }
+        assert!(packages_dir
+            .join(name)
+            .with_extension(EXTENSION_SPEC)
+            .exists());
+    }
+
+    fn file_url(path: &Path) -> String {
+        reqwest::Url::from_file_path(path).unwrap().to_string()
+    }
+
+    /// A local stand-in for an HTTP server, which responds to a single request with `body`.
+    async fn serve_once(body: Vec<u8>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+        });
+        format!("http://{}/package.tar.gz", addr)
+    }
+
+    #[tokio::test]
+    async fn it_installs_tarballs_from_file_urls() {
+        use_temp_cache_dir();
+        let dir = tempfile::tempdir().unwrap();
+        let tarball = mk_tarball("file-tarball");
+        let tarball_path = dir.path().join("package.tar.gz");
+        fs::write(&tarball_path, &tarball).unwrap();
+
+        let packages_dir = mk_packages_dir(dir.path());
+        let spec = PackageSpec::Tarball {
+            url: file_url(&tarball_path),
+            sha256: sha256::digest(tarball.as_slice()),
+        };
+        install(&packages_dir, "file-tarball", &spec).await.unwrap();
+        assert_installed(&packages_dir, "file-tarball");
+
+        // Now it's cached
+        fs::remove_file(&tarball_path).unwrap();
+        let packages_dir = mk_packages_dir(&dir.path().join("again"));
+        install(&packages_dir, "file-tarball", &spec).await.unwrap();
+        assert_installed(&packages_dir, "file-tarball");
+    }
+
+    #[tokio::test]
+    async fn it_installs_tarballs_from_http_urls() {
+        use_temp_cache_dir();
+        let dir = tempfile::tempdir().unwrap();
+        let tarball = mk_tarball("http-tarball");
+        let sha256 = sha256::digest(tarball.as_slice());
+        let url = serve_once(tarball).await;
+
+        let packages_dir = mk_packages_dir(dir.path());
+        let spec = PackageSpec::Tarball { url, sha256 };
+        install(&packages_dir, "http-tarball", &spec).await.unwrap();
+        assert_installed(&packages_dir, "http-tarball");
+    }
+
+    #[tokio::test]
+    async fn it_verifies_tarball_hashes() {
+        use_temp_cache_dir();
+        let dir = tempfile::tempdir().unwrap();
+        let tarball_path = dir.path().join("package.tar.gz");
+        fs::write(&tarball_path, mk_tarball("bad-tarball")).unwrap();
+
+        let packages_dir = mk_packages_dir(dir.path());
+        let spec = PackageSpec::Tarball {
+            url: file_url(&tarball_path),
+            sha256: "0".repeat(64),
+        };
+        let err = install(&packages_dir, "bad-tarball", &spec)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().starts_with("sha256 mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn it_checks_what_can_be_installed_offline() {
+        use_temp_cache_dir();
+        let tarball = mk_tarball("offline-tarball");
+        let sha256 = sha256::digest(tarball.as_slice());
+        let spec = PackageSpec::Tarball {
+            url: String::from("https://tarballs.example.invalid/offline-tarball.tar.gz"),
+            sha256: sha256.clone(),
+        };
+        assert!(!is_cached(&spec).unwrap());
+
+        let mut cached_tarball = get_tarball_cache_dir().unwrap();
+        cached_tarball.push(sha256);
+        cached_tarball.set_extension("tar.gz");
+        fs::write(cached_tarball, tarball).unwrap();
+        assert!(is_cached(&spec).unwrap());
+
+        // Local tarballs only need to exist
+        let dir = tempfile::tempdir().unwrap();
+        let local_tarball = mk_tarball("offline-local-tarball");
+        let local_path = dir.path().join("offline-local-tarball.tar.gz");
+        let spec = PackageSpec::Tarball {
+            url: file_url(&local_path),
+            sha256: sha256::digest(local_tarball.as_slice()),
+        };
+        assert!(!is_cached(&spec).unwrap());
+        fs::write(&local_path, local_tarball).unwrap();
+        assert!(is_cached(&spec).unwrap());
+
+        let spec = PackageSpec::Git {
+            url: String::from("https://git.example.invalid/missing.git"),
+            rev: String::from("main"),
+        };
+        assert!(!is_cached(&spec).unwrap());
+        assert!(is_cached(&path_spec("local")).unwrap());
+    }
+
+    #[tokio::test]
+    async fn it_installs_git_packages() {
+        use_temp_cache_dir();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        for (path, contents) in mk_package_files("git-package") {
+            let path = repo.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let git_in_repo = |args: &[&str]| {
+            run_git(
+                git()
+                    .arg("-C")
+                    .arg(&repo)
+                    .args([
+                        "-c",
+                        "user.name=ditto",
+                        "-c",
+                        "user.email=ditto@example.com",
+                    ])
+                    .args(["-c", "commit.gpgsign=false"])
+                    .args(args),
+            )
+            .unwrap()
+        };
+        git_in_repo(&["init", "--quiet"]);
+        git_in_repo(&["add", "."]);
+        git_in_repo(&["commit", "--quiet", "--message", "Initial commit"]);
+        let rev = git_in_repo(&["rev-parse", "HEAD"]);
+
+        let packages_dir = mk_packages_dir(dir.path());
+        let spec = PackageSpec::Git {
+            url: file_url(&repo),
+            rev: rev.clone(),
+        };
+        install(&packages_dir, "git-package", &spec).await.unwrap();
+        assert_installed(&packages_dir, "git-package");
+        assert!(!packages_dir.join("git-package").join(".git").exists());
+
+        // Unknown revisions are reported
+        let packages_dir = mk_packages_dir(&dir.path().join("again"));
+        let spec = PackageSpec::Git {
+            url: file_url(&repo),
+            rev: String::from("not-a-revision"),
+        };
+        let err = install(&packages_dir, "git-package", &spec)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not-a-revision"), "{}", err);
+    }
+
+    #[test]
+    fn it_fetches_branches_even_if_cached() {
+        use_temp_cache_dir();
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        let git_in_repo = |args: &[&str]| {
+            run_git(
+                git()
+                    .arg("-C")
+                    .arg(&repo)
+                    .args([
+                        "-c",
+                        "user.name=ditto",
+                        "-c",
+                        "user.email=ditto@example.com",
+                    ])
+                    .args(["-c", "commit.gpgsign=false"])
+                    .args(args),
+            )
+            .unwrap()
+        };
+        git_in_repo(&["init", "--quiet"]);
+        git_in_repo(&["commit", "--quiet", "--allow-empty", "--message", "First"]);
+        let first = git_in_repo(&["rev-parse", "HEAD"]);
+        let branch = git_in_repo(&["rev-parse", "--abbrev-ref", "HEAD"]);
+        let url = file_url(&repo);
+
+        let (_, commit) = fetch_cached_git_repo(&mut Spinner::new(), &url, &branch, false).unwrap();
+        assert_eq!(commit, first);
+
+        git_in_repo(&["commit", "--quiet", "--allow-empty", "--message", "Second"]);
+        let second = git_in_repo(&["rev-parse", "HEAD"]);
+        let (_, commit) = fetch_cached_git_repo(&mut Spinner::new(), &url, &branch, false).unwrap();
+        assert_eq!(commit, second);
+        // Full commit hashes are resolved from the cache
+        let (_, commit) = fetch_cached_git_repo(&mut Spinner::new(), &url, &first, false).unwrap();
+        assert_eq!(commit, first);
+
+        assert!(is_full_commit_hash(&first));
+        assert!(!is_full_commit_hash(&first[..7]));
+        assert!(!is_full_commit_hash(&branch));
+    }
+
+    #[tokio::test]
+    async fn it_rejects_git_urls_that_look_like_options() {
+        use_temp_cache_dir();
+        let packages_dir = tempfile::tempdir().unwrap();
+        let evil = PackageSpec::Git {
+            url: String::from("--upload-pack=touch pwned"),
+            rev: String::from("main"),
+        };
+
+        // From the package set
+        let mut config = Config::new(package_name("test"));
+        config
+            .package_set
+            .packages
+            .insert(package_name("evil"), evil.clone());
+        let packages = resolve_packages(&config, true).await.unwrap();
+        let spec = &packages[&package_name("evil")];
+        let err = install_package(Spinner::new(), packages_dir.path(), "evil", spec)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid git url"), "{}", err);
+
+        // From a package index
+        let mut index = mk_index(&[("evil", "1.0.0", &[])]);
+        index.get_mut(&package_name("evil")).unwrap().versions[0].spec = evil;
+        let (config, dependencies) = mk_root(&[("evil", "^1")]);
+        let resolved =
+            resolve_versions(&config, &dependencies, &index, &Packages::new(), None).unwrap();
+        let spec = &resolved[&package_name("evil")];
+        let err = install_package(Spinner::new(), packages_dir.path(), "evil", spec)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid git url"), "{}", err);
+    }
+
+    fn package_name(name: &str) -> PackageName {
+        PackageName::new_unchecked(name.to_string())
+    }
+
+    fn path_spec(path: &str) -> PackageSpec {
+        PackageSpec::Path { path: path.into() }
+    }
+
+    fn locked(name: &str, dependencies: &[&str]) -> LockedPackage {
+        LockedPackage {
+            name: package_name(name),
+            version: None,
+            revision: None,
+            content_hash: None,
+            dependencies: dependencies.iter().map(|name| package_name(name)).collect(),
+            spec: path_spec(name),
+        }
+    }
+
+    #[test]
+    fn it_diffs_lockfiles_against_the_package_set() {
+        let lockfile = Lockfile {
+            packages: vec![locked("a", &["b"]), locked("b", &[]), locked("old", &[])],
+        };
+        let available_packages = Packages::from([
+            (package_name("a"), path_spec("a")),
+            (package_name("b"), path_spec("b-moved")),
+            (package_name("c"), path_spec("c")),
+        ]);
+        let dependencies = Dependencies::from([package_name("a"), package_name("c")]);
+        assert_eq!(
+            diff_lockfile(&lockfile, &dependencies, &available_packages, true),
+            vec![
+                "  ~ b (b -> b-moved)",
+                "  + c (not locked)",
+                "  - old (no longer needed)"
+            ]
+        );
+        // Might be a test dependency
+        assert_eq!(
+            diff_lockfile(&lockfile, &dependencies, &available_packages, false),
+            vec!["  ~ b (b -> b-moved)", "  + c (not locked)"]
+        );
+
+        let dependencies = Dependencies::from([package_name("a")]);
+        let available_packages = Packages::from([
+            (package_name("a"), path_spec("a")),
+            (package_name("b"), path_spec("b")),
+        ]);
+        assert!(diff_lockfile(&lockfile, &dependencies, &available_packages, false).is_empty());
+    }
+
+    #[test]
+    fn it_diffs_lockfiles() {
+        let old = Lockfile {
+            packages: vec![locked("a", &["b"]), locked("b", &[])],
+        };
+        let new = Lockfile {
+            packages: vec![locked("a", &[]), locked("c", &[])],
+        };
+        assert_eq!(
+            diff_lockfiles(&old, &new),
+            vec!["  ~ a (dependencies changed)", "  - b", "  + c"]
+        );
+    }
+
+    #[test]
+    fn it_hashes_package_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, contents) in mk_package_files("hashed") {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let hash = hash_package_contents(dir.path()).unwrap();
+        assert_eq!(hash, hash_package_contents(dir.path()).unwrap());
+
+        fs::write(dir.path().join("ditto.toml"), "name = \"changed\"\n").unwrap();
+        assert_ne!(hash, hash_package_contents(dir.path()).unwrap());
+    }
+
+    /// Package name, version and dependencies (with version requirements).
+    type IndexEntry<'a> = (&'a str, &'a str, &'a [(&'a str, &'a str)]);
+
+    fn mk_index(packages: &[IndexEntry]) -> PackageIndex {
+        let mut index = PackageIndex::new();
+        for (name, version, dependencies) in packages {
+            let mut indexed_dependencies = Dependencies::new();
+            for (dependency, version_req) in dependencies.iter() {
+                indexed_dependencies.insert(
+                    package_name(dependency),
+                    Some(semver::VersionReq::parse(version_req).unwrap()),
+                );
+            }
+            index
+                .entry(package_name(name))
+                .or_default()
+                .versions
+                .push(IndexedVersion {
+                    version: semver::Version::parse(version).unwrap(),
+                    dependencies: indexed_dependencies,
+                    spec: path_spec(&format!("{}-{}", name, version)),
+                });
+        }
+        index
+    }
+
+    fn mk_root(dependencies: &[(&str, &str)]) -> (Config, Dependencies) {
+        let config = Config::new(package_name("root"));
+        let mut root_dependencies = Dependencies::new();
+        for (dependency, version_req) in dependencies.iter() {
+            root_dependencies.insert(
+                package_name(dependency),
+                Some(semver::VersionReq::parse(version_req).unwrap()),
+            );
+        }
+        (config, root_dependencies)
+    }
+
+    #[test]
+    fn it_resolves_versions() {
+        let index = mk_index(&[
+            ("a", "1.0.0", &[("b", "^1")]),
+            ("a", "2.0.0", &[("b", "^2")]),
+            ("b", "1.0.0", &[]),
+            ("b", "1.1.0", &[]),
+            ("b", "2.0.0", &[]),
+            ("c", "1.0.0", &[("b", "~1.0")]),
+        ]);
+
+        // Newest compatible versions, backtracking where needed
+        let (config, dependencies) = mk_root(&[("a", "*"), ("c", "^1")]);
+        let resolved =
+            resolve_versions(&config, &dependencies, &index, &Packages::new(), None).unwrap();
+        assert_eq!(resolved[&package_name("a")], path_spec("a-1.0.0"));
+        assert_eq!(resolved[&package_name("b")], path_spec("b-1.0.0"));
+        assert_eq!(resolved[&package_name("c")], path_spec("c-1.0.0"));
+
+        // Package set entries take precedence
+        let (config, dependencies) = mk_root(&[("a", "^2")]);
+        let package_set = Packages::from([(package_name("b"), path_spec("pinned-b"))]);
+        let resolved =
+            resolve_versions(&config, &dependencies, &index, &package_set, None).unwrap();
+        assert_eq!(resolved[&package_name("a")], path_spec("a-2.0.0"));
+        assert!(!resolved.contains_key(&package_name("b")));
+    }
+
+    #[test]
+    fn it_prefers_locked_versions() {
+        let index = mk_index(&[("a", "1.0.0", &[]), ("a", "1.1.0", &[])]);
+        let (config, dependencies) = mk_root(&[("a", "^1")]);
+        let mut locked_a = locked("a", &[]);
+        locked_a.version = Some(semver::Version::new(1, 0, 0));
+        let lockfile = Lockfile {
+            packages: vec![locked_a],
+        };
+        let resolved = resolve_versions(
+            &config,
+            &dependencies,
+            &index,
+            &Packages::new(),
+            Some(&lockfile),
+        )
+        .unwrap();
+        assert_eq!(resolved[&package_name("a")], path_spec("a-1.0.0"));
+    }
+
+    #[test]
+    fn it_explains_version_conflicts() {
+        let index = mk_index(&[
+            ("a", "1.0.0", &[("b", "^2")]),
+            ("b", "1.0.0", &[]),
+            ("b", "2.0.0", &[]),
+        ]);
+        let (config, dependencies) = mk_root(&[("a", "^1"), ("b", "^1")]);
+        let report =
+            resolve_versions(&config, &dependencies, &index, &Packages::new(), None).unwrap_err();
+        assert_eq!(
+            report.to_string(),
+            "no version of b satisfies all requirements:\n  root requires b ^1\n  root -> a 1.0.0 requires b ^2"
+        );
+        assert_eq!(
+            report.help().unwrap().to_string(),
+            "available versions of b: 2.0.0, 1.0.0"
+        );
+    }
 }
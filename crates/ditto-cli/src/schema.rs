@@ -0,0 +1,24 @@
+use clap::{arg, ArgMatches, Command};
+use miette::{IntoDiagnostic, Result};
+
+pub fn command(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Print the JSON Schema for ditto.toml files")
+        .arg(arg!(--"package-set" "Print the schema for package set files instead"))
+}
+
+#[test]
+fn verify_cmd() {
+    command("schema").debug_assert();
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let schema = if matches.get_flag("package-set") {
+        ditto_config::package_set_schema()
+    } else {
+        ditto_config::config_schema()
+    };
+    let json = serde_json::to_string_pretty(&schema).into_diagnostic()?;
+    println!("{}", json);
+    Ok(())
+}
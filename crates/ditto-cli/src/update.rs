@@ -0,0 +1,40 @@
+use crate::{make, pkg};
+use clap::{arg, ArgMatches, Command};
+use ditto_config::{CONFIG_FILE_NAME, LOCKFILE_NAME};
+use fs2::FileExt;
+use miette::{Result, WrapErr};
+use std::path::Path;
+
+pub fn command(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name)
+        .about("Update packages and the lockfile")
+        .arg(arg!(--offline "Don't download packages, use cached or vendored ones"))
+}
+
+#[test]
+fn verify_cmd() {
+    command("update").debug_assert();
+}
+
+pub async fn run(matches: &ArgMatches) -> Result<()> {
+    let config = pkg::read_project_config(CONFIG_FILE_NAME, None)?;
+
+    let lock = make::acquire_lock(&config)?;
+    let offline = matches.get_flag("offline");
+    let result = pkg::check_packages_up_to_date(
+        Path::new(CONFIG_FILE_NAME),
+        &config,
+        true,
+        pkg::LockMode::Update,
+        offline,
+    )
+    .await
+    .wrap_err("error updating packages")
+    .and_then(|_| pkg::remove_unused_packages(&config));
+    lock.unlock()
+        .expect("Error releasing lock on build directory");
+    result?;
+
+    println!("Updated {}", LOCKFILE_NAME);
+    Ok(())
+}
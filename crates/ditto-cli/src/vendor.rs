@@ -0,0 +1,37 @@
+use crate::{make, pkg};
+use clap::{ArgMatches, Command};
+use ditto_config::CONFIG_FILE_NAME;
+use fs2::FileExt;
+use miette::{Result, WrapErr};
+use std::path::Path;
+
+pub fn command(name: impl Into<clap::builder::Str>) -> Command {
+    Command::new(name).about("Copy packages into the vendor directory")
+}
+
+#[test]
+fn verify_cmd() {
+    command("vendor").debug_assert();
+}
+
+pub async fn run(_matches: &ArgMatches) -> Result<()> {
+    let config = pkg::read_project_config(CONFIG_FILE_NAME, None)?;
+
+    let lock = make::acquire_lock(&config)?;
+    let result = pkg::check_packages_up_to_date(
+        Path::new(CONFIG_FILE_NAME),
+        &config,
+        true,
+        pkg::LockMode::Normal,
+        false,
+    )
+    .await
+    .wrap_err("error updating packages")
+    .and_then(|_| pkg::vendor_packages(Path::new(CONFIG_FILE_NAME), &config));
+    lock.unlock()
+        .expect("Error releasing lock on build directory");
+    let vendored = result?;
+
+    println!("Vendored {} packages into {}/", vendored, pkg::VENDOR_DIR);
+    Ok(())
+}
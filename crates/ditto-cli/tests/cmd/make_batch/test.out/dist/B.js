@@ -0,0 +1,4 @@
+import {five as A$five} from "./A.js";
+import {ten as Data$Util$ten} from "./Data_Util.js";
+const numbers = [A$five,Data$Util$ten];
+export {numbers};
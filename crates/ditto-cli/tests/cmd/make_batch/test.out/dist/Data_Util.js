@@ -0,0 +1,2 @@
+const ten = 10;
+export {ten};
@@ -0,0 +1,291 @@
+use assert_fs::prelude::*;
+
+#[test]
+fn body_only_edits_dont_recheck_dependents() {
+    let project = assert_fs::TempDir::new().unwrap();
+    project
+        .child("ditto.toml")
+        .write_str("name = \"test\"\n")
+        .unwrap();
+    project
+        .child("ditto-src/A.ditto")
+        .write_str("module A exports (five)\n\nfive : Int = 5\n")
+        .unwrap();
+    project
+        .child("ditto-src/B.ditto")
+        .write_str(
+            "module B exports (fives)\n\nimport A\n\nfives : Array(Int) = [A.five, A.five]\n",
+        )
+        .unwrap();
+
+    make(&project)
+        .arg("--no-cache")
+        .assert()
+        .success()
+        .stdout("Checking A\nChecking B\n");
+
+    // The exports of A are unchanged, so B shouldn't be checked again
+    project
+        .child("ditto-src/A.ditto")
+        .write_str("module A exports (five)\n\nfive : Int = 500\n")
+        .unwrap();
+    make(&project)
+        .arg("--no-cache")
+        .assert()
+        .success()
+        .stdout("Checking A\n");
+
+    // But it should be if they do change
+    project
+        .child("ditto-src/A.ditto")
+        .write_str("module A exports (five)\n\nfive : String = \"5\"\n")
+        .unwrap();
+    make(&project).arg("--no-cache").assert().failure();
+}
+
+#[test]
+fn builds_share_the_build_cache() {
+    let build_cache_dir = assert_fs::TempDir::new().unwrap();
+    let new_project = || {
+        let project = assert_fs::TempDir::new().unwrap();
+        project
+            .child("ditto.toml")
+            .write_str("name = \"test\"\ntargets = [\"nodejs\"]\n")
+            .unwrap();
+        project
+            .child("ditto-src/A.ditto")
+            .write_str("module A exports (five)\n\nfive : Int = 5\n")
+            .unwrap();
+        project
+    };
+
+    let first = new_project();
+    make(&first)
+        .env("DITTO_BUILD_CACHE_DIR", build_cache_dir.path())
+        .assert()
+        .success();
+    // One entry for checking A, and another for generating its JavaScript
+    assert_eq!(cache_entries(&build_cache_dir).len(), 2);
+
+    // Mark the cached JavaScript, so we can tell if it gets used
+    for entry in cache_entries(&build_cache_dir) {
+        let js = entry.join("js");
+        if js.exists() {
+            let mut contents = std::fs::read_to_string(&js).unwrap();
+            contents.push_str("// cached\n");
+            std::fs::write(&js, contents).unwrap();
+        }
+    }
+
+    let second = new_project();
+    make(&second)
+        .env("DITTO_BUILD_CACHE_DIR", build_cache_dir.path())
+        .assert()
+        .success();
+    assert_eq!(cache_entries(&build_cache_dir).len(), 2);
+    second
+        .child("dist/A.js")
+        .assert(predicates::str::ends_with("// cached\n"));
+}
+
+#[test]
+fn denied_warnings_fail_the_build() {
+    let project = assert_fs::TempDir::new().unwrap();
+    let unused = "module A exports (five)\n\nfive = 5\n\nsix = 6\n";
+    let allowed =
+        "module A exports (five)\n\nfive = 5\n\n-- ditto-allow: unused-value-declaration\nsix = 6\n";
+
+    // Denied via ditto.toml
+    project
+        .child("ditto.toml")
+        .write_str("name = \"test\"\n\n[warnings]\nunused-value-declaration = \"deny\"\n")
+        .unwrap();
+    project
+        .child("ditto-src/A.ditto")
+        .write_str(unused)
+        .unwrap();
+    let output = make(&project).arg("--no-cache").assert().failure();
+    assert!(output_contains(
+        output.get_output(),
+        "1 denied warning in ditto-src/A.ditto"
+    ));
+    project
+        .child("ditto-src/A.ditto")
+        .write_str(allowed)
+        .unwrap();
+    make(&project).arg("--no-cache").assert().success();
+
+    // Denied via --deny-warnings
+    project
+        .child("ditto.toml")
+        .write_str("name = \"test\"\n")
+        .unwrap();
+    project
+        .child("ditto-src/A.ditto")
+        .write_str(unused)
+        .unwrap();
+    let output = make(&project)
+        .args(["--no-cache", "--deny-warnings"])
+        .assert()
+        .failure();
+    assert!(output_contains(
+        output.get_output(),
+        "1 denied warning in ditto-src/A.ditto"
+    ));
+    project
+        .child("ditto-src/A.ditto")
+        .write_str(allowed)
+        .unwrap();
+    make(&project)
+        .args(["--no-cache", "--deny-warnings"])
+        .assert()
+        .success();
+
+    // Workspace members are part of the project, so the same levels apply
+    let workspace = assert_fs::TempDir::new().unwrap();
+    workspace
+        .child("ditto.toml")
+        .write_str("name = \"monorepo\"\n\n[workspace]\nmembers = [\"packages/foo\"]\n")
+        .unwrap();
+    workspace
+        .child("packages/foo/ditto.toml")
+        .write_str("name = \"foo\"\n")
+        .unwrap();
+    workspace
+        .child("packages/foo/ditto-src/A.ditto")
+        .write_str(unused)
+        .unwrap();
+    let output = make(&workspace)
+        .args(["--no-cache", "--deny-warnings"])
+        .assert()
+        .failure();
+    assert!(output_contains(output.get_output(), "1 denied warning in"));
+    workspace
+        .child("ditto.toml")
+        .write_str("name = \"monorepo\"\n\n[workspace]\nmembers = [\"packages/foo\"]\n\n[warnings]\nunused-value-declaration = \"deny\"\n")
+        .unwrap();
+    let output = make(&workspace).arg("--no-cache").assert().failure();
+    assert!(output_contains(output.get_output(), "1 denied warning in"));
+    workspace
+        .child("packages/foo/ditto-src/A.ditto")
+        .write_str(allowed)
+        .unwrap();
+    make(&workspace)
+        .args(["--no-cache", "--deny-warnings"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn vendored_packages_build_offline() {
+    let repo = assert_fs::TempDir::new().unwrap();
+    repo.child("ditto.toml")
+        .write_str("name = \"dep\"\n")
+        .unwrap();
+    repo.child("ditto-src/Dep.ditto")
+        .write_str("module Dep exports (five)\n\nfive : Int = 5\n")
+        .unwrap();
+    let git = |args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(repo.path())
+            .args([
+                "-c",
+                "user.name=ditto",
+                "-c",
+                "user.email=ditto@example.com",
+            ])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    };
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "--message", "Initial commit"]);
+
+    let project = assert_fs::TempDir::new().unwrap();
+    let write_config = |rev: &str| {
+        project
+            .child("ditto.toml")
+            .write_str(&format!(
+                "name = \"test\"\ndependencies = [\"dep\"]\n\n[package-set.packages]\ndep = {{ url = \"file://{}\", rev = \"{}\" }}\n",
+                repo.path().display(),
+                rev
+            ))
+            .unwrap();
+    };
+    write_config("HEAD");
+    project
+        .child("ditto-src/A.ditto")
+        .write_str("module A exports (five)\n\nimport (dep) Dep\n\nfive : Int = Dep.five\n")
+        .unwrap();
+
+    let cache_dir = assert_fs::TempDir::new().unwrap();
+    ditto(&project)
+        .env("DITTO_CACHE_DIR", cache_dir.path())
+        .arg("vendor")
+        .assert()
+        .success()
+        .stdout(predicates::str::ends_with(
+            "Vendored 1 packages into vendor/\n",
+        ));
+    project
+        .child("vendor/dep/ditto-src/Dep.ditto")
+        .assert(predicates::path::exists());
+    project
+        .child("vendor/vendor.lock")
+        .assert(predicates::str::contains("name = 'dep'"));
+
+    // Without the repository, the package cache or any installed packages
+    // the vendored copy is all there is
+    repo.close().unwrap();
+    std::fs::remove_dir_all(project.child(".ditto").path()).unwrap();
+    let empty_cache_dir = assert_fs::TempDir::new().unwrap();
+    make(&project)
+        .env("DITTO_CACHE_DIR", empty_cache_dir.path())
+        .args(["--no-cache", "--offline"])
+        .assert()
+        .success();
+
+    // The vendored copy no longer matches the package set, so it isn't used
+    write_config("main");
+    let output = make(&project)
+        .env("DITTO_CACHE_DIR", empty_cache_dir.path())
+        .args(["--no-cache", "--offline"])
+        .assert()
+        .failure();
+    assert!(output_contains(
+        output.get_output(),
+        "can't resolve packages offline, missing from the cache"
+    ));
+}
+
+fn make(project: &assert_fs::TempDir) -> assert_cmd::Command {
+    let mut command = ditto(project);
+    command.arg("make");
+    command
+}
+
+fn ditto(project: &assert_fs::TempDir) -> assert_cmd::Command {
+    let mut command = assert_cmd::Command::new(env!("CARGO_BIN_EXE_ditto"));
+    command
+        .current_dir(project.path())
+        .env("DITTO_TEST_VERSION", "true")
+        .env("DITTO_PLAIN", "true");
+    command
+}
+
+fn cache_entries(build_cache_dir: &assert_fs::TempDir) -> Vec<std::path::PathBuf> {
+    std::fs::read_dir(build_cache_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect()
+}
+
+/// Ninja output might be forwarded to either stream, so check both.
+fn output_contains(output: &std::process::Output, needle: &str) -> bool {
+    String::from_utf8_lossy(&output.stdout).contains(needle)
+        || String::from_utf8_lossy(&output.stderr).contains(needle)
+}
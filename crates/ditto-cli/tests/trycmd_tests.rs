@@ -1,7 +1,16 @@
 #[test]
 fn cli_tests() {
+    // Keep test builds out of the real build cache
+    let build_cache_dir = tempfile::tempdir().unwrap();
+    // ...and start without any cached packages
+    let cache_dir = tempfile::tempdir().unwrap();
     trycmd::TestCases::new()
         .env("DITTO_TEST_VERSION", "true")
+        .env("DITTO_CACHE_DIR", cache_dir.path().to_string_lossy())
+        .env(
+            "DITTO_BUILD_CACHE_DIR",
+            build_cache_dir.path().to_string_lossy(),
+        )
         .case("tests/cmd/*/*.toml")
         .case("README.md");
 }
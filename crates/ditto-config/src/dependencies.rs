@@ -0,0 +1,152 @@
+use crate::PackageName;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The type of `config.dependencies`.
+///
+/// Each dependency is written either as a package name, or as a table with an
+/// optional version requirement:
+///
+/// ```toml
+/// dependencies = ["foo", { name = "bar", version = "^1.2" }]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Dependencies(BTreeMap<PackageName, Option<semver::VersionReq>>);
+
+impl Dependencies {
+    /// Construct an empty set of dependencies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Is the package depended on?
+    pub fn contains(&self, package_name: &PackageName) -> bool {
+        self.0.contains_key(package_name)
+    }
+
+    /// Add a dependency, replacing any existing version requirement.
+    pub fn insert(&mut self, package_name: PackageName, version_req: Option<semver::VersionReq>) {
+        self.0.insert(package_name, version_req);
+    }
+
+    /// Add all of `other`.
+    ///
+    /// If a package is in both then both version requirements need to be satisfied.
+    pub fn extend(&mut self, other: Dependencies) {
+        for (package_name, version_req) in other.0 {
+            let merged = match (self.0.remove(&package_name).flatten(), version_req) {
+                (Some(mut lhs), Some(rhs)) => {
+                    for comparator in rhs.comparators {
+                        if !lhs.comparators.contains(&comparator) {
+                            lhs.comparators.push(comparator);
+                        }
+                    }
+                    Some(lhs)
+                }
+                (lhs, rhs) => lhs.or(rhs),
+            };
+            self.0.insert(package_name, merged);
+        }
+    }
+
+    /// Names of the packages depended on, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &PackageName> {
+        self.0.keys()
+    }
+
+    /// Dependencies along with their version requirements, in order.
+    pub fn iter_with_version_reqs(
+        &self,
+    ) -> impl Iterator<Item = (&PackageName, Option<&semver::VersionReq>)> {
+        self.0
+            .iter()
+            .map(|(package_name, version_req)| (package_name, version_req.as_ref()))
+    }
+
+    /// The version requirement for a dependency, if there is one.
+    pub fn version_req(&self, package_name: &PackageName) -> Option<&semver::VersionReq> {
+        self.0
+            .get(package_name)
+            .and_then(|version_req| version_req.as_ref())
+    }
+
+    /// Number of dependencies.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Are there no dependencies?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromIterator<PackageName> for Dependencies {
+    fn from_iter<T: IntoIterator<Item = PackageName>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|package_name| (package_name, None))
+                .collect(),
+        )
+    }
+}
+
+impl<const N: usize> From<[PackageName; N]> for Dependencies {
+    fn from(package_names: [PackageName; N]) -> Self {
+        package_names.into_iter().collect()
+    }
+}
+
+/// How a single dependency is written.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+enum Dependency {
+    /// Any version of a package.
+    Name(PackageName),
+    /// A version of a package that satisfies a requirement.
+    Versioned {
+        /// The package name.
+        name: PackageName,
+        /// The version requirement, e.g. `^1.2`.
+        #[schemars(with = "String")]
+        version: semver::VersionReq,
+    },
+}
+
+impl JsonSchema for Dependencies {
+    fn schema_name() -> String {
+        "Dependencies".to_string()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        gen.subschema_for::<Vec<Dependency>>()
+    }
+}
+
+impl Serialize for Dependencies {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|(name, version_req)| match version_req {
+            None => Dependency::Name(name.clone()),
+            Some(version) => Dependency::Versioned {
+                name: name.clone(),
+                version: version.clone(),
+            },
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for Dependencies {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let dependencies = Vec::<Dependency>::deserialize(deserializer)?;
+        Ok(Self(
+            dependencies
+                .into_iter()
+                .map(|dependency| match dependency {
+                    Dependency::Name(name) => (name, None),
+                    Dependency::Versioned { name, version } => (name, Some(version)),
+                })
+                .collect(),
+        ))
+    }
+}
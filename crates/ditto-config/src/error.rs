@@ -0,0 +1,374 @@
+use crate::PACKAGE_NAME_REGEX;
+use lazy_static::lazy_static;
+use miette::{Diagnostic, NamedSource, SourceSpan};
+use regex::Regex;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+use thiserror::Error;
+
+/// An error in a config file, pointing at the offending part of the file.
+#[derive(Error, Debug, Diagnostic)]
+#[error("{description}")]
+#[diagnostic(severity(Error))]
+struct ConfigError {
+    #[source_code]
+    input: NamedSource,
+
+    #[label("here")]
+    location: SourceSpan,
+
+    description: String,
+
+    #[help]
+    help: Option<String>,
+}
+
+static PACKAGE_NAME_HELP: &str = "package names must start with a lower case letter, and contain only lower case letters, numbers and hyphens";
+
+/// Byte offsets of the start and end of something in a config file.
+pub(crate) type Span = (usize, usize);
+
+/// A config file, parsed just enough to be able to locate errors in it.
+pub(crate) struct Source<'a> {
+    name: &'a str,
+    input: &'a str,
+    /// `None` if the input isn't valid TOML.
+    document: Option<Item>,
+}
+
+impl<'a> Source<'a> {
+    pub(crate) fn new(name: &'a str, input: &'a str) -> Self {
+        let document = toml::from_str(input)
+            .ok()
+            .map(|node| Item { span: (0, 0), node });
+        Self {
+            name,
+            input,
+            document,
+        }
+    }
+
+    /// An error located at `span`, or at the start of the file if there isn't one.
+    pub(crate) fn error(
+        &self,
+        span: Option<Span>,
+        description: impl Into<String>,
+        help: Option<String>,
+    ) -> miette::Report {
+        let (start, end) = span.unwrap_or((0, 0));
+        miette::Report::from(ConfigError {
+            input: NamedSource::new(self.name, self.input.to_string()),
+            location: (start, end - start).into(),
+            description: description.into(),
+            help,
+        })
+    }
+
+    /// Locate an error from the `toml` crate.
+    ///
+    /// Errors from `toml` carry the key they occurred at, but their line and
+    /// column usually point at the start of the enclosing table. So we look up
+    /// the key ourselves, and then try to narrow things down to the offending
+    /// key or value using the error message.
+    pub(crate) fn toml_error(&self, error: &toml::de::Error) -> miette::Report {
+        let message = error.to_string();
+        let captures = TOML_ERROR_REGEX.captures(&message).unwrap();
+        let description = captures.get(1).map_or("", |m| m.as_str());
+        let item = captures
+            .get(2)
+            .and_then(|key| self.get(key.as_str().split('.')))
+            .or_else(|| self.document.as_ref().filter(|_| captures.get(2).is_none()));
+
+        // The untagged `Dependency` enum doesn't say what's wrong, so work it out
+        if description.contains("enum Dependency") {
+            if let Some((span, description, help)) = item.and_then(explain_dependencies_error) {
+                return self.error(Some(span), description, help);
+            }
+        }
+
+        let span = item.map(|item| narrow(item, description)).or_else(|| {
+            error
+                .line_col()
+                .map(|(line, col)| self.rest_of_line(line, col))
+        });
+        self.error(span, description, suggestion(description))
+    }
+
+    /// Locate the value at a key path, e.g. `["codegen-js", "dist-dir"]`.
+    pub(crate) fn value_span<'k>(&self, path: impl IntoIterator<Item = &'k str>) -> Option<Span> {
+        self.get(path).map(|item| item.span)
+    }
+
+    /// Locate a string within the value at a key path, e.g. a package name
+    /// within `dependencies`.
+    pub(crate) fn string_span<'k>(
+        &self,
+        path: impl IntoIterator<Item = &'k str>,
+        string: &str,
+    ) -> Option<Span> {
+        self.get(path)
+            .and_then(|item| item.find(&|candidate| candidate == string))
+    }
+
+    fn get<'k>(&self, path: impl IntoIterator<Item = &'k str>) -> Option<&Item> {
+        let mut item = self.document.as_ref()?;
+        for key in path {
+            match item.node {
+                Node::Table(ref entries) => {
+                    item = &entries.iter().find(|entry| entry.key == key)?.value;
+                }
+                _ => return None,
+            }
+        }
+        Some(item)
+    }
+
+    /// From a (zero-based) line and column to the end of that line.
+    ///
+    /// Locations past the end of the input (i.e. unexpected EOF) point at the
+    /// last character instead, so that there's something to see.
+    fn rest_of_line(&self, line: usize, col: usize) -> Span {
+        let input_end = self.input.trim_end().len();
+        let line_start = self
+            .input
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum::<usize>();
+        let line_end = line_start
+            + self.input[line_start..]
+                .lines()
+                .next()
+                .map_or(0, |line| line.trim_end().len());
+        let mut start = (line_start + col).min(line_end);
+        if start >= input_end {
+            let last_char_start = self.input[..input_end]
+                .char_indices()
+                .last()
+                .map_or(0, |(i, _)| i);
+            return (last_char_start, input_end);
+        }
+        while !self.input.is_char_boundary(start) {
+            start -= 1;
+        }
+        (start, line_end)
+    }
+}
+
+lazy_static! {
+    // NOTE: this relies on the `Display` implementation for `toml::de::Error`,
+    // which appends the key and location to the underlying message.
+    static ref TOML_ERROR_REGEX: Regex =
+        Regex::new(r"^(?s)(.*?)(?: for key `([^`]*)`)?(?: at line \d+ column \d+)?$").unwrap();
+    static ref UNKNOWN_FIELD_REGEX: Regex = Regex::new(r"^unknown field `([^`]*)`").unwrap();
+    static ref UNKNOWN_VARIANT_REGEX: Regex = Regex::new(r"^unknown variant `([^`]*)`").unwrap();
+    static ref BACKTICKED_REGEX: Regex = Regex::new(r"`([^`]*)`").unwrap();
+}
+
+/// Narrow an error at `item` down to the part of it that the error is about.
+fn narrow(item: &Item, description: &str) -> Span {
+    if let Some(captures) = UNKNOWN_FIELD_REGEX.captures(description) {
+        if let Node::Table(ref entries) = item.node {
+            if let Some(entry) = entries.iter().find(|entry| entry.key == captures[1]) {
+                return entry.key_span;
+            }
+        }
+    }
+    if let Some(captures) = UNKNOWN_VARIANT_REGEX.captures(description) {
+        if let Some(span) = item.find(&|string| string == &captures[1]) {
+            return span;
+        }
+    }
+    if description.contains("package name") || description.contains("enum Dependency") {
+        if let Some(span) = item.find(&|string| !PACKAGE_NAME_REGEX.is_match(string)) {
+            return span;
+        }
+    }
+    item.span
+}
+
+/// Find the first invalid dependency in a dependencies array.
+fn explain_dependencies_error(item: &Item) -> Option<(Span, String, Option<String>)> {
+    let invalid_name = |span: Span, name: &str| {
+        (!PACKAGE_NAME_REGEX.is_match(name)).then(|| {
+            (
+                span,
+                format!("{:?} isn't a valid package name", name),
+                Some(PACKAGE_NAME_HELP.to_string()),
+            )
+        })
+    };
+    let items = match item.node {
+        Node::Array(ref items) => items,
+        _ => return None,
+    };
+    items.iter().find_map(|item| match item.node {
+        Node::String(ref name) => invalid_name(item.span, name),
+        Node::Table(ref entries) => entries.iter().find_map(|entry| match entry.value.node {
+            Node::String(ref name) if entry.key == "name" => invalid_name(entry.value.span, name),
+            Node::String(ref version) if entry.key == "version" => {
+                semver::VersionReq::parse(version).err().map(|err| {
+                    (
+                        entry.value.span,
+                        format!("invalid version requirement {:?}: {}", version, err),
+                        None,
+                    )
+                })
+            }
+            _ => None,
+        }),
+        _ => Some((
+            item.span,
+            "expected a package name, or a table with a `name` and `version`".to_string(),
+            None,
+        )),
+    })
+}
+
+/// A "did you mean" suggestion for unknown fields and variants.
+fn suggestion(description: &str) -> Option<String> {
+    let unknown = UNKNOWN_FIELD_REGEX
+        .captures(description)
+        .or_else(|| UNKNOWN_VARIANT_REGEX.captures(description))?;
+    let expected = description.split_once("expected")?.1;
+    did_you_mean(
+        &unknown[1],
+        BACKTICKED_REGEX
+            .captures_iter(expected)
+            .filter_map(|captures| captures.get(1))
+            .map(|m| m.as_str()),
+    )
+}
+
+/// Suggest the candidate that's closest to `word`, if any are close enough.
+pub(crate) fn did_you_mean<'c>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'c str>,
+) -> Option<String> {
+    similar_names(word, candidates)
+        .first()
+        .map(|candidate| format!("did you mean `{}`?", candidate))
+}
+
+/// Returns the `candidates` that are similar to `name`, most similar first.
+pub fn similar_names<'c>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'c str>,
+) -> Vec<&'c str> {
+    // NOTE the default threshold is too strict for short names,
+    // e.g. it won't suggest "dep" for "dpe"
+    let options = simsearch::SearchOptions::new().threshold(0.55);
+    let mut engine: simsearch::SimSearch<&str> = simsearch::SimSearch::new_with(options);
+    for candidate in candidates {
+        engine.insert(candidate, candidate);
+    }
+    engine.search(name)
+}
+
+/// A TOML value and where it is.
+#[derive(Debug)]
+struct Item {
+    span: Span,
+    node: Node,
+}
+
+/// Just enough of a TOML value to find keys and strings.
+#[derive(Debug)]
+enum Node {
+    Table(Vec<Entry>),
+    Array(Vec<Item>),
+    String(String),
+    Other,
+}
+
+#[derive(Debug)]
+struct Entry {
+    key_span: Span,
+    key: String,
+    value: Item,
+}
+
+impl Item {
+    /// Find the first key or string matching `predicate`.
+    fn find(&self, predicate: &dyn Fn(&str) -> bool) -> Option<Span> {
+        match self.node {
+            Node::Table(ref entries) => entries.iter().find_map(|entry| {
+                if predicate(&entry.key) {
+                    Some(entry.key_span)
+                } else {
+                    entry.value.find(predicate)
+                }
+            }),
+            Node::Array(ref items) => items.iter().find_map(|item| item.find(predicate)),
+            Node::String(ref string) if predicate(string) => Some(self.span),
+            Node::String(_) | Node::Other => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Item {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let spanned = toml::Spanned::<Node>::deserialize(deserializer)?;
+        Ok(Self {
+            span: (spanned.start(), spanned.end()),
+            node: spanned.into_inner(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+
+    fn visit_bool<E>(self, _value: bool) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_i64<E>(self, _value: i64) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_u64<E>(self, _value: u64) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_f64<E>(self, _value: f64) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Node, E> {
+        Ok(Node::String(value.to_owned()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Node::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(key) = map.next_key::<toml::Spanned<String>>()? {
+            let value = map.next_value()?;
+            entries.push(Entry {
+                key_span: (key.start(), key.end()),
+                key: key.into_inner(),
+                value,
+            });
+        }
+        Ok(Node::Table(entries))
+    }
+}
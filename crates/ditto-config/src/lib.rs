@@ -1,19 +1,32 @@
 //! # The ditto config file
 #![warn(missing_docs)]
 
+mod dependencies;
+mod error;
+mod lockfile;
+mod package_index;
 mod package_set;
+mod schema;
 #[cfg(test)]
 mod tests;
+mod workspace;
 
-use miette::{Diagnostic, IntoDiagnostic, WrapErr};
+use error::Source;
+use miette::{IntoDiagnostic, WrapErr};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     path::{Path, PathBuf},
 };
-use thiserror::Error;
 
+pub use dependencies::*;
+pub use error::similar_names;
+pub use lockfile::*;
+pub use package_index::*;
 pub use package_set::*;
+pub use schema::*;
+pub use workspace::*;
 
 /// `"ditto.toml"`
 ///
@@ -22,18 +35,27 @@ pub use package_set::*;
 pub static CONFIG_FILE_NAME: &str = "ditto.toml";
 
 /// Ditto configurations.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Optional ditto version requirement.
     ///
     /// The syntax is inherited from [Cargo](https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html).
     #[serde(rename = "ditto-version")]
+    #[schemars(with = "Option<String>")]
     pub required_ditto_version: Option<semver::VersionReq>,
 
     /// Name of the package being compiled.
     pub name: PackageName,
 
+    /// Version of the package.
+    ///
+    /// Needed for packages that are published to a package index,
+    /// so that version requirements can be checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub version: Option<semver::Version>,
+
     /// Code generation targets.
     #[serde(default)]
     pub targets: HashSet<Target>,
@@ -46,34 +68,52 @@ pub struct Config {
     #[serde(rename = "test-dependencies", default)]
     pub test_dependencies: Dependencies,
 
-    /// Location of source files.
+    /// Package index to resolve version requirements against.
     ///
-    /// This is effectively hardcoded to `ditto-src` for the time being,
-    /// but might become configurable in the future.
-    #[serde(skip, rename = "src-dir", default = "default_src_dir")]
+    /// Either a directory or a `file://` URL. Packages in the `package-set`
+    /// take precedence over indexed packages.
+    #[serde(
+        default,
+        rename = "package-index",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub package_index: Option<String>,
+
+    /// Location of source files, defaults to `ditto-src`.
+    #[serde(
+        rename = "src-dir",
+        default = "default_src_dir",
+        skip_serializing_if = "is_default_src_dir"
+    )]
     pub src_dir: PathBuf,
 
-    /// Location of test files.
-    ///
-    /// This is effectively hardcoded to `ditto-test` for the time being,
-    /// but might become configurable in the future.
-    #[serde(skip, rename = "test-dir", default = "default_test_dir")]
+    /// Location of test files, defaults to `ditto-test`.
+    #[serde(
+        rename = "test-dir",
+        default = "default_test_dir",
+        skip_serializing_if = "is_default_test_dir"
+    )]
     pub test_dir: PathBuf,
 
-    /// Location for compiler artifacts.
-    ///
-    /// This is effectively hardcoded to `".ditto"` for the time being,
-    /// but might become configurable in the future.
-    #[serde(skip, rename = "ditto-dir", default = "default_ditto_dir")]
+    /// Location for compiler artifacts and installed packages, defaults to `.ditto`.
+    #[serde(
+        rename = "ditto-dir",
+        default = "default_ditto_dir",
+        skip_serializing_if = "is_default_ditto_dir"
+    )]
     pub ditto_dir: PathBuf,
 
+    /// Member packages, if this is the root of a workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<Workspace>,
+
     /// Configuration specific to the JavaScript code generator.
     #[serde(
         default,
         rename = "codegen-js",
         skip_serializing_if = "CodegenJsConfig::is_default"
     )]
-    pub codegen_js_config: CodegenJsConfig, // NOTE not currently documented in the crate README!
+    pub codegen_js_config: CodegenJsConfig,
 
     /// Available packages.
     #[serde(
@@ -82,10 +122,47 @@ pub struct Config {
         skip_serializing_if = "PackageSet::is_empty"
     )]
     pub package_set: PackageSet,
+
+    /// Warning levels, keyed by warning name (e.g. `unused-import`) or code (e.g. `W0013`).
+    ///
+    /// Warnings that aren't mentioned default to [WarningLevel::Warn].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub warnings: WarningLevels,
+}
+
+/// The type of `config.warnings`, for convenience.
+pub type WarningLevels = BTreeMap<String, WarningLevel>;
+
+/// How a warning should be treated.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, JsonSchema, Hash, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WarningLevel {
+    /// Don't report the warning.
+    Allow,
+    /// Report the warning (the default).
+    Warn,
+    /// Report the warning as an error.
+    Deny,
+}
+
+impl WarningLevel {
+    /// Parse a level as it would be written in a config file, e.g. `"deny"`.
+    pub fn parse(value: &str) -> Option<Self> {
+        use serde::de::{value::StrDeserializer, IntoDeserializer};
+        let deserializer: StrDeserializer<serde::de::value::Error> = value.into_deserializer();
+        Self::deserialize(deserializer).ok()
+    }
 }
 
-/// The type of `config.dependencies`, for convenience.
-pub type Dependencies = HashSet<PackageName>;
+impl std::fmt::Display for WarningLevel {
+    /// The inverse of [WarningLevel::parse].
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(level)) => f.write_str(&level),
+            _ => Err(std::fmt::Error),
+        }
+    }
+}
 
 impl Config {
     /// Returns a default package configuration with the given `name`.
@@ -93,6 +170,7 @@ impl Config {
         Self {
             required_ditto_version: None,
             name,
+            version: None,
             dependencies: Default::default(),
             test_dependencies: Default::default(),
             targets: Default::default(), // empty
@@ -101,6 +179,9 @@ impl Config {
             codegen_js_config: Default::default(), // nada
             ditto_dir: default_ditto_dir(),
             package_set: Default::default(), //empty
+            package_index: None,
+            workspace: None,
+            warnings: Default::default(), // empty
         }
     }
 
@@ -109,23 +190,176 @@ impl Config {
         self.targets.contains(&Target::Nodejs) || self.targets.contains(&Target::Web)
     }
 
+    /// Where packages are installed, i.e. `<ditto-dir>/packages`.
+    pub fn installed_packages_dir(&self) -> PathBuf {
+        self.ditto_dir.join("packages")
+    }
+
+    /// Check that `ditto_version` meets the `ditto-version` requirement, if any.
+    ///
+    /// Pre-release and build metadata are ignored, so that development
+    /// builds satisfy the requirements of the version they lead up to.
+    pub fn check_ditto_version(
+        &self,
+        ditto_version: &semver::Version,
+    ) -> Result<(), UnsupportedDittoVersion> {
+        if let Some(ref required_ditto_version) = self.required_ditto_version {
+            let version = semver::Version {
+                pre: Default::default(),
+                build: Default::default(),
+                ..ditto_version.clone()
+            };
+            if !required_ditto_version.matches(&version) {
+                return Err(UnsupportedDittoVersion {
+                    current: ditto_version.clone(),
+                    wanted: required_ditto_version.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Parse a config file.
-    pub fn parse(_name: &str, input: &str) -> miette::Result<Config> {
-        toml::from_str(input).map_err(|toml_error| {
-            // TODO try and get this working nicely
-            //if let Some((line, col)) = toml_error.line_col() {
-            //    let offset = miette::SourceOffset::from_location(input, line, col).offset();
-            //    ParseError::Located {
-            //        input: miette::NamedSource::new(name, input.to_string()),
-            //        location: (offset, 0).into(),
-            //        description: toml_error.to_string(),
-            //    }
-            //}
-            miette::Report::from(ParseError::Unlocated {
-                description: toml_error.to_string(),
-            })
-        })
+    ///
+    /// Errors point at the offending part of the `input`, which is referred to by `name`.
+    pub fn parse(name: &str, input: &str) -> miette::Result<Config> {
+        let source = Source::new(name, input);
+        let config: Config = toml::from_str(input).map_err(|err| source.toml_error(&err))?;
+        config.check_dirs(&source)?;
+        Ok(config)
+    }
+
+    /// Check for problems that can't be caught by [Config::parse], given the
+    /// `input` that this config was parsed from.
+    ///
+    /// This is only meant for the package being built, as the dependencies
+    /// of other packages are resolved against its package set.
+    pub fn check(
+        &self,
+        name: &str,
+        input: &str,
+        ditto_version: Option<&semver::Version>,
+    ) -> miette::Result<()> {
+        let source = Source::new(name, input);
+        if let Some(ditto_version) = ditto_version {
+            if let Err(err) = self.check_ditto_version(ditto_version) {
+                return Err(source.error(
+                    source.value_span(["ditto-version"]),
+                    err.to_string(),
+                    None,
+                ));
+            }
+        }
+
+        // Packages can also come from extended package sets, a package index
+        // or workspace members, in which case we can't tell what's available yet
+        let is_self_contained = self.package_set.extends.is_empty()
+            && self.package_index.is_none()
+            && self.workspace.is_none();
+        if is_self_contained {
+            for (key, dependencies) in [
+                ("dependencies", &self.dependencies),
+                ("test-dependencies", &self.test_dependencies),
+            ] {
+                for package_name in dependencies.iter() {
+                    if !self.package_set.packages.contains_key(package_name) {
+                        return Err(source.error(
+                            source.string_span([key], package_name.as_str()),
+                            format!("{:?} isn't in the package set", package_name.as_str()),
+                            error::did_you_mean(
+                                package_name.as_str(),
+                                self.package_set.packages.keys().map(PackageName::as_str),
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
     }
+
+    /// Check that configured directories are inside the package root, and don't overlap.
+    ///
+    /// Overlapping directories would mean, for example, generated code being
+    /// picked up as source code.
+    fn check_dirs(&self, source: &Source) -> miette::Result<()> {
+        let dirs = [
+            ("src-dir", &self.src_dir),
+            ("test-dir", &self.test_dir),
+            ("ditto-dir", &self.ditto_dir),
+            ("codegen-js.dist-dir", &self.codegen_js_config.dist_dir),
+            (
+                "codegen-js.packages-dir",
+                &self.codegen_js_config.packages_dir,
+            ),
+        ];
+        for (key, dir) in dirs {
+            if !dir.is_relative() {
+                return Err(source.error(
+                    source.value_span(key.split('.')),
+                    format!(
+                        "`{}` should be a path relative to the package root, got {:?}",
+                        key,
+                        dir.as_os_str()
+                    ),
+                    None,
+                ));
+            }
+            let escapes_root = dir
+                .components()
+                .any(|component| matches!(component, std::path::Component::ParentDir));
+            if escapes_root {
+                return Err(source.error(
+                    source.value_span(key.split('.')),
+                    format!(
+                        "`{}` should be inside the package root, got {:?}",
+                        key,
+                        dir.as_os_str()
+                    ),
+                    None,
+                ));
+            }
+        }
+        for (i, (key, dir)) in dirs.iter().enumerate() {
+            let dir = normalize_dir(dir);
+            for (other_key, other_dir) in dirs.iter().skip(i + 1) {
+                let other_dir = normalize_dir(other_dir);
+                if dir.starts_with(&other_dir) || other_dir.starts_with(&dir) {
+                    return Err(source.error(
+                        source
+                            .value_span(other_key.split('.'))
+                            .or_else(|| source.value_span(key.split('.'))),
+                        format!(
+                            "`{}` and `{}` overlap: {:?} and {:?}",
+                            key,
+                            other_key,
+                            dir.as_os_str(),
+                            other_dir.as_os_str()
+                        ),
+                        Some("use separate directories for each".to_string()),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The current ditto version doesn't meet a package's `ditto-version` requirement.
+#[derive(Debug, thiserror::Error)]
+#[error("ditto version requirement not met: current version = {current}, wanted = {wanted}")]
+pub struct UnsupportedDittoVersion {
+    /// The version of ditto being run.
+    pub current: semver::Version,
+    /// The `ditto-version` requirement.
+    pub wanted: semver::VersionReq,
+}
+
+/// Strips `.` components, so that `./src` and `src` compare as equal.
+fn normalize_dir(dir: &Path) -> PathBuf {
+    dir.components()
+        .filter(|component| !matches!(component, std::path::Component::CurDir))
+        .collect()
 }
 
 fn default_src_dir() -> PathBuf {
@@ -140,20 +374,32 @@ fn default_ditto_dir() -> PathBuf {
     PathBuf::from(".ditto")
 }
 
+fn is_default_src_dir(path: &Path) -> bool {
+    path == default_src_dir()
+}
+
+fn is_default_test_dir(path: &Path) -> bool {
+    path == default_test_dir()
+}
+
+fn is_default_ditto_dir(path: &Path) -> bool {
+    path == default_ditto_dir()
+}
+
 /// Configuration for JavaScript code generation.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct CodegenJsConfig {
     /// Where to compile _this package's_ JavaScript to.
     ///
     /// Similar to TypeScript's `outDir` option, which is typically `dist`.
-    #[serde(skip, default = "default_js_dist_dir", rename = "dist-dir")]
+    #[serde(default = "default_js_dist_dir", rename = "dist-dir")]
     pub dist_dir: PathBuf,
     /// Where to compile dependencies JavaScript packages to.
     ///
     /// This ultimately leans on the "workspaces" feature of npm/yarn, where
     /// workspace packages are generally added to a root `packages` directory.
-    #[serde(skip, default = "default_js_packages_dir", rename = "packages-dir")]
+    #[serde(default = "default_js_packages_dir", rename = "packages-dir")]
     pub packages_dir: PathBuf,
     /// Extra fields to be (deep) merged into the compiled `package.json` when this
     /// package is built as a dependency.
@@ -188,7 +434,7 @@ fn default_js_packages_dir() -> PathBuf {
 }
 
 /// Code generation targets.
-#[derive(Clone, Debug, Deserialize, Serialize, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Hash, Eq, PartialEq)]
 pub enum Target {
     /// JavaScript for the browser/web.
     #[serde(rename = "web")]
@@ -198,29 +444,6 @@ pub enum Target {
     Nodejs,
 }
 
-#[derive(Error, Debug, Diagnostic)]
-enum ParseError {
-    // TODO nicer syntax errors
-    //
-    // We're not currently using this error as the `toml` crate
-    // seems to give some questionable error locations? Either that or I'm
-    // not plugging them into `miette` correctly...
-    #[error("{description}")]
-    #[diagnostic(severity(Error))]
-    _Located {
-        #[source_code]
-        input: miette::NamedSource,
-
-        #[label("here")]
-        location: miette::SourceSpan,
-
-        description: String,
-    },
-    #[error("{description}")]
-    #[diagnostic(severity(Error))]
-    Unlocated { description: String },
-}
-
 /// Read in a config file.
 pub fn read_config<P: AsRef<Path>>(path: P) -> miette::Result<Config> {
     let contents = std::fs::read_to_string(&path)
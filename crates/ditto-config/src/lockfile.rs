@@ -0,0 +1,106 @@
+use crate::{PackageName, PackageSpec};
+use miette::{IntoDiagnostic, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// `"ditto.lock"`
+///
+/// Lives alongside the [CONFIG_FILE_NAME](crate::CONFIG_FILE_NAME).
+pub static LOCKFILE_NAME: &str = "ditto.lock";
+
+static LOCKFILE_HEADER: &str = "\
+# This file is generated by ditto, it is not intended for manual editing.
+# Run `ditto update` to update it.
+";
+
+/// Records exactly which packages were installed, so that installs are reproducible.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct Lockfile {
+    /// Every package that is (transitively) depended on, ordered by name.
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A package recorded in a [Lockfile].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct LockedPackage {
+    /// Name of the package.
+    pub name: PackageName,
+
+    /// The version that was installed, if the package declares one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<semver::Version>,
+
+    /// The revision that was installed, for packages from version control.
+    ///
+    /// For git packages this is the commit that the spec's `rev` resolved to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+
+    /// sha256 hash of the installed package contents.
+    ///
+    /// Not recorded for local packages, as they're expected to change.
+    #[serde(
+        default,
+        rename = "content-hash",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content_hash: Option<String>,
+
+    /// Packages that this package directly depends on.
+    #[serde(default)]
+    pub dependencies: Vec<PackageName>,
+
+    /// The package set entry that the package was installed from.
+    pub spec: PackageSpec,
+}
+
+impl Lockfile {
+    /// Find the entry for a package.
+    pub fn get(&self, package_name: &PackageName) -> Option<&LockedPackage> {
+        self.packages
+            .iter()
+            .find(|locked_package| locked_package.name == *package_name)
+    }
+
+    /// Parse a lockfile.
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// Render as TOML, with a header comment.
+    pub fn to_toml_string(&self) -> String {
+        self.to_toml_string_with_header(LOCKFILE_HEADER)
+    }
+
+    /// Render as TOML, with a custom header comment.
+    pub fn to_toml_string_with_header(&self, header: &str) -> String {
+        // NOTE: going via `toml::Value` so that plain values are
+        // serialized ahead of tables, which `toml` requires.
+        let value = toml::Value::try_from(self).expect("error serializing lockfile");
+        let toml = toml::to_string_pretty(&value).expect("error serializing lockfile");
+        format!("{}\n{}", header, toml)
+    }
+}
+
+/// Read in a lockfile, if it exists.
+pub fn read_lockfile<P: AsRef<Path>>(path: P) -> miette::Result<Option<Lockfile>> {
+    if !path.as_ref().exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path)
+        .into_diagnostic()
+        .wrap_err(format!(
+            "error reading lockfile at {:?}",
+            path.as_ref().as_os_str()
+        ))?;
+    Lockfile::parse(&contents)
+        .into_diagnostic()
+        .wrap_err(format!(
+            "error reading lockfile at {:?}",
+            path.as_ref().as_os_str()
+        ))
+        .map(Some)
+}
@@ -0,0 +1,86 @@
+use crate::{Dependencies, PackageName, PackageSpec, PACKAGE_NAME_REGEX};
+use miette::{IntoDiagnostic, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path};
+
+/// The published versions of a package, as listed in a package index.
+///
+/// A package index is a directory containing a `<package-name>.toml` file for
+/// each package:
+///
+/// ```toml
+/// [[version]]
+/// version = "1.0.0"
+/// dependencies = ["bar"]
+/// spec = { path = "foo-1.0.0" }  # relative to the index
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexedPackage {
+    /// Every published version, in no particular order.
+    #[serde(default, rename = "version")]
+    pub versions: Vec<IndexedVersion>,
+}
+
+/// A single published version of an [IndexedPackage].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexedVersion {
+    /// The version, which should match the package's own `version`.
+    pub version: semver::Version,
+
+    /// Dependencies of this version (test dependencies aren't needed).
+    #[serde(default)]
+    pub dependencies: Dependencies,
+
+    /// Where to get this version from.
+    ///
+    /// Relative paths are relative to the index directory.
+    pub spec: PackageSpec,
+}
+
+/// A package index, keyed by package name.
+pub type PackageIndex = HashMap<PackageName, IndexedPackage>;
+
+/// Read every package in a package index directory.
+///
+/// Relative paths in package specs are resolved against the index directory.
+pub fn read_package_index<P: AsRef<Path>>(dir: P) -> miette::Result<PackageIndex> {
+    let dir = dir.as_ref();
+    let entries = std::fs::read_dir(dir).into_diagnostic().wrap_err(format!(
+        "error reading package index at {:?}",
+        dir.as_os_str()
+    ))?;
+
+    let mut index = PackageIndex::new();
+    for entry in entries {
+        let path = entry.into_diagnostic()?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("toml") {
+            continue;
+        }
+        let package_name = match path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| PACKAGE_NAME_REGEX.is_match(stem))
+            .map(|stem| PackageName::new_unchecked(stem.to_string()))
+        {
+            Some(package_name) => package_name,
+            None => continue,
+        };
+        let contents = std::fs::read_to_string(&path)
+            .into_diagnostic()
+            .wrap_err(format!("error reading {:?}", path.as_os_str()))?;
+        let mut indexed: IndexedPackage = toml::from_str(&contents)
+            .into_diagnostic()
+            .wrap_err(format!("error reading {:?}", path.as_os_str()))?;
+        for indexed_version in indexed.versions.iter_mut() {
+            if let PackageSpec::Path { path } = &mut indexed_version.spec {
+                if path.is_relative() {
+                    *path = dir.join(&path);
+                }
+            }
+        }
+        index.insert(package_name, indexed);
+    }
+    Ok(index)
+}
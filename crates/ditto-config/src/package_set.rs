@@ -1,5 +1,6 @@
 use lazy_static::lazy_static;
 use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, hash::Hash, path::PathBuf};
 use validated_newtype::validated_newtype;
@@ -37,12 +38,37 @@ impl PackageName {
     }
 }
 
+impl JsonSchema for PackageName {
+    fn schema_name() -> String {
+        "PackageName".to_string()
+    }
+
+    fn json_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, Metadata, SchemaObject, StringValidation};
+        SchemaObject {
+            instance_type: Some(InstanceType::String.into()),
+            metadata: Some(Box::new(Metadata {
+                description: Some(
+                    "A package name must start with a lower case letter, and contain only lower case letters, numbers and hyphens (\"-\").".to_string(),
+                ),
+                ..Default::default()
+            })),
+            string: Some(Box::new(StringValidation {
+                pattern: Some(PACKAGE_NAME_RE.to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
 /// A package set describes the packages available to a package.
 ///
 /// The complete set of _packages_ is the result of resolving (and merging) a number of
 /// partial package sets, which are specified either in other files or in the
 /// main ditto config itself.
-#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema, Default)]
 #[serde(deny_unknown_fields)]
 pub struct PackageSet {
     /// Package sets to extend. Later entries override previous ones.
@@ -63,7 +89,7 @@ impl PackageSet {
 }
 
 /// The specification of a package set to be extended.
-#[derive(Clone, Hash, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Hash, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum PackageSetExtension {
     /// A local package set.
@@ -83,7 +109,7 @@ pub enum PackageSetExtension {
 pub type PackageSetPackages = HashMap<PackageName, PackageSpec>;
 
 /// The specification of a single package's location.
-#[derive(Clone, Hash, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Hash, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum PackageSpec {
     /// A local package.
@@ -100,10 +126,25 @@ pub enum PackageSpec {
         /// The hash of the repo zip.
         sha256: String,
     },
+    /// A git repository.
+    Git {
+        /// Where to clone the repository from, e.g. `https://git.example.com/repo.git`
+        /// or `file:///path/to/repo`.
+        url: String,
+        /// The revision to use, preferably a full commit hash.
+        rev: String,
+    },
+    /// A gzipped tarball, containing the package in a single root directory.
+    Tarball {
+        /// Where to fetch the tarball from, either `file://` or `http(s)://`.
+        url: String,
+        /// The hash of the tarball.
+        sha256: String,
+    },
 }
 
 /// Description of a Github repository.
-#[derive(Clone, Hash, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Hash, Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
 pub struct GithubPackageSpec {
     /// The user or organisation name.
     pub owner: String,
@@ -0,0 +1,19 @@
+use crate::{Config, PackageSetPackages};
+use schemars::schema::RootSchema;
+
+/// JSON Schema for [CONFIG_FILE_NAME](crate::CONFIG_FILE_NAME) files.
+///
+/// Useful for TOML language servers, which can then validate and autocomplete
+/// config files.
+pub fn config_schema() -> RootSchema {
+    schemars::schema_for!(Config)
+}
+
+/// JSON Schema for package set files, i.e. those listed in `package-set.extends`.
+pub fn package_set_schema() -> RootSchema {
+    let mut schema = schemars::schema_for!(PackageSetPackages);
+    let metadata = schema.schema.metadata();
+    metadata.title = Some("PackageSetPackages".to_string());
+    metadata.description = Some("Packages, keyed by package name.".to_string());
+    schema
+}
@@ -23,7 +23,8 @@ mod macros {
 
 mod successes {
     use super::macros::assert_parses;
-    use crate::{CodegenJsConfig, Config};
+    use crate::{CodegenJsConfig, Config, PackageName, PackageSpec, WarningLevel};
+    use std::path::Path;
 
     #[test]
     fn it_parses_a_minimal_config() {
@@ -93,6 +94,70 @@ mod successes {
             sha256 = "some-digest"
         "#
         );
+        let config = assert_parses!(
+            r#"
+            name = "test"
+            dependencies = ["foo", "bar"]
+
+            [package-set.packages]
+            foo = { url = "https://git.example.com/foo.git", rev = "some-rev" }
+            bar = { url = "file:///tmp/bar.tar.gz", sha256 = "some-digest" }
+        "#
+        );
+        let get = |name: &str| {
+            config
+                .package_set
+                .packages
+                .get(&PackageName::new_unchecked(name.to_string()))
+        };
+        assert!(matches!(get("foo"), Some(PackageSpec::Git { .. })));
+        assert!(matches!(get("bar"), Some(PackageSpec::Tarball { .. })));
+    }
+
+    #[test]
+    fn it_parses_versioned_dependencies() {
+        let config = assert_parses!(
+            r#"
+            name = "test"
+            version = "0.1.0"
+            package-index = "../index"
+            dependencies = ["foo", { name = "bar", version = "^1.2" }]
+            test-dependencies = [{ name = "baz", version = "~0.3" }]
+        "#
+        );
+        let name = |name: &str| PackageName::new_unchecked(name.to_string());
+        assert_eq!(config.version, Some(semver::Version::new(0, 1, 0)));
+        assert_eq!(config.package_index.as_deref(), Some("../index"));
+        assert!(config.dependencies.contains(&name("foo")));
+        assert_eq!(config.dependencies.version_req(&name("foo")), None);
+        assert_eq!(
+            config.dependencies.version_req(&name("bar")),
+            Some(&semver::VersionReq::parse("^1.2").unwrap())
+        );
+        assert!(config.test_dependencies.version_req(&name("baz")).is_some());
+    }
+
+    #[test]
+    fn it_merges_dependencies() {
+        let config = assert_parses!(
+            r#"
+            name = "test"
+            dependencies = ["foo", { name = "bar", version = "^1.2" }, "baz"]
+            test-dependencies = [{ name = "foo", version = "^2" }, { name = "bar", version = "<1.5" }, "baz"]
+        "#
+        );
+        let name = |name: &str| PackageName::new_unchecked(name.to_string());
+        let mut dependencies = config.dependencies;
+        dependencies.extend(config.test_dependencies);
+        assert_eq!(
+            dependencies.version_req(&name("foo")),
+            Some(&semver::VersionReq::parse("^2").unwrap())
+        );
+        assert_eq!(
+            dependencies.version_req(&name("bar")),
+            Some(&semver::VersionReq::parse("^1.2, <1.5").unwrap())
+        );
+        assert_eq!(dependencies.version_req(&name("baz")), None);
     }
 
     #[test]
@@ -153,10 +218,72 @@ mod successes {
             }
         );
     }
+
+    #[test]
+    fn it_parses_warning_levels() {
+        let config = assert_parses!(
+            r#"
+            name = "test" 
+            [warnings]
+            unused-import = "allow"
+            W0001 = "deny"
+            unused-let-binder = "warn"
+        "#
+        );
+        assert_eq!(config.warnings["unused-import"], WarningLevel::Allow);
+        assert_eq!(config.warnings["W0001"], WarningLevel::Deny);
+        assert_eq!(config.warnings["unused-let-binder"], WarningLevel::Warn);
+    }
+
+    #[test]
+    fn it_parses_and_displays_warning_levels() {
+        for level in [WarningLevel::Allow, WarningLevel::Warn, WarningLevel::Deny] {
+            assert_eq!(WarningLevel::parse(&level.to_string()), Some(level));
+        }
+        assert_eq!(WarningLevel::Deny.to_string(), "deny");
+        assert_eq!(WarningLevel::parse("Deny"), None);
+    }
+
+    #[test]
+    fn it_parses_dirs() {
+        let config = Config::parse(
+            "ditto.toml",
+            r#"
+            name = "test"
+            src-dir = "src/ditto"
+            test-dir = "./test/ditto"
+            ditto-dir = "build/ditto"
+            [codegen-js]
+            dist-dir = "build/js"
+            packages-dir = "node_packages"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(config.src_dir, Path::new("src/ditto"));
+        assert_eq!(config.test_dir, Path::new("./test/ditto"));
+        assert_eq!(config.ditto_dir, Path::new("build/ditto"));
+        assert_eq!(
+            config.installed_packages_dir(),
+            Path::new("build/ditto/packages")
+        );
+        assert_eq!(config.codegen_js_config.dist_dir, Path::new("build/js"));
+        assert_eq!(
+            config.codegen_js_config.packages_dir,
+            Path::new("node_packages")
+        );
+
+        // Defaults aren't serialized
+        let config = Config::parse("ditto.toml", "name = \"test\"").unwrap();
+        assert_eq!(
+            toml::to_string(&config).unwrap(),
+            "name = \"test\"\ntargets = []\ndependencies = []\ntest-dependencies = []\n"
+        );
+    }
 }
 
 mod errors {
     use super::macros::assert_error;
+    use crate::Config;
 
     #[test]
     fn it_errors_for_empty_input() {
@@ -195,4 +322,296 @@ mod errors {
         "#
         );
     }
+
+    #[test]
+    fn it_errors_for_invalid_versions() {
+        assert_error!(
+            r#"
+            name = "test" 
+            version = "1"
+        "#
+        );
+        assert_error!(
+            r#"
+            name = "test" 
+            dependencies = [{ name = "foo", version = "not a version" }]
+        "#
+        );
+    }
+
+    #[test]
+    fn it_errors_for_invalid_warning_levels() {
+        assert_error!(
+            r#"
+            name = "test" 
+            [warnings]
+            unused-import = "ignore"
+        "#
+        );
+    }
+
+    #[test]
+    fn it_errors_for_bad_dirs() {
+        let parse = |input: &str| Config::parse("ditto.toml", input).unwrap_err().to_string();
+        assert_eq!(
+            parse("name = \"test\"\nsrc-dir = \"/src\""),
+            "`src-dir` should be a path relative to the package root, got \"/src\""
+        );
+        assert_eq!(
+            parse("name = \"test\"\nsrc-dir = \"../elsewhere\""),
+            "`src-dir` should be inside the package root, got \"../elsewhere\""
+        );
+        assert_eq!(
+            parse("name = \"test\"\n[codegen-js]\ndist-dir = \"dist/../../dist\""),
+            "`codegen-js.dist-dir` should be inside the package root, got \"dist/../../dist\""
+        );
+        assert_eq!(
+            parse("name = \"test\"\nditto-dir = \"./ditto-src/.ditto\""),
+            "`src-dir` and `ditto-dir` overlap: \"ditto-src\" and \"ditto-src/.ditto\""
+        );
+        assert_eq!(
+            parse("name = \"test\"\n[codegen-js]\ndist-dir = \"out\"\npackages-dir = \"out\""),
+            "`codegen-js.dist-dir` and `codegen-js.packages-dir` overlap: \"out\" and \"out\""
+        );
+    }
+}
+
+mod package_index {
+    use crate::{read_package_index, PackageName, PackageSpec};
+
+    #[test]
+    fn it_reads_package_indexes() {
+        let dir = std::env::temp_dir().join(format!("ditto-index-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("foo.toml"),
+            r#"
+            [[version]]
+            version = "1.0.0"
+            spec = { path = "foo-1.0.0" }
+
+            [[version]]
+            version = "1.1.0"
+            dependencies = [{ name = "bar", version = "^2" }]
+            spec = { url = "https://example.com/foo.tar.gz", sha256 = "some-digest" }
+        "#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("README.md"), "not an entry").unwrap();
+
+        let index = read_package_index(&dir).unwrap();
+        assert_eq!(index.len(), 1);
+        let foo = &index[&PackageName::new_unchecked("foo".to_string())];
+        assert_eq!(foo.versions.len(), 2);
+        assert_eq!(
+            foo.versions[0].spec,
+            PackageSpec::Path {
+                path: dir.join("foo-1.0.0")
+            }
+        );
+        assert!(foo.versions[1]
+            .dependencies
+            .contains(&PackageName::new_unchecked("bar".to_string())));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod workspaces {
+    use super::macros::{assert_error, assert_parses};
+    use crate::{read_config, Config, Workspace};
+
+    #[test]
+    fn it_parses_workspaces() {
+        assert_parses!(
+            r#"
+            name = "monorepo"
+            [workspace]
+            members = ["packages/foo", "packages/bar"]
+        "#,
+            Config {
+                workspace: Some(Workspace { .. }),
+                ..
+            }
+        );
+        assert_error!(
+            r#"
+            name = "monorepo"
+            [workspace]
+            members = ["packages/foo"]
+            exclude = ["packages/bar"]
+        "#
+        );
+    }
+
+    #[test]
+    fn it_reads_workspace_members() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        for (path, contents) in [
+            (
+                "ditto.toml",
+                "name = \"monorepo\"\n[workspace]\nmembers = [\"foo\", \"bar\"]\n",
+            ),
+            ("foo/ditto.toml", "name = \"foo\"\n"),
+            (
+                "bar/ditto.toml",
+                "name = \"bar\"\ndependencies = [\"foo\"]\n",
+            ),
+            (
+                "nested/ditto.toml",
+                "name = \"nested\"\n[workspace]\nmembers = []\n",
+            ),
+        ] {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        let root = read_config(dir.join("ditto.toml")).unwrap();
+        let workspace = root.workspace.clone().unwrap();
+        let members = workspace.read_members(dir, &root).unwrap();
+        assert_eq!(
+            members
+                .iter()
+                .map(|member| (member.path.to_string_lossy(), member.config.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("foo".into(), "foo"), ("bar".into(), "bar")]
+        );
+
+        let err = Workspace {
+            members: vec!["foo".into(), "foo".into()],
+        }
+        .read_members(dir, &root)
+        .unwrap_err();
+        assert!(err.to_string().contains("same name"), "{}", err);
+
+        let err = Workspace {
+            members: vec!["nested".into()],
+        }
+        .read_members(dir, &root)
+        .unwrap_err();
+        assert!(err.to_string().contains("workspace itself"), "{}", err);
+    }
+}
+
+mod checks {
+    use crate::Config;
+
+    fn check(input: &str, ditto_version: &str) -> miette::Result<()> {
+        let config = Config::parse("ditto.toml", input).unwrap();
+        let ditto_version = semver::Version::parse(ditto_version).unwrap();
+        config.check("ditto.toml", input, Some(&ditto_version))
+    }
+
+    fn label_text(input: &str, report: &miette::Report) -> String {
+        let label = report.labels().unwrap().next().unwrap();
+        input[label.offset()..label.offset() + label.len()].to_string()
+    }
+
+    #[test]
+    fn it_locates_parse_errors() {
+        let input =
+            "name = \"test\"\ndependencies = [\"core\"]\n[codegen-js]\npakages-dir = \"out\"\n";
+        let report = Config::parse("ditto.toml", input).unwrap_err();
+        assert_eq!(label_text(input, &report), "pakages-dir");
+        assert_eq!(
+            report.help().unwrap().to_string(),
+            "did you mean `packages-dir`?"
+        );
+
+        let input = "name = \"test\"\ntargets = [\"web\", \"wbe\"]\n";
+        let report = Config::parse("ditto.toml", input).unwrap_err();
+        assert_eq!(label_text(input, &report), "\"wbe\"");
+        assert_eq!(report.help().unwrap().to_string(), "did you mean `web`?");
+
+        let input =
+            "name = \"test\"\ndependencies = [\"core\", { name = \"Nope\", version = \"^1\" }]\n";
+        let report = Config::parse("ditto.toml", input).unwrap_err();
+        assert_eq!(report.to_string(), "\"Nope\" isn't a valid package name");
+        assert_eq!(label_text(input, &report), "\"Nope\"");
+    }
+
+    #[test]
+    fn it_checks_dependencies_are_in_the_package_set() {
+        let input = r#"
+            name = "test"
+            dependencies = ["core"]
+            test-dependencies = ["fancy-tset"]
+            [package-set.packages]
+            core = { path = "../core" }
+            fancy-test = { path = "../fancy-test" }
+        "#;
+        let report = check(input, "0.1.0").unwrap_err();
+        assert_eq!(
+            report.to_string(),
+            "\"fancy-tset\" isn't in the package set"
+        );
+        assert_eq!(label_text(input, &report), "\"fancy-tset\"");
+        assert_eq!(
+            report.help().unwrap().to_string(),
+            "did you mean `fancy-test`?"
+        );
+
+        // Could come from the package index
+        check(
+            r#"
+            name = "test"
+            dependencies = ["core"]
+            package-index = "../index"
+        "#,
+            "0.1.0",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn it_checks_the_ditto_version() {
+        let input = "name = \"test\"\nditto-version = \"^0.2\"\n";
+        check(input, "0.2.1").unwrap();
+        let report = check(input, "0.1.0").unwrap_err();
+        assert_eq!(
+            report.to_string(),
+            "ditto version requirement not met: current version = 0.1.0, wanted = ^0.2"
+        );
+        assert_eq!(label_text(input, &report), "\"^0.2\"");
+    }
+}
+
+mod lockfiles {
+    use crate::{GithubPackageSpec, LockedPackage, Lockfile, PackageName, PackageSpec};
+
+    #[test]
+    fn it_roundtrips_lockfiles() {
+        let lockfile = Lockfile {
+            packages: vec![
+                LockedPackage {
+                    name: PackageName::new_unchecked("core".to_string()),
+                    version: Some(semver::Version::new(1, 2, 3)),
+                    revision: Some("some-rev".to_string()),
+                    content_hash: Some("some-hash".to_string()),
+                    dependencies: vec![],
+                    spec: PackageSpec::Github {
+                        github: GithubPackageSpec {
+                            owner: "some-owner".to_string(),
+                            repo: "some-repo".to_string(),
+                        },
+                        revision: "some-rev".to_string(),
+                        sha256: "some-digest".to_string(),
+                    },
+                },
+                LockedPackage {
+                    name: PackageName::new_unchecked("local".to_string()),
+                    version: None,
+                    revision: None,
+                    content_hash: None,
+                    dependencies: vec![PackageName::new_unchecked("core".to_string())],
+                    spec: PackageSpec::Path {
+                        path: "../local".into(),
+                    },
+                },
+            ],
+        };
+        let toml = lockfile.to_toml_string();
+        assert!(toml.starts_with("# This file is generated by ditto"));
+        similar_asserts::assert_eq!(Lockfile::parse(&toml).unwrap(), lockfile);
+    }
 }
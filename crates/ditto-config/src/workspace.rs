@@ -0,0 +1,82 @@
+use crate::{read_config, Config, CONFIG_FILE_NAME};
+use miette::miette;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Packages that are built together, sharing a single build graph and
+/// installed dependencies.
+///
+/// ```toml
+/// [workspace]
+/// members = ["packages/foo", "packages/bar"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Serialize, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+// The code block above doesn't survive being flattened into a description
+#[schemars(
+    description = "Packages that are built together, sharing a single build graph and installed dependencies."
+)]
+pub struct Workspace {
+    /// Directories containing member packages, relative to the workspace root.
+    pub members: Vec<PathBuf>,
+}
+
+/// A package that's a member of a [Workspace].
+#[derive(Clone, Debug)]
+pub struct WorkspaceMember {
+    /// Path to the member, relative to the workspace root.
+    pub path: PathBuf,
+
+    /// The member's own config.
+    pub config: Config,
+}
+
+impl Workspace {
+    /// Read the config of every member.
+    ///
+    /// `root_dir` is the directory containing the workspace config, and `root`
+    /// is the config itself. Members can't be nested workspaces, and names must be
+    /// unique across the workspace.
+    pub fn read_members(
+        &self,
+        root_dir: &Path,
+        root: &Config,
+    ) -> miette::Result<Vec<WorkspaceMember>> {
+        let mut members = Vec::new();
+        let mut seen: HashMap<String, PathBuf> = HashMap::new();
+        seen.insert(root.name.as_str().to_owned(), PathBuf::from("."));
+        for path in self.members.iter() {
+            if !path.is_relative() {
+                return Err(miette!(
+                    "workspace member {:?} should be a path relative to the workspace root",
+                    path.as_os_str()
+                ));
+            }
+            let config = read_config(root_dir.join(path).join(CONFIG_FILE_NAME))?;
+            if config.workspace.is_some() {
+                return Err(miette!(
+                    help = "list its members in the root workspace instead",
+                    "workspace member {:?} can't be a workspace itself",
+                    path.as_os_str()
+                ));
+            }
+            if let Some(other_path) = seen.insert(config.name.as_str().to_owned(), path.clone()) {
+                return Err(miette!(
+                    "workspace member {:?} has the same name as {:?}: {}",
+                    path.as_os_str(),
+                    other_path.as_os_str(),
+                    config.name.as_str()
+                ));
+            }
+            members.push(WorkspaceMember {
+                path: path.clone(),
+                config,
+            });
+        }
+        Ok(members)
+    }
+}
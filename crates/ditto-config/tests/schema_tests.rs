@@ -0,0 +1,29 @@
+//! The JSON Schemas in `schema/` are checked in, so that editors can point at
+//! them. These tests keep them in sync with the serde definitions.
+//!
+//! Run with `UPDATE_GOLDEN=1` to regenerate them.
+
+use schemars::schema::RootSchema;
+
+#[test]
+fn config_schema_is_up_to_date() {
+    assert_schema("schema/ditto.schema.json", ditto_config::config_schema());
+}
+
+#[test]
+fn package_set_schema_is_up_to_date() {
+    assert_schema(
+        "schema/package-set.schema.json",
+        ditto_config::package_set_schema(),
+    );
+}
+
+fn assert_schema(path: &str, schema: RootSchema) {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+    let expected = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+    let actual = std::fs::read_to_string(&path).unwrap_or_default();
+    if actual != expected && std::env::var("UPDATE_GOLDEN").is_ok() {
+        std::fs::write(&path, &expected).unwrap();
+    }
+    similar_asserts::assert_eq!(expected: expected, actual: actual);
+}
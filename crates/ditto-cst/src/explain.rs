@@ -0,0 +1,106 @@
+/// Build a table of explanations from the markdown files in `explanations/`.
+macro_rules! explanations {
+    ($($code:literal),* $(,)?) => {
+        &[$(($code, include_str!(concat!("../explanations/", $code, ".md")))),*]
+    };
+}
+
+/// Long-form explanations, keyed by diagnostic code.
+static EXPLANATIONS: &[(&str, &str)] = explanations!["E0001", "E0002", "E0003", "E0004"];
+
+/// Get the long-form explanation (as markdown) for the code of a
+/// [ParseErrorReport](crate::ParseErrorReport), e.g. `"E0001"`.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(explained, _)| explained.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
+
+/// All the codes that can be passed to [explain].
+pub fn explained_codes() -> impl Iterator<Item = &'static str> {
+    EXPLANATIONS.iter().map(|(code, _)| *code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseErrorReport;
+    use miette::{Diagnostic, NamedSource, SourceSpan};
+    use std::collections::HashSet;
+
+    fn input() -> NamedSource {
+        NamedSource::new("test", String::new())
+    }
+
+    fn span() -> SourceSpan {
+        SourceSpan::from((0, 0))
+    }
+
+    /// One of every [ParseErrorReport] variant.
+    fn all_parse_error_reports() -> Vec<ParseErrorReport> {
+        vec![
+            ParseErrorReport::InvalidToken {
+                input: input(),
+                location: span(),
+            },
+            ParseErrorReport::UnexpectedToken {
+                input: input(),
+                location: span(),
+                expected: String::new(),
+            },
+            ParseErrorReport::ExtraToken {
+                input: input(),
+                location: span(),
+            },
+            ParseErrorReport::UnexpectedEOF {
+                input: input(),
+                location: span(),
+                expected: String::new(),
+            },
+        ]
+    }
+
+    /// Adding a [ParseErrorReport] variant without adding it to
+    /// [all_parse_error_reports] will fail `explanations_are_complete`.
+    fn parse_error_report_index(report: &ParseErrorReport) -> usize {
+        match report {
+            ParseErrorReport::InvalidToken { .. } => 0,
+            ParseErrorReport::UnexpectedToken { .. } => 1,
+            ParseErrorReport::ExtraToken { .. } => 2,
+            ParseErrorReport::UnexpectedEOF { .. } => 3,
+        }
+    }
+    const PARSE_ERROR_REPORT_VARIANTS: usize = 4;
+
+    #[test]
+    fn explanations_are_complete() {
+        let reports = all_parse_error_reports();
+        let indices = reports
+            .iter()
+            .map(parse_error_report_index)
+            .collect::<HashSet<_>>();
+        assert_eq!(indices, (0..PARSE_ERROR_REPORT_VARIANTS).collect());
+
+        let mut used_codes = HashSet::new();
+        for report in reports {
+            let code = report
+                .code()
+                .unwrap_or_else(|| panic!("no code for {:?}", report.to_string()))
+                .to_string();
+            let explanation =
+                explain(&code).unwrap_or_else(|| panic!("no explanation for {}", code));
+            assert!(
+                explanation.starts_with(&format!("# {}: ", code)),
+                "bad heading for {}",
+                code
+            );
+            used_codes.insert(code);
+        }
+
+        // No stale explanations
+        for code in explained_codes() {
+            assert!(used_codes.contains(code), "{} isn't used", code);
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
-    Braces, Brackets, Expression, ModuleName, Name, PackageName, Parens, Pattern, ProperName,
-    QualifiedName, QualifiedProperName, Span, Token, Type, TypeAnnotation, TypeCallFunction,
-    UnusedName,
+    Braces, Brackets, Constructor, Declaration, Expression, ForeignValueDeclaration, ImportLine,
+    ModuleName, Name, PackageName, Parens, Pattern, ProperName, QualifiedName, QualifiedProperName,
+    Span, Token, Type, TypeAliasDeclaration, TypeAnnotation, TypeCallFunction, TypeDeclaration,
+    UnusedName, ValueDeclaration,
 };
 
 impl<Value> Token<Value> {
@@ -213,3 +214,103 @@ impl Pattern {
         }
     }
 }
+
+impl ImportLine {
+    /// Get the source span.
+    ///
+    /// Note that this doesn't include any leading comments.
+    pub fn get_span(&self) -> Span {
+        let span = self.import_keyword.0.get_span();
+        if let Some(ref imports) = self.imports {
+            span.merge(&imports.0.get_span())
+        } else if let Some((_, ref alias)) = self.alias {
+            span.merge(&alias.get_span())
+        } else {
+            span.merge(&self.module_name.get_span())
+        }
+    }
+}
+
+impl Declaration {
+    /// Get the source span.
+    ///
+    /// Note that this doesn't include any leading comments.
+    pub fn get_span(&self) -> Span {
+        match self {
+            Self::Value(value_declaration) => value_declaration.get_span(),
+            Self::Type(type_declaration) => type_declaration.get_span(),
+            Self::TypeAlias(type_alias_declaration) => type_alias_declaration.get_span(),
+            Self::ForeignValue(foreign_value_declaration) => foreign_value_declaration.get_span(),
+        }
+    }
+}
+
+impl ValueDeclaration {
+    /// Get the source span.
+    pub fn get_span(&self) -> Span {
+        self.name.get_span().merge(&self.expression.get_span())
+    }
+}
+
+impl TypeDeclaration {
+    /// Get the source span.
+    pub fn get_span(&self) -> Span {
+        match self {
+            Self::WithConstructors {
+                type_keyword,
+                head_constructor,
+                tail_constructors,
+                ..
+            } => {
+                let last_constructor_span = tail_constructors
+                    .last()
+                    .map_or_else(|| head_constructor.get_span(), |ctor| ctor.get_span());
+                type_keyword.0.get_span().merge(&last_constructor_span)
+            }
+            Self::WithoutConstructors {
+                type_keyword,
+                type_name,
+                type_variables,
+            } => {
+                if let Some(type_variables) = type_variables {
+                    type_keyword.0.get_span().merge(&type_variables.get_span())
+                } else {
+                    type_keyword.0.get_span().merge(&type_name.get_span())
+                }
+            }
+        }
+    }
+}
+
+impl<P> Constructor<P> {
+    /// Get the source span.
+    ///
+    /// Note that this doesn't include the leading pipe (if any).
+    pub fn get_span(&self) -> Span {
+        if let Some(ref fields) = self.fields {
+            self.constructor_name.get_span().merge(&fields.get_span())
+        } else {
+            self.constructor_name.get_span()
+        }
+    }
+}
+
+impl TypeAliasDeclaration {
+    /// Get the source span.
+    pub fn get_span(&self) -> Span {
+        self.type_keyword
+            .0
+            .get_span()
+            .merge(&self.aliased_type.get_span())
+    }
+}
+
+impl ForeignValueDeclaration {
+    /// Get the source span.
+    pub fn get_span(&self) -> Span {
+        self.foreign_keyword
+            .0
+            .get_span()
+            .merge(&self.type_annotation.get_span())
+    }
+}
@@ -5,6 +5,7 @@
 #[macro_use]
 extern crate lalrpop_util;
 
+mod explain;
 mod expression;
 mod get_span;
 mod lexer;
@@ -15,10 +16,13 @@ mod syntax;
 mod token;
 mod r#type;
 
+pub use explain::{explain, explained_codes};
 pub use expression::*;
 pub use module::*;
 pub use name::*;
-pub use parser::{partial_parse_header, partial_parse_header_and_imports, ParseError};
+pub use parser::{
+    partial_parse_header, partial_parse_header_and_imports, ParseError, ParseErrorReport,
+};
 pub use r#type::*;
 pub use syntax::*;
 pub use token::*;
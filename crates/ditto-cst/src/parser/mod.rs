@@ -27,7 +27,7 @@ pub enum ParseError {
 #[derive(Error, Debug, Diagnostic)]
 pub enum ParseErrorReport {
     #[error("invalid token")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0001), severity(Error))]
     InvalidToken {
         /// The offending input.
         #[source_code]
@@ -37,7 +37,7 @@ pub enum ParseErrorReport {
         location: SourceSpan,
     },
     #[error("unexpected token")]
-    #[diagnostic(severity(Error), help("{expected}"))]
+    #[diagnostic(code(E0002), severity(Error), help("{expected}"))]
     UnexpectedToken {
         /// The offending input.
         #[source_code]
@@ -49,7 +49,7 @@ pub enum ParseErrorReport {
         expected: String,
     },
     #[error("extra token")]
-    #[diagnostic(severity(Error))]
+    #[diagnostic(code(E0003), severity(Error))]
     ExtraToken {
         /// The offending input.
         #[source_code]
@@ -59,7 +59,7 @@ pub enum ParseErrorReport {
         location: SourceSpan,
     },
     #[error("unexpected eof")]
-    #[diagnostic(severity(Error), help("{expected}"))]
+    #[diagnostic(code(E0004), severity(Error), help("{expected}"))]
     UnexpectedEOF {
         /// The offending input.
         #[source_code]
@@ -14,6 +14,7 @@ mod token;
 mod r#type;
 
 use config::{INDENT_WIDTH, MAX_WIDTH};
+use std::ops::Range;
 
 /// Pretty-print a CST module.
 pub fn format_module(module: ditto_cst::Module) -> String {
@@ -43,7 +44,77 @@ pub struct Edit<T = Vec<u8>> {
 /// Returns the edits that must be made to `source` in order to make it pretty.
 pub fn format_module_edits(module: ditto_cst::Module, source: &[u8]) -> Vec<Edit> {
     let formatted = format_module(module).as_bytes().to_owned();
-    let diffs = similar::capture_diff_slices(similar::Algorithm::Myers, source, &formatted);
+    diff_edits(source, &formatted)
+}
+
+/// Returns the edits that must be made to `source` in order to make the declarations
+/// overlapping the `from..to` byte range pretty.
+///
+/// The rest of the module is left untouched.
+pub fn format_range_edits(
+    module: ditto_cst::Module,
+    source: &[u8],
+    from: usize,
+    to: usize,
+) -> Vec<Edit> {
+    let regions = declaration_regions(&module, source);
+    if !regions
+        .iter()
+        .any(|region| region.start <= to && from <= region.end)
+    {
+        return vec![];
+    }
+    // Find each declaration again in the formatted module, so that every region
+    // can be diffed against its formatted counterpart. That way edits can't spill
+    // over into the surrounding (unselected) source.
+    let formatted = format_module(module);
+    let formatted_regions = match ditto_cst::Module::parse(&formatted) {
+        Ok(formatted_module) => declaration_regions(&formatted_module, formatted.as_bytes()),
+        Err(_) => return vec![],
+    };
+    if formatted_regions.len() != regions.len() {
+        return vec![];
+    }
+    regions
+        .into_iter()
+        .zip(formatted_regions)
+        .filter(|(region, _)| region.start <= to && from <= region.end)
+        .flat_map(|(region, formatted_region)| {
+            diff_edits(
+                &source[region.clone()],
+                &formatted.as_bytes()[formatted_region],
+            )
+            .into_iter()
+            .map(move |edit| Edit {
+                from: region.start + edit.from,
+                to: region.start + edit.to,
+                replacement: edit.replacement,
+            })
+        })
+        .collect()
+}
+
+/// The source regions belonging to each declaration.
+///
+/// A region starts at the first token of the declaration and extends to the end of the
+/// line, so that a trailing comment is included.
+fn declaration_regions(module: &ditto_cst::Module, source: &[u8]) -> Vec<Range<usize>> {
+    module
+        .declarations
+        .iter()
+        .map(|declaration| {
+            let span = declaration.get_span();
+            let end = source[span.end_offset..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(source.len(), |i| span.end_offset + i);
+            span.start_offset..end
+        })
+        .collect()
+}
+
+fn diff_edits(source: &[u8], formatted: &[u8]) -> Vec<Edit> {
+    let diffs = similar::capture_diff_slices(similar::Algorithm::Myers, source, formatted);
     diffs
         .into_iter()
         .filter_map(|diff| match diff {
@@ -126,3 +197,39 @@ mod test_macros {
 
     pub(crate) use assert_expression_fmt;
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn it_formats_ranges() {
+        let source = "module Test exports (..)\n\n\nfive  =  5\n\n\nsix  =  6\n";
+        let module = ditto_cst::Module::parse(source).unwrap();
+        let offset = source.find("six").unwrap();
+        let edits = crate::format_range_edits(module, source.as_bytes(), offset, offset);
+        let mut formatted = source.as_bytes().to_vec();
+        for edit in edits.into_iter().rev() {
+            formatted.splice(edit.from..edit.to, edit.replacement);
+        }
+        similar_asserts::assert_eq!(
+            got: String::from_utf8(formatted).unwrap(),
+            want: "module Test exports (..)\n\n\nfive  =  5\n\n\nsix = 6\n"
+        );
+    }
+
+    #[test]
+    fn it_formats_ranges_spanning_declarations() {
+        let source = "module Test exports (..)\n\n\nfive  =  5\n\n\nsix  =  6\n\n\nseven  =  7\n";
+        let module = ditto_cst::Module::parse(source).unwrap();
+        let from = source.find("five").unwrap();
+        let to = source.find("six").unwrap();
+        let edits = crate::format_range_edits(module, source.as_bytes(), from, to);
+        let mut formatted = source.as_bytes().to_vec();
+        for edit in edits.into_iter().rev() {
+            formatted.splice(edit.from..edit.to, edit.replacement);
+        }
+        similar_asserts::assert_eq!(
+            got: String::from_utf8(formatted).unwrap(),
+            want: "module Test exports (..)\n\n\nfive = 5\n\n\nsix = 6\n\n\nseven  =  7\n"
+        );
+    }
+}
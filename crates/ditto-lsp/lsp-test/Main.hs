@@ -14,6 +14,7 @@ ghcid --command='stack repl ditto-lsp-test'
 module Main (main) where
 
 import "base" Control.Monad.IO.Class (liftIO)
+import "base" Data.List (sort)
 import "text" Data.Text.IO qualified as TextIO
 import "lsp-test" Language.LSP.Test qualified as Lsp
 import "lsp-types" Language.LSP.Types as LspTypes
@@ -34,9 +35,18 @@ main =
 test :: String -> IO ()
 test lspExe = do
   testSemanticTokens lspExe
+  testSemanticTokensChecked lspExe
   testFormatting lspExe
+  testRangeFormatting lspExe
+  testDocumentHighlight lspExe
+  testCallHierarchy lspExe
   testDiagnostics0 lspExe
   testDiagnostics1 lspExe
+  testDiagnosticsStandalone lspExe
+  testDiagnosticsConfig lspExe
+  testDiagnosticsProject lspExe
+  testDiagnosticsDependents lspExe
+  testDiagnosticsWorkspace lspExe
 
 testSemanticTokens :: String -> IO ()
 testSemanticTokens lspExe =
@@ -45,6 +55,52 @@ testSemanticTokens lspExe =
     Just LspTypes.SemanticTokens {} <- Lsp.getSemanticTokens example
     pure ()
 
+testSemanticTokensChecked :: String -> IO ()
+testSemanticTokensChecked lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/semantic-tokens-checked" do
+    example <- Lsp.openDoc "ditto-src/Example.ditto" "ditto"
+    Just LspTypes.SemanticTokens {_xdata = LspTypes.List tokenData} <- Lsp.getSemanticTokens example
+    let tokens = decodeSemanticTokens tokenData
+    case filter (`notElem` tokens) expectedTokens of
+      [] -> pure ()
+      missing ->
+        liftIO (die ("missing semantic tokens: " <> show missing <> "\ngot: " <> show tokens))
+  where
+    -- Token types and modifiers are indices into `semantic_tokens::legend`
+    expectedTokens :: [DecodedToken]
+    expectedTokens =
+      [ (4, 11, 1, typeParameter, declaration), -- type Maybe(a)
+        (4, 21, 1, typeParameter, 0), -- Just(a)
+        (6, 20, 6, type_, 0), -- (String) -> Int
+        (8, 0, 7, function, declaration), -- example
+        (8, 14, 1, parameter, declaration), -- x
+        (8, 17, 3, type_, 0), -- x: Int
+        (8, 22, 6, parameter, declaration + unused), -- unused
+        (14, 0, 8, function, declaration + unused), -- identity
+        (14, 22, 1, typeParameter, 0) -- value: a
+      ]
+    type_, typeParameter, function, parameter, declaration, unused :: UInt
+    type_ = 3
+    typeParameter = 4
+    function = 10
+    parameter = 11
+    declaration = 1
+    unused = 4
+
+-- | (line, column, length, token type, token modifiers)
+type DecodedToken = (UInt, UInt, UInt, UInt, UInt)
+
+-- | Semantic tokens are encoded relative to the previous token.
+decodeSemanticTokens :: [UInt] -> [DecodedToken]
+decodeSemanticTokens = go 0 0
+  where
+    go :: UInt -> UInt -> [UInt] -> [DecodedToken]
+    go line column (deltaLine : deltaColumn : len : tokenType : modifiers : rest) =
+      let line' = line + deltaLine
+          column' = if deltaLine == 0 then column + deltaColumn else deltaColumn
+       in (line', column', len, tokenType, modifiers) : go line' column' rest
+    go _ _ _ = []
+
 testFormatting :: String -> IO ()
 testFormatting lspExe =
   runSession lspExe "crates/ditto-lsp/fixtures/formatting" do
@@ -64,6 +120,86 @@ testFormatting lspExe =
 
     pure ()
 
+testRangeFormatting :: String -> IO ()
+testRangeFormatting lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/range-formatting" do
+    example <- Lsp.openDoc "Example.ditto" "ditto"
+    Lsp.formatRange
+      example
+      LspTypes.FormattingOptions
+        { LspTypes._tabSize = 4,
+          LspTypes._insertSpaces = True,
+          LspTypes._trimTrailingWhitespace = Nothing,
+          LspTypes._insertFinalNewline = Nothing,
+          LspTypes._trimFinalNewlines = Nothing
+        }
+      (LspTypes.Range (LspTypes.Position 6 0) (LspTypes.Position 6 0))
+    contents <- Lsp.documentContents example
+    if contents == "module Example exports (..)\n\n\nfive  =  5\n\n\nsix = 6\n"
+      then pure ()
+      else liftIO (die ("unexpected range formatting result: " <> show contents))
+
+testDocumentHighlight :: String -> IO ()
+testDocumentHighlight lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/document-highlight" do
+    example <- Lsp.openDoc "ditto-src/Example.ditto" "ditto"
+    LspTypes.List [_, _, _] <- Lsp.getHighlights example (LspTypes.Position 2 0)
+    pure ()
+
+testCallHierarchy :: String -> IO ()
+testCallHierarchy lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/call-hierarchy" do
+    example <- Lsp.openDoc "ditto-src/Example.ditto" "ditto"
+    [five@LspTypes.CallHierarchyItem {_name = "five"}] <- prepare example (LspTypes.Position 2 0)
+    incoming <-
+      Lsp.incomingCalls
+        LspTypes.CallHierarchyIncomingCallsParams
+          { _workDoneToken = Nothing,
+            _partialResultToken = Nothing,
+            _item = five
+          }
+    let callers =
+          sort
+            [ (name, length ranges)
+              | LspTypes.CallHierarchyIncomingCall
+                  { _from = LspTypes.CallHierarchyItem {_name = name},
+                    _fromRanges = LspTypes.List ranges
+                  } <-
+                  incoming
+            ]
+    -- fives calls five twice, and Other.six calls it once
+    if callers == [("fives", 2), ("six", 1)]
+      then pure ()
+      else liftIO (die ("unexpected incoming calls: " <> show callers))
+    [fives@LspTypes.CallHierarchyItem {_name = "fives"}] <- prepare example (LspTypes.Position 4 0)
+    outgoing <-
+      Lsp.outgoingCalls
+        LspTypes.CallHierarchyOutgoingCallsParams
+          { _workDoneToken = Nothing,
+            _partialResultToken = Nothing,
+            _item = fives
+          }
+    let callees =
+          [ (name, length ranges)
+            | LspTypes.CallHierarchyOutgoingCall
+                { _to = LspTypes.CallHierarchyItem {_name = name},
+                  _fromRanges = LspTypes.List ranges
+                } <-
+                outgoing
+          ]
+    if callees == [("five", 2)]
+      then pure ()
+      else liftIO (die ("unexpected outgoing calls: " <> show callees))
+  where
+    prepare :: LspTypes.TextDocumentIdentifier -> LspTypes.Position -> Lsp.Session [LspTypes.CallHierarchyItem]
+    prepare document position =
+      Lsp.prepareCallHierarchy
+        LspTypes.CallHierarchyPrepareParams
+          { _textDocument = document,
+            _position = position,
+            _workDoneToken = Nothing
+          }
+
 testDiagnostics0 :: String -> IO ()
 testDiagnostics0 lspExe =
   runSession lspExe "crates/ditto-lsp/fixtures/diagnostics-0" do
@@ -92,6 +228,88 @@ testDiagnostics1 lspExe =
       Lsp.waitForDiagnostics
     pure ()
 
+testDiagnosticsStandalone :: String -> IO ()
+testDiagnosticsStandalone lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/diagnostics-standalone" do
+    _ <- Lsp.openDoc "Broken.ditto" "ditto"
+    [ LspTypes.Diagnostic
+        { _source =
+            Just "ditto",
+          _severity = Just LspTypes.DsError
+        }
+      ] <-
+      Lsp.waitForDiagnostics
+    pure ()
+
+testDiagnosticsConfig :: String -> IO ()
+testDiagnosticsConfig lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/diagnostics-config" do
+    _ <- Lsp.openDoc "ditto.toml" "toml"
+    [ LspTypes.Diagnostic
+        { _source =
+            Just "ditto",
+          _severity = Just LspTypes.DsError
+        }
+      ] <-
+      Lsp.waitForDiagnostics
+    pure ()
+
+testDiagnosticsProject :: String -> IO ()
+testDiagnosticsProject lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/diagnostics-project" do
+    -- A.ditto is never opened
+    [ LspTypes.Diagnostic
+        { _source =
+            Just "ditto",
+          _severity = Just LspTypes.DsError,
+          _message = "types don't unify \nexpected Int\ngot String"
+        }
+      ] <-
+      Lsp.waitForDiagnostics
+    pure ()
+
+testDiagnosticsDependents :: String -> IO ()
+testDiagnosticsDependents lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/diagnostics-dependents" do
+    b <- Lsp.openDoc "ditto-src/B.ditto" "ditto"
+    Lsp.changeDoc
+      b
+      [ LspTypes.TextDocumentContentChangeEvent
+          { _range = Just (LspTypes.Range (LspTypes.Position 2 7) (LspTypes.Position 2 14)),
+            _rangeLength = Nothing,
+            _text = ""
+          },
+        LspTypes.TextDocumentContentChangeEvent
+          { _range = Just (LspTypes.Range (LspTypes.Position 2 7) (LspTypes.Position 2 7)),
+            _rangeLength = Nothing,
+            _text = "String = \"five\""
+          }
+      ]
+    -- A.ditto imports B.ditto, so should be re-checked
+    [ LspTypes.Diagnostic
+        { _source =
+            Just "ditto",
+          _severity = Just LspTypes.DsError,
+          _message = "types don't unify \nexpected Int\ngot String"
+        }
+      ] <-
+      Lsp.waitForDiagnostics
+    pure ()
+
+testDiagnosticsWorkspace :: String -> IO ()
+testDiagnosticsWorkspace lspExe =
+  runSession lspExe "crates/ditto-lsp/fixtures/diagnostics-workspace" do
+    -- Foo.ditto belongs to a workspace member, and is never opened
+    [ LspTypes.Diagnostic
+        { _source =
+            Just "ditto",
+          _severity = Just LspTypes.DsError,
+          _message = "types don't unify \nexpected Int\ngot String"
+        }
+      ] <-
+      Lsp.waitForDiagnostics
+    pure ()
+
 runSession :: String -> FilePath -> Lsp.Session a -> IO a
 runSession lspExe rootDir session = do
   Process.withCreateProcess proc \(Just stdin) (Just stdout) _ _ -> do
@@ -0,0 +1,149 @@
+use crate::{
+    common::offset_to_position,
+    db::{self, Db},
+    references::{self, Target},
+    Backend,
+};
+use ditto_ast::{self as ast, FullyQualifiedName};
+use std::collections::BTreeMap;
+use tower_lsp::lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Range, SymbolKind, Url,
+};
+
+pub fn prepare(backend: &Backend, uri: &Url, offset: usize) -> Option<Vec<CallHierarchyItem>> {
+    let (key, module) = backend.checked_module(uri)?;
+    let target = references::occurrences(&key, &module)
+        .into_iter()
+        .find(|occurrence| occurrence.span.contains(offset))
+        .and_then(|occurrence| match occurrence.target {
+            Target::Value(value) => Some(value),
+            Target::Foreign(_) | Target::Local(_) => None,
+        })?;
+    Some(vec![call_hierarchy_item(backend, &target)?])
+}
+
+pub fn incoming_calls(
+    backend: &Backend,
+    item: &CallHierarchyItem,
+) -> Option<Vec<CallHierarchyIncomingCall>> {
+    let target = item_target(backend, item)?;
+    let mut calls = Vec::new();
+    // Only the module itself and the modules that (transitively) import it
+    // can reference the target, so there's no need to look anywhere else.
+    let mut uris = backend.transitive_dependents(&item.uri);
+    uris.push(item.uri.clone());
+    for uri in uris.iter() {
+        if let Some((key, module)) = backend.checked_module(uri) {
+            let mut callers: BTreeMap<ast::Name, Vec<ast::Span>> = BTreeMap::new();
+            for occurrence in references::occurrences(&key, &module) {
+                if !occurrence.is_binding && occurrence.target == Target::Value(target.clone()) {
+                    callers
+                        .entry(occurrence.within)
+                        .or_default()
+                        .push(occurrence.span);
+                }
+            }
+            for (caller, spans) in callers {
+                let caller = ast::FullyQualified {
+                    module_name: key.clone(),
+                    value: caller,
+                };
+                if let Some(from) = call_hierarchy_item(backend, &caller) {
+                    calls.push(CallHierarchyIncomingCall {
+                        from_ranges: spans_to_ranges(backend, uri, spans),
+                        from,
+                    });
+                }
+            }
+        }
+    }
+    Some(calls)
+}
+
+pub fn outgoing_calls(
+    backend: &Backend,
+    item: &CallHierarchyItem,
+) -> Option<Vec<CallHierarchyOutgoingCall>> {
+    let target = item_target(backend, item)?;
+    let (key, module) = backend.checked_module(&item.uri)?;
+    let mut callees: Vec<(FullyQualifiedName, Vec<ast::Span>)> = Vec::new();
+    for occurrence in references::occurrences(&key, &module) {
+        if occurrence.is_binding || occurrence.within != target.value {
+            continue;
+        }
+        if let Target::Value(callee) = occurrence.target {
+            if let Some((_, spans)) = callees.iter_mut().find(|(name, _)| *name == callee) {
+                spans.push(occurrence.span);
+            } else {
+                callees.push((callee, vec![occurrence.span]));
+            }
+        }
+    }
+    let calls = callees
+        .into_iter()
+        .filter_map(|(callee, spans)| {
+            Some(CallHierarchyOutgoingCall {
+                to: call_hierarchy_item(backend, &callee)?,
+                from_ranges: spans_to_ranges(backend, &item.uri, spans),
+            })
+        })
+        .collect();
+    Some(calls)
+}
+
+fn call_hierarchy_item(
+    backend: &Backend,
+    target: &FullyQualifiedName,
+) -> Option<CallHierarchyItem> {
+    let document = backend.db.get_document(&target.module_name)?;
+    let module = db::parse_and_check(&backend.db, document, target.module_name.0.clone())?;
+    let module_value = module.values.get(&target.value)?;
+    let indexed_text = lsp_document::IndexedText::new(document.rope(&backend.db).to_string());
+    let name_span = module_value.name_span;
+    let span = name_span.merge(&module_value.expression.get_span());
+    let kind = match module_value.expression.get_type() {
+        ast::Type::Function { .. } => SymbolKind::FUNCTION,
+        _ => SymbolKind::VARIABLE,
+    };
+    Some(CallHierarchyItem {
+        name: target.value.0.clone(),
+        kind,
+        tags: None,
+        detail: Some(target.module_name.1.to_string()),
+        uri: document.uri(&backend.db).clone(),
+        range: Range {
+            start: offset_to_position(span.start_offset, &indexed_text)?,
+            end: offset_to_position(span.end_offset, &indexed_text)?,
+        },
+        selection_range: Range {
+            start: offset_to_position(name_span.start_offset, &indexed_text)?,
+            end: offset_to_position(name_span.end_offset, &indexed_text)?,
+        },
+        data: None,
+    })
+}
+
+/// Recover the value that a [CallHierarchyItem] was created for.
+fn item_target(backend: &Backend, item: &CallHierarchyItem) -> Option<FullyQualifiedName> {
+    let (key, _) = backend.documents.get(&item.uri)?;
+    Some(ast::FullyQualified {
+        module_name: key.clone()?,
+        value: ast::Name(item.name.clone()),
+    })
+}
+
+fn spans_to_ranges(backend: &Backend, uri: &Url, spans: Vec<ast::Span>) -> Vec<Range> {
+    let rope = match backend.documents.get(uri) {
+        Some((_, document)) => document.rope(&backend.db),
+        None => return vec![],
+    };
+    let indexed_text = lsp_document::IndexedText::new(rope.to_string());
+    spans
+        .into_iter()
+        .filter_map(|span| {
+            let start = offset_to_position(span.start_offset, &indexed_text)?;
+            let end = offset_to_position(span.end_offset, &indexed_text)?;
+            Some(Range { start, end })
+        })
+        .collect()
+}
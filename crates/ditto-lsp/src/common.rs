@@ -1,7 +1,8 @@
 use ditto_cst::{self as cst, Span};
 use ropey::Rope;
 use tower_lsp::lsp_types::{
-    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, Position, Range, Url,
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, Location,
+    NumberOrString, Position, Range, Url,
 };
 
 pub(crate) fn parse_error_into_lsp_diagnostic(
@@ -32,8 +33,30 @@ pub(crate) fn warning_into_lsp_diagnostic(
     rope: &Rope,
 ) -> Option<Diagnostic> {
     let span = warning_span(&warning);
+    let tags = warning_tags(&warning);
     let report = miette::Report::from(warning.into_report());
-    report_into_lsp_diagnostic(report, DiagnosticSeverity::WARNING, span, uri, rope)
+    let diagnostic =
+        report_into_lsp_diagnostic(report, DiagnosticSeverity::WARNING, span, uri, rope)?;
+    Some(Diagnostic { tags, ..diagnostic })
+}
+
+pub(crate) fn config_error_into_lsp_diagnostic(
+    report: miette::Report,
+    uri: &Url,
+    rope: &Rope,
+) -> Option<Diagnostic> {
+    let span = report
+        .labels()
+        .and_then(|mut labels| labels.next())
+        .map(|label| Span {
+            start_offset: label.offset(),
+            end_offset: label.offset() + label.len(),
+        })
+        .unwrap_or(Span {
+            start_offset: 0,
+            end_offset: 0,
+        });
+    report_into_lsp_diagnostic(report, DiagnosticSeverity::ERROR, span, uri, rope)
 }
 
 pub(crate) fn report_into_lsp_diagnostic(
@@ -71,6 +94,9 @@ pub(crate) fn report_into_lsp_diagnostic(
     Some(Diagnostic {
         message,
         range,
+        code: report
+            .code()
+            .map(|code| NumberOrString::String(code.to_string())),
         source: Some("ditto".to_string()),
         severity: Some(severity),
         related_information,
@@ -217,3 +243,23 @@ fn warning_span(warning: &ditto_checker::Warning) -> Span {
         | RedundantMatchPattern { span, .. } => *span,
     }
 }
+
+fn warning_tags(warning: &ditto_checker::Warning) -> Option<Vec<DiagnosticTag>> {
+    use ditto_checker::Warning::*;
+    match warning {
+        UnusedFunctionBinder { .. }
+        | UnusedPatternBinder { .. }
+        | UnusedEffectBinder { .. }
+        | UnusedLetBinder { .. }
+        | UnusedValueDeclaration { .. }
+        | UnusedForeignValue { .. }
+        | UnusedTypeDeclaration { .. }
+        | UnusedTypeConstructors { .. }
+        | UnusedImport { .. } => Some(vec![DiagnosticTag::UNNECESSARY]),
+        DuplicateValueExport { .. }
+        | DuplicateTypeExport { .. }
+        | DuplicateValueImport { .. }
+        | DuplicateTypeImport { .. }
+        | RedundantMatchPattern { .. } => None,
+    }
+}
@@ -12,6 +12,7 @@ pub struct Jar(
     Document,
     Diagnostics,
     Imports,
+    module_imports,
     parse_and_check,
     prepare_checking_environment,
 );
@@ -75,6 +76,10 @@ impl Database {
     pub(crate) fn remove_document(&mut self, key: &FullyQualifiedModuleName) {
         self.documents.remove(key);
     }
+
+    pub(crate) fn clear_documents(&mut self) {
+        self.documents.clear();
+    }
 }
 
 pub type DocumentVersion = Option<i32>;
@@ -123,7 +128,7 @@ pub fn parse_and_check(
             None
         }
         Ok(cst_module) => {
-            let imports = extract_imports(db, &cst_module);
+            let imports = module_imports(db, source);
             let everything = prepare_checking_environment(db, imports, package);
             match checker::check_module(&everything, cst_module) {
                 Err(err) => {
@@ -169,19 +174,32 @@ pub fn parse_and_check(
     }
 }
 
+/// The modules imported by a module, i.e. its outgoing edges in the module graph.
+///
+/// Note that imports without an explicit package refer to the package of the
+/// importing module.
 #[salsa::interned (jar = Jar)]
-struct Imports {
+pub struct Imports {
     #[return_ref]
-    imports: Vec<FullyQualifiedModuleName>,
+    pub imports: Vec<FullyQualifiedModuleName>,
+}
+
+#[salsa::tracked(jar = Jar)]
+pub fn module_imports(db: &dyn Db, source: Document) -> Imports {
+    let input = source.rope(db).to_string();
+    let import_lines = cst::partial_parse_header_and_imports(&input)
+        .map(|(_header, import_lines)| import_lines)
+        .unwrap_or_default();
+    extract_imports(db, &import_lines)
 }
 
-fn extract_imports(db: &dyn Db, cst_module: &cst::Module) -> Imports {
+fn extract_imports(db: &dyn Db, import_lines: &[cst::ImportLine]) -> Imports {
     let mut imports = vec![];
     for cst::ImportLine {
         package,
         module_name,
         ..
-    } in cst_module.imports.iter()
+    } in import_lines.iter()
     {
         let package_name: Option<ast::PackageName> =
             package.as_ref().map(|parens| parens.value.clone().into());
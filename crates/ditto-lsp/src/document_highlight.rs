@@ -0,0 +1,36 @@
+use crate::{common::offset_to_position, references};
+use ditto_ast::{self as ast, FullyQualifiedModuleName};
+use ropey::Rope;
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Range};
+
+pub fn document_highlight(
+    key: &FullyQualifiedModuleName,
+    module: &ast::Module,
+    rope: &Rope,
+    offset: usize,
+) -> Option<Vec<DocumentHighlight>> {
+    let occurrences = references::occurrences(key, module);
+    let target = occurrences
+        .iter()
+        .find(|occurrence| occurrence.span.contains(offset))?
+        .target
+        .clone();
+    let indexed_text = lsp_document::IndexedText::new(rope.to_string());
+    let highlights = occurrences
+        .into_iter()
+        .filter(|occurrence| occurrence.target == target)
+        .filter_map(|occurrence| {
+            let start = offset_to_position(occurrence.span.start_offset, &indexed_text)?;
+            let end = offset_to_position(occurrence.span.end_offset, &indexed_text)?;
+            Some(DocumentHighlight {
+                range: Range { start, end },
+                kind: Some(if occurrence.is_binding {
+                    DocumentHighlightKind::WRITE
+                } else {
+                    DocumentHighlightKind::READ
+                }),
+            })
+        })
+        .collect();
+    Some(highlights)
+}
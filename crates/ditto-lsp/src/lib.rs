@@ -1,21 +1,32 @@
 #![feature(box_patterns)]
 
+mod call_hierarchy;
 mod common;
 mod db;
+mod document_highlight;
 mod goto_definition;
 mod hover;
 mod locate;
+mod references;
 mod semantic_tokens;
 mod test;
 
 pub use test::main as main_test;
 
-use common::{offset_to_position, position_to_offset};
+use common::{
+    config_error_into_lsp_diagnostic, offset_to_position, parse_error_into_lsp_diagnostic,
+    position_to_offset,
+};
 use ditto_ast::{self as ast, FullyQualifiedModuleName};
 use ditto_cst as cst;
 use ditto_make::find_ditto_files;
 use ropey::Rope;
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    path::PathBuf,
+    sync::Arc,
+};
 use tokio::sync::Mutex;
 use tower_lsp::{jsonrpc, lsp_types::*, Client, LanguageServer};
 
@@ -51,7 +62,9 @@ struct Backend {
     db: db::Database,
     documents: Documents,
     highlight_query: Option<ditto_highlight::Query>,
+    root_uri: Option<Url>,
     project_config: Option<ProjectConfig>,
+    watch_files: bool,
 }
 
 type Documents = HashMap<Url, (Option<FullyQualifiedModuleName>, db::Document)>;
@@ -60,23 +73,90 @@ type Diagnostics = Vec<db::Diagnostic>;
 
 #[derive(Debug)]
 struct ProjectConfig {
-    /// Canonical path to .ditto/packages
+    /// Path to the project root, i.e. the directory containing ditto.toml
+    root_dir: PathBuf,
+    /// Path to .ditto/packages
     packages_dir: PathBuf,
+    /// Canonical paths to workspace members, and their package names
+    members: Vec<(PathBuf, ast::PackageName)>,
 }
 
 impl ProjectConfig {
-    fn initialize(root: Url) -> Option<Self> {
-        let mut config = root.to_file_path().ok()?;
+    fn initialize(root: &Url) -> Option<Self> {
+        let root_dir = root.to_file_path().ok()?;
+        let mut config = root_dir.clone();
         config.push(ditto_config::CONFIG_FILE_NAME);
         if config.exists() {
             let config = ditto_config::read_config(&config).ok()?;
-            let mut packages_dir = config.ditto_dir;
-            packages_dir.push("packages"); // HACK
-            return Some(ProjectConfig { packages_dir });
+            let packages_dir = root_dir.join(config.installed_packages_dir());
+            let members = config
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.read_members(&root_dir, &config).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|member| {
+                    let dir = root_dir.join(member.path).canonicalize().ok()?;
+                    let package_name = ast::PackageName(member.config.name.into_string());
+                    Some((dir, package_name))
+                })
+                .collect();
+            return Some(ProjectConfig {
+                root_dir,
+                packages_dir,
+                members,
+            });
         }
         None
     }
 
+    fn is_root_config_file(&self, uri: &Url) -> bool {
+        let mut config = self.root_dir.clone();
+        config.push(ditto_config::CONFIG_FILE_NAME);
+        uri.to_file_path().map_or(false, |path| path == config)
+    }
+
+    /// Is this the root config file, or the config file of a workspace member?
+    fn is_project_config_file(&self, uri: &Url) -> bool {
+        uri.to_file_path().map_or(false, |path| {
+            std::iter::once(&self.root_dir)
+                .chain(self.members.iter().map(|(dir, _)| dir))
+                .any(|dir| path == dir.join(ditto_config::CONFIG_FILE_NAME))
+        })
+    }
+
+    fn is_member(&self, package_name: &ast::PackageName) -> bool {
+        self.members
+            .iter()
+            .any(|(_, member_name)| member_name == package_name)
+    }
+
+    fn is_package_file(&self, uri: &Url) -> bool {
+        uri.to_file_path().map_or(false, |path| {
+            path.starts_with(&self.packages_dir)
+                || self.members.iter().any(|(dir, _)| path.starts_with(dir))
+        })
+    }
+
+    fn file_system_watchers(&self) -> Vec<FileSystemWatcher> {
+        let mut watchers = vec![];
+        if let Ok(packages_dir) = self.packages_dir.strip_prefix(&self.root_dir) {
+            watchers.push(FileSystemWatcher {
+                glob_pattern: format!("**/{}/**", packages_dir.to_string_lossy()),
+                kind: None,
+            });
+        }
+        for (dir, _) in self.members.iter() {
+            if let Ok(member_dir) = dir.strip_prefix(&self.root_dir) {
+                watchers.push(FileSystemWatcher {
+                    glob_pattern: format!("**/{}/**/*.ditto", member_dir.to_string_lossy()),
+                    kind: None,
+                });
+            }
+        }
+        watchers
+    }
+
     fn package_dirs(&self) -> Vec<PathBuf> {
         let mut dirs = vec![];
         if let Ok(entries) = std::fs::read_dir(&self.packages_dir) {
@@ -92,6 +172,15 @@ impl ProjectConfig {
     }
 
     fn uri_to_package_name(&self, uri: &Url) -> Result<Option<ast::PackageName>, ()> {
+        // NOTE: member paths are canonical, so this needs checking before the
+        // packages directory (which links to them)
+        let path = uri.to_file_path()?;
+        for (dir, package_name) in self.members.iter() {
+            if path.starts_with(dir) {
+                return Ok(Some(package_name.clone()));
+            }
+        }
+
         if !self.packages_dir.exists() {
             // packages directory doesn't exist,
             // so assume this uri belongs to the current package
@@ -99,7 +188,7 @@ impl ProjectConfig {
         }
 
         let packages_dir = self.packages_dir.canonicalize().map_err(|_| ())?;
-        let uri = uri.to_file_path()?;
+        let uri = path;
         if !uri.starts_with(&packages_dir) {
             // uri isn't beneath the packages directory
             return Ok(None);
@@ -122,7 +211,9 @@ impl Backend {
             db: db::Database::default(),
             documents: Documents::new(),
             highlight_query: ditto_highlight::try_init_query().ok(),
+            root_uri: None,
             project_config: None,
+            watch_files: false,
         }
     }
 
@@ -130,6 +221,8 @@ impl Backend {
         ServerCapabilities {
             hover_provider: Some(HoverProviderCapability::Simple(true)),
             definition_provider: Some(OneOf::Left(true)),
+            document_highlight_provider: Some(OneOf::Left(true)),
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
             ..self.non_project_server_capabilities()
         }
     }
@@ -141,6 +234,12 @@ impl Backend {
             )),
 
             document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
+            document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                first_trigger_character: "}".to_string(),
+                // For `end`
+                more_trigger_character: Some(vec!["d".to_string()]),
+            }),
             semantic_tokens_provider: self.highlight_query.as_ref().map(|_| {
                 SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
                     full: Some(SemanticTokensFullOptions::Bool(true)),
@@ -234,8 +333,40 @@ impl Backend {
         }
     }
 
+    /// Checks a config file, more strictly if it's the root config of the project.
+    fn check_config(&self, uri: &Url, version: db::DocumentVersion, rope: &Rope) -> Diagnostics {
+        let is_root = self
+            .project_config
+            .as_ref()
+            .map_or(false, |project_config| {
+                project_config.is_root_config_file(uri)
+            });
+        // NOTE: the version might have a `*` suffix, if it was built from a dirty tree
+        let ditto_version = semver::Version::parse(self.version.trim_end_matches('*')).ok();
+        let input = rope.to_string();
+        let diagnostic = ditto_config::Config::parse(uri.path(), &input)
+            .and_then(|config| {
+                if is_root {
+                    config.check(uri.path(), &input, ditto_version.as_ref())?;
+                }
+                Ok(config)
+            })
+            .err()
+            .and_then(|report| config_error_into_lsp_diagnostic(report, uri, rope));
+        vec![db::Diagnostic {
+            uri: uri.clone(),
+            version,
+            diagnostic,
+        }]
+    }
+
     fn check_module(&self, uri: &Url) -> (Option<ast::Module>, Diagnostics) {
         if let Some((_, document)) = self.documents.get(uri) {
+            if is_config_file(uri) {
+                let version = *document.version(&self.db);
+                let rope = document.rope(&self.db);
+                return (None, self.check_config(uri, version, rope));
+            }
             if let Some(ref project_config) = self.project_config {
                 if let Ok(package_name) = project_config.uri_to_package_name(uri) {
                     let module = db::parse_and_check(&self.db, *document, package_name.clone());
@@ -247,18 +378,250 @@ impl Backend {
                     return (module, diagnostics);
                 }
             } else {
-                // TODO: report parse errors, at least?
+                // Not part of a project, so we can't type check it.
+                // We can still report parse errors though.
+                let version = *document.version(&self.db);
+                let rope = document.rope(&self.db);
+                let diagnostic = cst::Module::parse(&rope.to_string())
+                    .err()
+                    .and_then(|err| parse_error_into_lsp_diagnostic(err, uri, rope));
+                return (
+                    None,
+                    vec![db::Diagnostic {
+                        uri: uri.clone(),
+                        version,
+                        diagnostic,
+                    }],
+                );
             }
         }
         (None, vec![])
     }
+
+    /// Checks every module in the current package, along with any other
+    /// documents that are open in the editor.
+    fn check_project_modules(&self) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+        for (uri, (key, document)) in self.documents.iter() {
+            // Modules of the current package and of workspace members belong to the project,
+            // documents that have been opened by the client will have a version
+            let is_project_module = match key {
+                Some((None, _)) => true,
+                Some((Some(package_name), _)) => self
+                    .project_config
+                    .as_ref()
+                    .map_or(false, |project_config| {
+                        project_config.is_member(package_name)
+                    }),
+                None => false,
+            };
+            if is_project_module || document.version(&self.db).is_some() {
+                diagnostics.extend(self.check_module(uri).1);
+            }
+        }
+        diagnostics
+    }
+
+    /// Returns the (memoized) checked module for a document, without
+    /// collecting its diagnostics.
+    fn checked_module(&self, uri: &Url) -> Option<(FullyQualifiedModuleName, ast::Module)> {
+        // Documents outside of a project can't be type checked
+        self.project_config.as_ref()?;
+        let (key, document) = self.documents.get(uri)?;
+        let key = key.clone()?;
+        let module = db::parse_and_check(&self.db, *document, key.0.clone())?;
+        Some((key, module))
+    }
+
+    fn module_exports(&self, uri: &Url) -> Option<ast::ModuleExports> {
+        self.check_module(uri).0.map(|module| module.exports)
+    }
+
+    /// Re-checks everything that (transitively) imports the given module,
+    /// but only if its exports have changed.
+    fn check_dependents(
+        &self,
+        uri: &Url,
+        previous_exports: Option<ast::ModuleExports>,
+    ) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new();
+        if self.module_exports(uri) == previous_exports {
+            return diagnostics;
+        }
+        for dependent in self.transitive_dependents(uri) {
+            diagnostics.extend(self.check_module(&dependent).1);
+        }
+        diagnostics
+    }
+
+    fn transitive_dependents(&self, uri: &Url) -> Vec<Url> {
+        let key = if let Some((Some(key), _)) = self.documents.get(uri) {
+            key
+        } else {
+            return vec![];
+        };
+
+        // Invert the import edges
+        let mut importers: HashMap<FullyQualifiedModuleName, Vec<&Url>> = HashMap::new();
+        for (importer_uri, (importer_key, document)) in self.documents.iter() {
+            if let Some((importer_package, _)) = importer_key {
+                let imports = db::module_imports(&self.db, *document);
+                for (import_package, import_module_name) in imports.imports(&self.db) {
+                    let package_name: Option<ast::PackageName> = import_package
+                        .as_ref()
+                        .or(importer_package.as_ref())
+                        .cloned();
+                    importers
+                        .entry((package_name, import_module_name.clone()))
+                        .or_default()
+                        .push(importer_uri);
+                }
+            }
+        }
+
+        let mut dependents = vec![];
+        let mut seen = HashSet::from([uri]);
+        let mut queue = vec![key];
+        while let Some(key) = queue.pop() {
+            for importer_uri in importers.get(key).into_iter().flatten() {
+                if seen.insert(*importer_uri) {
+                    if let Some((Some(importer_key), _)) = self.documents.get(*importer_uri) {
+                        queue.push(importer_key);
+                    }
+                    dependents.push((*importer_uri).clone());
+                }
+            }
+        }
+        dependents
+    }
+
+    /// (Re)reads all the ditto files for the current project and its packages.
+    ///
+    /// Documents that are open in the editor are kept as they are,
+    /// as they are likely to be more recent than what's on disk.
+    fn load_project_documents(&mut self) {
+        if let Some(ref project_config) = self.project_config {
+            let mut documents = read_package_files(&self.db, project_config.root_dir.clone(), None);
+            for (dir, package_name) in project_config.members.iter() {
+                documents.extend(read_package_files(
+                    &self.db,
+                    dir.clone(),
+                    Some(package_name.clone()),
+                ));
+            }
+            for dir in project_config.package_dirs() {
+                if let Some(package_name) = dir
+                    .components()
+                    .last()
+                    .map(package_name_from_component)
+                    .filter(|package_name| {
+                        // Workspace members have been read already
+                        !project_config.is_member(package_name)
+                    })
+                {
+                    documents.extend(read_package_files(&self.db, dir, Some(package_name)));
+                }
+            }
+            for (uri, (key, document)) in self.documents.drain() {
+                if document.version(&self.db).is_some() {
+                    documents.insert(uri, (key, document));
+                }
+            }
+            self.db.clear_documents();
+            for (_, (key, document)) in documents.iter() {
+                if let Some(key) = key {
+                    self.db.insert_document(key.clone(), *document);
+                }
+            }
+            self.documents = documents;
+        }
+    }
+
+    /// Config files are always watched, so that we notice a project being
+    /// created (or fixed).
+    fn file_watchers_registration(&self) -> Option<Registration> {
+        if !self.watch_files {
+            return None;
+        }
+        let mut watchers = vec![FileSystemWatcher {
+            glob_pattern: format!("**/{}", ditto_config::CONFIG_FILE_NAME),
+            kind: None,
+        }];
+        if let Some(ref project_config) = self.project_config {
+            watchers.extend(project_config.file_system_watchers());
+        }
+        Some(Registration {
+            id: "watch-project-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+                watchers,
+            })
+            .ok(),
+        })
+    }
+
+    fn did_change_watched_files(&mut self, changes: Vec<FileEvent>) -> Diagnostics {
+        let mut reload_config = false;
+        let mut reload_packages = false;
+        let mut diagnostics = Diagnostics::new();
+        for FileEvent { uri, typ } in changes {
+            if let Some(ref project_config) = self.project_config {
+                reload_config = reload_config || project_config.is_project_config_file(&uri);
+                reload_packages = reload_packages || project_config.is_package_file(&uri);
+            } else if let Some(ref root_uri) = self.root_uri {
+                // A config file might have been created (or fixed)
+                let root_config = root_uri.to_file_path().map(|mut path| {
+                    path.push(ditto_config::CONFIG_FILE_NAME);
+                    path
+                });
+                reload_config =
+                    reload_config || (root_config.is_ok() && root_config == uri.to_file_path());
+            }
+            // Config files that aren't open in the editor are checked from disk
+            if is_config_file(&uri) && typ != FileChangeType::DELETED {
+                let is_open = self
+                    .documents
+                    .get(&uri)
+                    .map_or(false, |(_, document)| document.version(&self.db).is_some());
+                if !is_open {
+                    if let Some((uri, rope)) = uri.to_file_path().ok().and_then(read_file) {
+                        diagnostics.extend(self.check_config(&uri, None, &rope));
+                    }
+                }
+            }
+        }
+        if reload_config {
+            if let Some(project_config) = self.root_uri.as_ref().and_then(ProjectConfig::initialize)
+            {
+                self.project_config = Some(project_config);
+            }
+            // NOTE: if the config is broken we hang on to the previous project
+            // configuration, the error will have been reported above.
+        }
+        if reload_config || reload_packages {
+            self.load_project_documents();
+            diagnostics.extend(self.check_project_modules());
+        }
+        diagnostics
+    }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Server {
     async fn initialize(&self, params: InitializeParams) -> jsonrpc::Result<InitializeResult> {
         let mut backend = self.backend.clone().lock_owned().await;
-        if let Some(project_config) = params.root_uri.and_then(ProjectConfig::initialize) {
+        backend.watch_files = params
+            .capabilities
+            .workspace
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|capabilities| capabilities.dynamic_registration)
+            .unwrap_or(false);
+        backend.root_uri = params.root_uri;
+        if let Some(project_config) = backend
+            .root_uri
+            .as_ref()
+            .and_then(ProjectConfig::initialize)
+        {
             backend.project_config = Some(project_config);
 
             Ok(InitializeResult {
@@ -274,21 +637,18 @@ impl LanguageServer for Server {
     }
 
     async fn initialized(&self, _: InitializedParams) {
-        let mut backend = self.backend.clone().lock_owned().await;
-        if let Some(ref project_config) = backend.project_config {
-            let mut documents = read_package_files(&backend.db, ".".into(), None);
-            for dir in project_config.package_dirs() {
-                if let Some(package_name) = dir.components().last().map(package_name_from_component)
-                {
-                    documents.extend(read_package_files(&backend.db, dir, Some(package_name)));
-                }
-            }
-            for (_, (key, document)) in documents.iter() {
-                if let Some(key) = key {
-                    backend.db.insert_document(key.clone(), *document);
-                }
+        let (registration, diagnostics) = {
+            let mut backend = self.backend.clone().lock_owned().await;
+            backend.load_project_documents();
+            let diagnostics = backend.check_project_modules();
+            let registration = backend.file_watchers_registration();
+            (registration, diagnostics)
+        };
+        publish_diagnostics(&self.client, diagnostics).await;
+        if let Some(registration) = registration {
+            if let Err(err) = self.client.register_capability(vec![registration]).await {
+                log::error!("error registering file watchers: {}", err);
             }
-            backend.documents = documents;
         }
     }
 
@@ -302,10 +662,13 @@ impl LanguageServer for Server {
                 uri, version, text, ..
             },
         } = params;
-        let (_, diagnostics) = {
+        let diagnostics = {
             let mut backend = self.backend.clone().lock_owned().await;
+            let previous_exports = backend.module_exports(&uri);
             backend.insert_document(uri.clone(), version, text);
-            backend.check_module(&uri)
+            let (_, mut diagnostics) = backend.check_module(&uri);
+            diagnostics.extend(backend.check_dependents(&uri, previous_exports));
+            diagnostics
         };
         publish_diagnostics(&self.client, diagnostics).await;
     }
@@ -315,12 +678,45 @@ impl LanguageServer for Server {
             text_document: VersionedTextDocumentIdentifier { uri, version },
             content_changes,
         } = params;
-        let (_, diagnostics) = {
+        let diagnostics = {
             let mut backend = self.backend.clone().lock_owned().await;
+            let previous_exports = backend.module_exports(&uri);
             backend.update_document(uri.clone(), version, content_changes);
-            backend.check_module(&uri)
+            let (_, mut diagnostics) = backend.check_module(&uri);
+            diagnostics.extend(backend.check_dependents(&uri, previous_exports));
+            diagnostics
+        };
+        publish_diagnostics(&self.client, diagnostics).await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let (registration, diagnostics) = {
+            let mut backend = self.backend.clone().lock_owned().await;
+            let previous_registration = backend.file_watchers_registration();
+            let diagnostics = backend.did_change_watched_files(params.changes);
+            // The project might have been created, or gained workspace members
+            let registration = backend
+                .file_watchers_registration()
+                .filter(|registration| Some(registration) != previous_registration.as_ref());
+            (registration, diagnostics)
         };
         publish_diagnostics(&self.client, diagnostics).await;
+        if let Some(registration) = registration {
+            let unregistration = Unregistration {
+                id: registration.id.clone(),
+                method: registration.method.clone(),
+            };
+            if let Err(err) = self
+                .client
+                .unregister_capability(vec![unregistration])
+                .await
+            {
+                log::error!("error unregistering file watchers: {}", err);
+            }
+            if let Err(err) = self.client.register_capability(vec![registration]).await {
+                log::error!("error registering file watchers: {}", err);
+            }
+        }
     }
 
     async fn hover(&self, params: HoverParams) -> jsonrpc::Result<Option<Hover>> {
@@ -358,6 +754,50 @@ impl LanguageServer for Server {
         })())
     }
 
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let backend = self.backend.clone().lock_owned().await;
+        Ok((|| {
+            let (_, document) = backend.documents.get(&params.text_document.uri)?;
+            let rope = document.rope(&backend.db);
+            let from = position_to_offset(params.range.start, rope)?;
+            let to = position_to_offset(params.range.end, rope)?;
+            let cst_module = cst::Module::parse(&rope.to_string()).ok()?;
+            let edits = fmt_range(cst_module, rope, from, to);
+            Some(edits)
+        })())
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let DocumentOnTypeFormattingParams {
+            text_document_position:
+                TextDocumentPositionParams {
+                    text_document,
+                    position,
+                },
+            ch,
+            ..
+        } = params;
+        let backend = self.backend.clone().lock_owned().await;
+        Ok((|| {
+            let (_, document) = backend.documents.get(&text_document.uri)?;
+            let rope = document.rope(&backend.db);
+            let source = rope.to_string();
+            let offset = position_to_offset(position, rope)?;
+            if ch == "d" && !ends_with_keyword(source.get(..offset)?, "end") {
+                return None;
+            }
+            let cst_module = cst::Module::parse(&source).ok()?;
+            let edits = fmt_range(cst_module, rope, offset, offset);
+            Some(edits)
+        })())
+    }
+
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -382,6 +822,64 @@ impl LanguageServer for Server {
         })())
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> jsonrpc::Result<Option<Vec<DocumentHighlight>>> {
+        let DocumentHighlightParams {
+            text_document_position_params:
+                TextDocumentPositionParams {
+                    text_document,
+                    position,
+                },
+            ..
+        } = params;
+        let backend = self.backend.clone().lock_owned().await;
+        Ok((|| {
+            let (_, document) = backend.documents.get(&text_document.uri)?;
+            let (key, module) = backend.checked_module(&text_document.uri)?;
+            let rope = document.rope(&backend.db);
+            let offset = position_to_offset(position, rope)?;
+            document_highlight::document_highlight(&key, &module, rope, offset)
+        })())
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyItem>>> {
+        let CallHierarchyPrepareParams {
+            text_document_position_params:
+                TextDocumentPositionParams {
+                    text_document,
+                    position,
+                },
+            ..
+        } = params;
+        let backend = self.backend.clone().lock_owned().await;
+        Ok((|| {
+            let (_, document) = backend.documents.get(&text_document.uri)?;
+            let offset = position_to_offset(position, document.rope(&backend.db))?;
+            call_hierarchy::prepare(&backend, &text_document.uri, offset)
+        })())
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let backend = self.backend.clone().lock_owned().await;
+        Ok(call_hierarchy::incoming_calls(&backend, &params.item))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let backend = self.backend.clone().lock_owned().await;
+        Ok(call_hierarchy::outgoing_calls(&backend, &params.item))
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
@@ -394,7 +892,24 @@ impl LanguageServer for Server {
             let rope = document.rope(&backend.db);
             let source = rope.to_string();
             let tree = parser.parse(&source, None)?;
-            let tokens = semantic_tokens::get_tokens(&tree, &source, query);
+            let tokens = match (
+                backend.check_module(&params.text_document.uri),
+                cst::Module::parse(&source),
+            ) {
+                ((Some(module), diagnostics), Ok(cst_module)) => {
+                    let unused = unused_offsets(&params.text_document.uri, diagnostics, rope);
+                    semantic_tokens::get_checked_tokens(
+                        &tree,
+                        &source,
+                        query,
+                        &module,
+                        &cst_module,
+                        &unused,
+                    )
+                }
+                // Fallback to purely syntactic highlighting
+                _ => semantic_tokens::get_tokens(&tree, &source, query),
+            };
             Some(SemanticTokensResult::Tokens(tokens))
         })())
     }
@@ -428,6 +943,28 @@ async fn publish_diagnostics(client: &Client, diagnostics: Vec<db::Diagnostic>)
     }
 }
 
+fn is_config_file(uri: &Url) -> bool {
+    uri.to_file_path().map_or(false, |path| {
+        path.file_name() == Some(OsStr::new(ditto_config::CONFIG_FILE_NAME))
+    })
+}
+
+/// Start offsets of anything flagged as unused in the given document.
+fn unused_offsets(uri: &Url, diagnostics: Diagnostics, rope: &Rope) -> HashSet<usize> {
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| diagnostic.uri == *uri)
+        .filter_map(|diagnostic| diagnostic.diagnostic)
+        .filter(|diagnostic| {
+            diagnostic
+                .tags
+                .as_ref()
+                .map_or(false, |tags| tags.contains(&DiagnosticTag::UNNECESSARY))
+        })
+        .filter_map(|diagnostic| position_to_offset(diagnostic.range.start, rope))
+        .collect()
+}
+
 fn package_name_from_component(component: std::path::Component) -> ast::PackageName {
     ast::PackageName(component.as_os_str().to_string_lossy().into_owned())
 }
@@ -491,7 +1028,7 @@ fn read_package_files(
     documents
 }
 
-fn read_file(path: std::path::PathBuf) -> Option<(Url, Rope)> {
+fn read_file(path: PathBuf) -> Option<(Url, Rope)> {
     let file = std::fs::File::open(&path).ok()?;
     let reader = std::io::BufReader::new(file);
     let rope = Rope::from_reader(reader).ok()?;
@@ -501,9 +1038,19 @@ fn read_file(path: std::path::PathBuf) -> Option<(Url, Rope)> {
 }
 
 fn fmt(cst_module: cst::Module, rope: &Rope) -> Vec<TextEdit> {
-    let indexed_text = lsp_document::IndexedText::new(rope.to_string());
     let before = rope.bytes().collect::<Vec<_>>();
     let edits = ditto_fmt::format_module_edits(cst_module, &before);
+    into_text_edits(edits, rope)
+}
+
+fn fmt_range(cst_module: cst::Module, rope: &Rope, from: usize, to: usize) -> Vec<TextEdit> {
+    let before = rope.bytes().collect::<Vec<_>>();
+    let edits = ditto_fmt::format_range_edits(cst_module, &before, from, to);
+    into_text_edits(edits, rope)
+}
+
+fn into_text_edits(edits: Vec<ditto_fmt::Edit>, rope: &Rope) -> Vec<TextEdit> {
+    let indexed_text = lsp_document::IndexedText::new(rope.to_string());
     edits
         .into_iter()
         .filter_map(
@@ -521,3 +1068,13 @@ fn fmt(cst_module: cst::Module, rope: &Rope) -> Vec<TextEdit> {
         )
         .collect()
 }
+
+/// Does `source` end with the given keyword (and not some longer identifier)?
+fn ends_with_keyword(source: &str, keyword: &str) -> bool {
+    source.strip_suffix(keyword).map_or(false, |before| {
+        !before
+            .chars()
+            .last()
+            .map_or(false, |c| c.is_alphanumeric() || c == '_')
+    })
+}
@@ -0,0 +1,241 @@
+use ditto_ast::{self as ast, FullyQualifiedModuleName, FullyQualifiedName};
+
+/// Something that a name can refer to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// A top-level value, possibly belonging to another module.
+    Value(FullyQualifiedName),
+    /// A foreign value.
+    Foreign(ast::Name),
+    /// A locally bound name, identified by the span of its binder.
+    Local(ast::Span),
+}
+
+#[derive(Debug, Clone)]
+pub struct Occurrence {
+    pub span: ast::Span,
+    pub target: Target,
+    /// Is this the binding site of `target`?
+    pub is_binding: bool,
+    /// The top-level value declaration that this occurrence appears in.
+    pub within: ast::Name,
+}
+
+/// Finds all the (value) names in a checked module, along with what they refer to.
+///
+/// Declarations are visited in the order given by `values_toposort`.
+pub fn occurrences(key: &FullyQualifiedModuleName, module: &ast::Module) -> Vec<Occurrence> {
+    let mut collector = Collector {
+        key,
+        module,
+        within: None,
+        scope: Vec::new(),
+        occurrences: Vec::new(),
+    };
+    for scc in module.values_toposort.iter() {
+        for name in scc.clone().flatten() {
+            if let Some(module_value) = module.values.get(&name) {
+                collector.within = Some(name.clone());
+                collector.push(
+                    module_value.name_span,
+                    Target::Value(ast::FullyQualified {
+                        module_name: key.clone(),
+                        value: name,
+                    }),
+                    true,
+                );
+                collector.collect_expression(&module_value.expression);
+            }
+        }
+    }
+    collector.occurrences
+}
+
+struct Collector<'a> {
+    key: &'a FullyQualifiedModuleName,
+    module: &'a ast::Module,
+    within: Option<ast::Name>,
+    /// Names that are currently in scope, innermost last.
+    ///
+    /// Binders without a source location (e.g. effect binds) are `None`.
+    scope: Vec<(ast::Name, Option<ast::Span>)>,
+    occurrences: Vec<Occurrence>,
+}
+
+impl Collector<'_> {
+    fn collect_expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::LocalVariable { span, variable, .. } => {
+                if let Some(target) = self.resolve_local(variable) {
+                    self.push(*span, target, false);
+                }
+            }
+            ast::Expression::ImportedVariable { span, variable, .. } => {
+                let package_name = variable
+                    .module_name
+                    .0
+                    .as_ref()
+                    .or(self.key.0.as_ref())
+                    .cloned();
+                let target = Target::Value(ast::FullyQualified {
+                    module_name: (package_name, variable.module_name.1.clone()),
+                    value: variable.value.clone(),
+                });
+                self.push(*span, target, false)
+            }
+            ast::Expression::ForeignVariable { span, variable, .. } => {
+                self.push(*span, Target::Foreign(variable.clone()), false)
+            }
+            ast::Expression::Function { binders, body, .. } => {
+                let scope_len = self.scope.len();
+                for (pattern, _) in binders {
+                    self.bind_pattern(pattern);
+                }
+                self.collect_expression(body);
+                self.scope.truncate(scope_len);
+            }
+            ast::Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                self.collect_expression(function);
+                for ast::Argument::Expression(argument) in arguments {
+                    self.collect_expression(argument);
+                }
+            }
+            ast::Expression::If {
+                condition,
+                true_clause,
+                false_clause,
+                ..
+            } => {
+                self.collect_expression(condition);
+                self.collect_expression(true_clause);
+                self.collect_expression(false_clause);
+            }
+            ast::Expression::Match {
+                expression, arms, ..
+            } => {
+                self.collect_expression(expression);
+                for (pattern, expression) in arms.iter() {
+                    let scope_len = self.scope.len();
+                    self.bind_pattern(pattern);
+                    self.collect_expression(expression);
+                    self.scope.truncate(scope_len);
+                }
+            }
+            ast::Expression::Effect { effect, .. } => {
+                let scope_len = self.scope.len();
+                self.collect_effect(effect);
+                self.scope.truncate(scope_len);
+            }
+            ast::Expression::Let {
+                declaration,
+                expression,
+                ..
+            } => {
+                self.collect_expression(&declaration.expression);
+                let scope_len = self.scope.len();
+                self.bind_pattern(&declaration.pattern);
+                self.collect_expression(expression);
+                self.scope.truncate(scope_len);
+            }
+            ast::Expression::RecordAccess { target, .. } => self.collect_expression(target),
+            ast::Expression::RecordUpdate { target, fields, .. } => {
+                self.collect_expression(target);
+                for expression in fields.values() {
+                    self.collect_expression(expression);
+                }
+            }
+            ast::Expression::Record { fields, .. } => {
+                for expression in fields.values() {
+                    self.collect_expression(expression);
+                }
+            }
+            ast::Expression::Array { elements, .. } => {
+                for element in elements {
+                    self.collect_expression(element);
+                }
+            }
+            ast::Expression::LocalConstructor { .. }
+            | ast::Expression::ImportedConstructor { .. }
+            | ast::Expression::String { .. }
+            | ast::Expression::Int { .. }
+            | ast::Expression::Float { .. }
+            | ast::Expression::True { .. }
+            | ast::Expression::False { .. }
+            | ast::Expression::Unit { .. } => {}
+        }
+    }
+
+    fn collect_effect(&mut self, effect: &ast::Effect) {
+        match effect {
+            ast::Effect::Bind {
+                name,
+                expression,
+                rest,
+            } => {
+                self.collect_expression(expression);
+                self.scope.push((name.clone(), None));
+                self.collect_effect(rest);
+            }
+            ast::Effect::Let {
+                pattern,
+                expression,
+                rest,
+            } => {
+                self.collect_expression(expression);
+                self.bind_pattern(pattern);
+                self.collect_effect(rest);
+            }
+            ast::Effect::Expression { expression, rest } => {
+                self.collect_expression(expression);
+                if let Some(rest) = rest {
+                    self.collect_effect(rest);
+                }
+            }
+            ast::Effect::Return { expression } => self.collect_expression(expression),
+        }
+    }
+
+    fn bind_pattern(&mut self, pattern: &ast::Pattern) {
+        match pattern {
+            ast::Pattern::Variable { span, name } => {
+                self.push(*span, Target::Local(*span), true);
+                self.scope.push((name.clone(), Some(*span)));
+            }
+            ast::Pattern::Unused { .. } => {}
+            ast::Pattern::LocalConstructor { arguments, .. }
+            | ast::Pattern::ImportedConstructor { arguments, .. } => {
+                for argument in arguments {
+                    self.bind_pattern(argument);
+                }
+            }
+        }
+    }
+
+    fn resolve_local(&self, variable: &ast::Name) -> Option<Target> {
+        if let Some((_, binder)) = self.scope.iter().rev().find(|(name, _)| name == variable) {
+            return binder.map(Target::Local);
+        }
+        if self.module.values.contains_key(variable) {
+            return Some(Target::Value(ast::FullyQualified {
+                module_name: self.key.clone(),
+                value: variable.clone(),
+            }));
+        }
+        None
+    }
+
+    fn push(&mut self, span: ast::Span, target: Target, is_binding: bool) {
+        if let Some(ref within) = self.within {
+            self.occurrences.push(Occurrence {
+                span,
+                target,
+                is_binding,
+                within: within.clone(),
+            })
+        }
+    }
+}
@@ -1,6 +1,9 @@
+use ditto_ast as ast;
+use ditto_cst as cst;
 use ditto_tree_sitter as tree_sitter;
+use std::collections::HashSet;
 use tower_lsp::lsp_types::{
-    SemanticToken, SemanticTokenType, SemanticTokens, SemanticTokensLegend,
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensLegend,
 };
 
 pub fn legend() -> SemanticTokensLegend {
@@ -15,9 +18,14 @@ pub fn legend() -> SemanticTokensLegend {
             SemanticTokenType::STRING,         // 6
             SemanticTokenType::NUMBER,         // 7
             SemanticTokenType::MACRO,          // 8
+            SemanticTokenType::VARIABLE,       // 9
+            SemanticTokenType::FUNCTION,       // 10
+            SemanticTokenType::PARAMETER,      // 11
         ],
         token_modifiers: vec![
-            // TODO
+            SemanticTokenModifier::DECLARATION,     // 0
+            SemanticTokenModifier::DEFAULT_LIBRARY, // 1
+            SemanticTokenModifier::new("unused"),   // 2
         ],
     }
 }
@@ -34,6 +42,23 @@ enum TokenType {
     String = 6,
     Number = 7,
     Special = 8,
+    Variable = 9,
+    Function = 10,
+    Parameter = 11,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TokenModifier {
+    // Keep these in sync with indices of `token_modifiers` above!
+    Declaration = 0,
+    DefaultLibrary = 1,
+    Unused = 2,
+}
+
+impl TokenModifier {
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
 }
 
 impl std::convert::TryFrom<ditto_highlight::TokenType> for TokenType {
@@ -69,51 +94,574 @@ pub fn get_tokens(
     source: &str,
     query: &ditto_highlight::Query,
 ) -> SemanticTokens {
+    let mut tokens_builder = TokensBuilder::new(source);
+    push_highlight_tokens(&mut tokens_builder, tree, source, query);
+    SemanticTokens {
+        result_id: None,
+        data: tokens_builder.into_tokens().unwrap_or_default(),
+    }
+}
+
+/// Like [get_tokens], but identifiers are classified using the checked module.
+///
+/// The checked module doesn't keep the source locations of type annotations, so
+/// those are found in `cst_module`.
+///
+/// `unused` contains the start offsets of anything the checker has flagged as unused.
+pub fn get_checked_tokens(
+    tree: &tree_sitter::Tree,
+    source: &str,
+    query: &ditto_highlight::Query,
+    module: &ast::Module,
+    cst_module: &cst::Module,
+    unused: &HashSet<usize>,
+) -> SemanticTokens {
+    let mut tokens_builder = TokensBuilder::new(source);
+    push_highlight_tokens(&mut tokens_builder, tree, source, query);
+    let mut module_tokens = ModuleTokens {
+        tokens_builder: &mut tokens_builder,
+        unused,
+    };
+    module_tokens.push_module(module);
+    module_tokens.push_type_annotations(cst_module);
+    SemanticTokens {
+        result_id: None,
+        data: tokens_builder.into_tokens().unwrap_or_default(),
+    }
+}
+
+fn push_highlight_tokens(
+    tokens_builder: &mut TokensBuilder,
+    tree: &tree_sitter::Tree,
+    source: &str,
+    query: &ditto_highlight::Query,
+) {
     let tokens = ditto_highlight::get_tokens(source, tree, query);
-    let mut tokens_builder = TokensBuilder::new();
     for token in tokens {
         if let Ok(token_type) = token.token_type.try_into() {
             tokens_builder.push_node(token.node, token_type)
         }
     }
-    SemanticTokens {
-        result_id: None,
-        data: tokens_builder.into_tokens().unwrap_or_default(),
+}
+
+struct ModuleTokens<'a> {
+    tokens_builder: &'a mut TokensBuilder,
+    unused: &'a HashSet<usize>,
+}
+
+impl ModuleTokens<'_> {
+    fn push_module(&mut self, module: &ast::Module) {
+        for module_type in module.types.values() {
+            self.push(
+                module_type.type_name_span(),
+                TokenType::Type,
+                TokenModifier::Declaration.bit(),
+            );
+        }
+        for module_constructor in module.constructors.values() {
+            self.push(
+                module_constructor.constructor_name_span,
+                TokenType::Constructor,
+                TokenModifier::Declaration.bit(),
+            );
+        }
+        for module_value in module.values.values() {
+            self.push(
+                module_value.name_span,
+                value_token_type(&module_value.expression.get_type()),
+                TokenModifier::Declaration.bit(),
+            );
+            self.push_expression(&module_value.expression);
+        }
+    }
+
+    fn push_expression(&mut self, expression: &ast::Expression) {
+        match expression {
+            ast::Expression::LocalVariable {
+                span,
+                variable_type,
+                ..
+            } => self.push(*span, value_token_type(variable_type), 0),
+            ast::Expression::ForeignVariable {
+                span,
+                variable_type,
+                ..
+            } => self.push(
+                *span,
+                value_token_type(variable_type),
+                TokenModifier::DefaultLibrary.bit(),
+            ),
+            ast::Expression::ImportedVariable {
+                span,
+                variable_type,
+                variable,
+            } => {
+                // Values imported from another package are considered "library" values
+                let modifiers = if variable.module_name.0.is_some() {
+                    TokenModifier::DefaultLibrary.bit()
+                } else {
+                    0
+                };
+                self.push(
+                    qualified_name_span(*span, &variable.value.0),
+                    value_token_type(variable_type),
+                    modifiers,
+                )
+            }
+            ast::Expression::LocalConstructor { span, .. } => {
+                self.push(*span, TokenType::Constructor, 0)
+            }
+            ast::Expression::ImportedConstructor {
+                span, constructor, ..
+            } => self.push(
+                qualified_name_span(*span, &constructor.value.0),
+                TokenType::Constructor,
+                0,
+            ),
+            ast::Expression::Function { binders, body, .. } => {
+                for (pattern, _) in binders {
+                    self.push_pattern(pattern, TokenType::Parameter);
+                }
+                self.push_expression(body);
+            }
+            ast::Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                self.push_expression(function);
+                for ast::Argument::Expression(argument) in arguments {
+                    self.push_expression(argument);
+                }
+            }
+            ast::Expression::If {
+                condition,
+                true_clause,
+                false_clause,
+                ..
+            } => {
+                self.push_expression(condition);
+                self.push_expression(true_clause);
+                self.push_expression(false_clause);
+            }
+            ast::Expression::Match {
+                expression, arms, ..
+            } => {
+                self.push_expression(expression);
+                for (pattern, expression) in arms.iter() {
+                    self.push_pattern(pattern, TokenType::Variable);
+                    self.push_expression(expression);
+                }
+            }
+            ast::Expression::Effect { effect, .. } => self.push_effect(effect),
+            ast::Expression::Let {
+                declaration,
+                expression,
+                ..
+            } => {
+                self.push_pattern(&declaration.pattern, TokenType::Variable);
+                self.push_expression(&declaration.expression);
+                self.push_expression(expression);
+            }
+            ast::Expression::RecordAccess { target, .. } => self.push_expression(target),
+            ast::Expression::RecordUpdate { target, fields, .. } => {
+                self.push_expression(target);
+                for expression in fields.values() {
+                    self.push_expression(expression);
+                }
+            }
+            ast::Expression::Record { fields, .. } => {
+                for expression in fields.values() {
+                    self.push_expression(expression);
+                }
+            }
+            ast::Expression::Array { elements, .. } => {
+                for element in elements {
+                    self.push_expression(element);
+                }
+            }
+            ast::Expression::String { .. }
+            | ast::Expression::Int { .. }
+            | ast::Expression::Float { .. }
+            | ast::Expression::True { .. }
+            | ast::Expression::False { .. }
+            | ast::Expression::Unit { .. } => {
+                // Literals are handled by tree-sitter
+            }
+        }
+    }
+
+    fn push_effect(&mut self, effect: &ast::Effect) {
+        match effect {
+            ast::Effect::Bind {
+                expression, rest, ..
+            } => {
+                self.push_expression(expression);
+                self.push_effect(rest);
+            }
+            ast::Effect::Let {
+                pattern,
+                expression,
+                rest,
+            } => {
+                self.push_pattern(pattern, TokenType::Variable);
+                self.push_expression(expression);
+                self.push_effect(rest);
+            }
+            ast::Effect::Expression { expression, rest } => {
+                self.push_expression(expression);
+                if let Some(rest) = rest {
+                    self.push_effect(rest);
+                }
+            }
+            ast::Effect::Return { expression } => self.push_expression(expression),
+        }
+    }
+
+    fn push_pattern(&mut self, pattern: &ast::Pattern, token_type: TokenType) {
+        match pattern {
+            ast::Pattern::Variable { span, .. } => {
+                self.push(*span, token_type, TokenModifier::Declaration.bit())
+            }
+            ast::Pattern::Unused { span, .. } => self.push(
+                *span,
+                token_type,
+                TokenModifier::Declaration.bit() | TokenModifier::Unused.bit(),
+            ),
+            ast::Pattern::LocalConstructor { arguments, .. }
+            | ast::Pattern::ImportedConstructor { arguments, .. } => {
+                for argument in arguments {
+                    self.push_pattern(argument, token_type);
+                }
+            }
+        }
+    }
+
+    fn push_type_annotations(&mut self, module: &cst::Module) {
+        for declaration in module.declarations.iter() {
+            match declaration {
+                cst::Declaration::Value(value_declaration) => {
+                    if let Some(ref type_annotation) = value_declaration.type_annotation {
+                        self.push_type(&type_annotation.1);
+                    }
+                    self.push_expression_type_annotations(&value_declaration.expression);
+                }
+                cst::Declaration::Type(type_declaration) => {
+                    self.push_type_variables(type_declaration.type_variables());
+                    if let cst::TypeDeclaration::WithConstructors {
+                        head_constructor,
+                        tail_constructors,
+                        ..
+                    } = type_declaration.as_ref()
+                    {
+                        let fields = std::iter::once(&head_constructor.fields)
+                            .chain(tail_constructors.iter().map(|ctor| &ctor.fields))
+                            .flatten();
+                        for field in fields.flat_map(|fields| fields.value.iter()) {
+                            self.push_type(field);
+                        }
+                    }
+                }
+                cst::Declaration::TypeAlias(type_alias_declaration) => {
+                    self.push_type_variables(&type_alias_declaration.type_variables);
+                    self.push_type(&type_alias_declaration.aliased_type);
+                }
+                cst::Declaration::ForeignValue(foreign_value_declaration) => {
+                    self.push_type(&foreign_value_declaration.type_annotation.1);
+                }
+            }
+        }
+    }
+
+    fn push_type_variables(&mut self, type_variables: &Option<cst::ParensList1<cst::Name>>) {
+        for name in type_variables.iter().flat_map(|parens| parens.value.iter()) {
+            self.push(
+                name.get_span(),
+                TokenType::TypeVariable,
+                TokenModifier::Declaration.bit(),
+            );
+        }
+    }
+
+    fn push_expression_type_annotations(&mut self, expression: &cst::Expression) {
+        match expression {
+            cst::Expression::Parens(parens) => self.push_expression_type_annotations(&parens.value),
+            cst::Expression::Function {
+                parameters,
+                return_type_annotation,
+                body,
+                ..
+            } => {
+                for (_, type_annotation) in parameters.value.iter().flat_map(|ps| ps.iter()) {
+                    if let Some(type_annotation) = type_annotation {
+                        self.push_type(&type_annotation.1);
+                    }
+                }
+                if let Some(ref type_annotation) = **return_type_annotation {
+                    self.push_type(&type_annotation.1);
+                }
+                self.push_expression_type_annotations(body);
+            }
+            cst::Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.push_expression_type_annotations(function);
+                for argument in arguments.value.iter().flat_map(|args| args.iter()) {
+                    self.push_expression_type_annotations(argument);
+                }
+            }
+            cst::Expression::If {
+                condition,
+                true_clause,
+                false_clause,
+                ..
+            } => {
+                self.push_expression_type_annotations(condition);
+                self.push_expression_type_annotations(true_clause);
+                self.push_expression_type_annotations(false_clause);
+            }
+            cst::Expression::Match {
+                expression,
+                head_arm,
+                tail_arms,
+                ..
+            } => {
+                self.push_expression_type_annotations(expression);
+                for arm in std::iter::once(head_arm.as_ref()).chain(tail_arms.iter()) {
+                    self.push_expression_type_annotations(&arm.expression);
+                }
+            }
+            cst::Expression::Effect { effect, .. } => self.push_effect_type_annotations(effect),
+            cst::Expression::Array(brackets) => {
+                for element in brackets.value.iter().flat_map(|elements| elements.iter()) {
+                    self.push_expression_type_annotations(element);
+                }
+            }
+            cst::Expression::Record(braces) => {
+                for field in braces.value.iter().flat_map(|fields| fields.iter()) {
+                    self.push_expression_type_annotations(&field.value);
+                }
+            }
+            cst::Expression::BinOp { lhs, rhs, .. } => {
+                self.push_expression_type_annotations(lhs);
+                self.push_expression_type_annotations(rhs);
+            }
+            cst::Expression::RecordAccess { target, .. } => {
+                self.push_expression_type_annotations(target)
+            }
+            cst::Expression::RecordUpdate {
+                target, updates, ..
+            } => {
+                self.push_expression_type_annotations(target);
+                for field in updates.iter() {
+                    self.push_expression_type_annotations(&field.value);
+                }
+            }
+            cst::Expression::Let {
+                head_declaration,
+                tail_declarations,
+                expr,
+                ..
+            } => {
+                for declaration in
+                    std::iter::once(head_declaration.as_ref()).chain(tail_declarations.iter())
+                {
+                    if let Some(ref type_annotation) = declaration.type_annotation {
+                        self.push_type(&type_annotation.1);
+                    }
+                    self.push_expression_type_annotations(&declaration.expression);
+                }
+                self.push_expression_type_annotations(expr);
+            }
+            cst::Expression::Constructor(_)
+            | cst::Expression::Variable(_)
+            | cst::Expression::Unit(_)
+            | cst::Expression::True(_)
+            | cst::Expression::False(_)
+            | cst::Expression::String(_)
+            | cst::Expression::Int(_)
+            | cst::Expression::Float(_) => {}
+        }
+    }
+
+    fn push_effect_type_annotations(&mut self, effect: &cst::Effect) {
+        match effect {
+            cst::Effect::Return { expression, .. } => {
+                self.push_expression_type_annotations(expression)
+            }
+            cst::Effect::Bind {
+                expression, rest, ..
+            } => {
+                self.push_expression_type_annotations(expression);
+                self.push_effect_type_annotations(rest);
+            }
+            cst::Effect::Let {
+                type_annotation,
+                expression,
+                rest,
+                ..
+            } => {
+                if let Some(type_annotation) = type_annotation {
+                    self.push_type(&type_annotation.1);
+                }
+                self.push_expression_type_annotations(expression);
+                self.push_effect_type_annotations(rest);
+            }
+            cst::Effect::Expression { expression, rest } => {
+                self.push_expression_type_annotations(expression);
+                if let Some((_, rest)) = rest {
+                    self.push_effect_type_annotations(rest);
+                }
+            }
+        }
+    }
+
+    fn push_type(&mut self, t: &cst::Type) {
+        match t {
+            cst::Type::Parens(parens) => self.push_type(&parens.value),
+            cst::Type::Call {
+                function,
+                arguments,
+            } => {
+                match function {
+                    cst::TypeCallFunction::Constructor(constructor) => {
+                        self.push(constructor.value.get_span(), TokenType::Type, 0)
+                    }
+                    cst::TypeCallFunction::Variable(variable) => {
+                        self.push(variable.get_span(), TokenType::TypeVariable, 0)
+                    }
+                }
+                for argument in arguments.value.iter() {
+                    self.push_type(argument);
+                }
+            }
+            cst::Type::Function {
+                parameters,
+                return_type,
+                ..
+            } => {
+                for parameter in parameters.value.iter().flat_map(|ps| ps.iter()) {
+                    self.push_type(parameter);
+                }
+                self.push_type(return_type);
+            }
+            cst::Type::Constructor(constructor) => {
+                self.push(constructor.value.get_span(), TokenType::Type, 0)
+            }
+            cst::Type::Variable(variable) => {
+                self.push(variable.get_span(), TokenType::TypeVariable, 0)
+            }
+            cst::Type::RecordClosed(braces) => {
+                for field in braces.value.iter().flat_map(|fields| fields.iter()) {
+                    self.push_type(&field.value);
+                }
+            }
+            cst::Type::RecordOpen(braces) => {
+                let (variable, _, fields) = &braces.value;
+                self.push(variable.get_span(), TokenType::TypeVariable, 0);
+                for field in fields.iter() {
+                    self.push_type(&field.value);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, span: ast::Span, token_type: TokenType, mut token_modifiers: u32) {
+        if self.unused.contains(&span.start_offset) {
+            token_modifiers |= TokenModifier::Unused.bit();
+        }
+        self.tokens_builder
+            .push_span(span, token_type, token_modifiers)
+    }
+}
+
+fn value_token_type(t: &ast::Type) -> TokenType {
+    match t {
+        ast::Type::Function { .. } => TokenType::Function,
+        ast::Type::ConstructorAlias { aliased_type, .. } => value_token_type(aliased_type),
+        // NOTE: a type variable could be instantiated to a function,
+        // but we can't know that here.
+        _ => TokenType::Variable,
     }
 }
 
-struct TokensBuilder(Vec<Node>);
+/// The span of `name` within a (possibly) qualified name, e.g. `five` in `B.five`.
+fn qualified_name_span(span: ast::Span, name: &str) -> ast::Span {
+    ast::Span {
+        start_offset: span.end_offset.saturating_sub(name.len()),
+        end_offset: span.end_offset,
+    }
+}
+
+struct TokensBuilder {
+    nodes: Vec<Node>,
+    line_offsets: Vec<usize>,
+}
 
 #[derive(Debug)]
 struct Node {
     start_line: usize,
     start_col: usize,
     token_type: TokenType,
+    token_modifiers: u32,
     length: usize,
 }
 
 impl TokensBuilder {
-    fn new() -> Self {
-        Self(Vec::new())
+    fn new(source: &str) -> Self {
+        let line_offsets = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            nodes: Vec::new(),
+            line_offsets,
+        }
     }
 
     fn push_node(&mut self, node: tree_sitter::Node, token_type: TokenType) {
         let tree_sitter::Point { row, column } = node.start_position();
         let length = node.byte_range().len();
-        self.0.push(Node {
+        self.nodes.push(Node {
             start_line: row,
             start_col: column,
             length,
             token_type,
+            token_modifiers: 0,
+        })
+    }
+
+    fn push_span(&mut self, span: ast::Span, token_type: TokenType, token_modifiers: u32) {
+        let start_line = match self.line_offsets.binary_search(&span.start_offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let start_col = span.start_offset - self.line_offsets[start_line];
+        self.nodes.push(Node {
+            start_line,
+            start_col,
+            length: span.end_offset - span.start_offset,
+            token_type,
+            token_modifiers,
         })
     }
 
     fn into_tokens(mut self) -> Option<Vec<SemanticToken>> {
         let mut tokens = Vec::new();
-        self.0.sort_by_key(|node| (node.start_line, node.start_col));
+        // NOTE: this sort is stable, so later tokens (i.e. those derived from the checked
+        // module) come after tree-sitter tokens at the same position.
+        self.nodes
+            .sort_by_key(|node| (node.start_line, node.start_col));
+        let mut nodes = self.nodes.into_iter().peekable();
         let mut current_line = 0;
         let mut current_col = 0;
-        for node in self.0 {
+        while let Some(node) = nodes.next() {
+            if let Some(next) = nodes.peek() {
+                if (next.start_line, next.start_col) == (node.start_line, node.start_col) {
+                    // Prefer the later token
+                    continue;
+                }
+            }
             let delta_line: u32 = (node.start_line - current_line).try_into().ok()?;
             let delta_start: u32 = if delta_line > 0 {
                 node.start_col.try_into().ok()?
@@ -124,7 +672,7 @@ impl TokensBuilder {
                 delta_line,
                 delta_start,
                 token_type: node.token_type as u32,
-                token_modifiers_bitset: 0,
+                token_modifiers_bitset: node.token_modifiers,
                 length: node.length as u32,
             });
             current_line = node.start_line;
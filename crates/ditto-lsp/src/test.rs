@@ -58,6 +58,11 @@ impl LanguageServer for Backend {
         self.0.did_open(params).await
     }
 
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        log_value("< workspace/didChangeWatchedFiles", &params);
+        self.0.did_change_watched_files(params).await
+    }
+
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         log_value("< textDocument/hover", &params);
         let result = self.0.hover(params).await;
@@ -79,6 +84,54 @@ impl LanguageServer for Backend {
         result
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        log_value("< textDocument/documentHighlight", &params);
+        let result = self.0.document_highlight(params).await;
+        if let Ok(ref res) = result {
+            log_value("> textDocument/documentHighlight", res);
+        }
+        result
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        log_value("< textDocument/prepareCallHierarchy", &params);
+        let result = self.0.prepare_call_hierarchy(params).await;
+        if let Ok(ref res) = result {
+            log_value("> textDocument/prepareCallHierarchy", res);
+        }
+        result
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        log_value("< callHierarchy/incomingCalls", &params);
+        let result = self.0.incoming_calls(params).await;
+        if let Ok(ref res) = result {
+            log_value("> callHierarchy/incomingCalls", res);
+        }
+        result
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        log_value("< callHierarchy/outgoingCalls", &params);
+        let result = self.0.outgoing_calls(params).await;
+        if let Ok(ref res) = result {
+            log_value("> callHierarchy/outgoingCalls", res);
+        }
+        result
+    }
+
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
         log_value("< textDocument/formatting", &params);
         let result = self.0.formatting(params).await;
@@ -88,6 +141,30 @@ impl LanguageServer for Backend {
         result
     }
 
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        log_value("< textDocument/rangeFormatting", &params);
+        let result = self.0.range_formatting(params).await;
+        if let Ok(ref res) = result {
+            log_value("> textDocument/rangeFormatting", res);
+        }
+        result
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        log_value("< textDocument/onTypeFormatting", &params);
+        let result = self.0.on_type_formatting(params).await;
+        if let Ok(ref res) = result {
+            log_value("> textDocument/onTypeFormatting", res);
+        }
+        result
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
@@ -16,8 +16,15 @@ fn main() -> std::io::Result<()> {
             dep_sources,
         );
     }
-    let result = generate_build_ninja(sources, package_sources)
-        .map(|(build_ninja, _get_warnings)| build_ninja);
+    let check_only = std::env::args().any(|arg| arg == "--check");
+    let result =
+        generate_build_ninja(sources, package_sources).map(|(build_ninja, _get_warnings)| {
+            if check_only {
+                build_ninja.check_only()
+            } else {
+                build_ninja
+            }
+        });
 
     match result {
         Ok(build_ninja) => {
@@ -34,11 +41,19 @@ fn generate_build_ninja(
     sources: ditto_make::Sources,
     package_sources: ditto_make::PackageSources,
 ) -> miette::Result<(ditto_make::BuildNinja, ditto_make::GetWarnings)> {
+    let compile_mode = if std::env::args().any(|arg| arg == "--batch") {
+        ditto_make::CompileMode::Batch
+    } else {
+        ditto_make::CompileMode::PerModule
+    };
+    let deny_warnings = std::env::args().any(|arg| arg == "--deny-warnings");
     ditto_make::generate_build_ninja(
         std::path::PathBuf::from("builddir"),
         std::path::PathBuf::from("ditto"),
         &semver::Version::parse("0.0.0-test").unwrap(),
         "compile",
+        compile_mode,
+        deny_warnings,
         sources,
         package_sources,
     )
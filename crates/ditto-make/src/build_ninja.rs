@@ -1,10 +1,11 @@
 use crate::{common, compile};
 use ditto_ast as ast;
-use ditto_config::{read_config, Config, PackageName};
+use ditto_checker as checker;
+use ditto_config::{read_config, Config, PackageName, WarningLevel};
 use ditto_cst as cst;
-use miette::{bail, Diagnostic, IntoDiagnostic, NamedSource, Result, SourceSpan};
+use miette::{bail, Diagnostic, IntoDiagnostic, NamedSource, Result, SourceSpan, WrapErr};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt,
     path::{Path, PathBuf},
 };
@@ -25,22 +26,64 @@ pub type PackageSources = HashMap<PackageName, Sources>;
 /// compilation warnings.
 pub type GetWarnings = impl FnOnce() -> Result<Vec<miette::Report>>;
 
+/// How modules should be checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompileMode {
+    /// A separate `ast` process for every module.
+    #[default]
+    PerModule,
+    /// A single `ast-batch` process for every package.
+    ///
+    /// Reduces the overhead of (de)serializing exports, at the cost of
+    /// coarser incremental rebuilds.
+    Batch,
+}
+
 /// Generates a [build.ninja](https://ninja-build.org/manual.html#_writing_your_own_ninja_files)
 /// file and also returns a function for retrieving compiler warnings once `ninja` has run.
+///
+/// If `deny_warnings` is set then any warnings for the current package that haven't been
+/// configured otherwise will fail the build.
+///
+/// Warnings are collected (and warning levels applied) for the members of a workspace too,
+/// as they're part of the current project.
 #[tracing::instrument(level = "trace", skip_all)]
+#[allow(clippy::too_many_arguments)]
 pub fn generate_build_ninja(
     build_dir: PathBuf,
     ditto_bin: PathBuf,
     ditto_version: &semver::Version,
     compile_subcommand: &'static str,
+    compile_mode: CompileMode,
+    deny_warnings: bool,
     sources: Sources,
     package_sources: PackageSources,
 ) -> Result<(BuildNinja, GetWarnings)> {
     // TODO make this more concurrent!
     let config = read_config(&sources.config)?;
 
+    let workspace_members = if let Some(ref workspace) = config.workspace {
+        let root_dir = sources.config.parent().unwrap_or_else(|| Path::new(""));
+        workspace
+            .read_members(root_dir, &config)?
+            .into_iter()
+            .map(|member| member.config.name)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    let warning_args = warning_level_args(&config, deny_warnings, &workspace_members)?;
+
     // Initial build.ninja file, extended later
-    let mut build_ninja = BuildNinja::new(&build_dir, &ditto_bin, compile_subcommand, &config);
+    let mut build_ninja = BuildNinja::new(
+        &build_dir,
+        &ditto_bin,
+        compile_subcommand,
+        compile_mode,
+        warning_args,
+        &config,
+    );
 
     let js_dirs = if config.targets_js() {
         let dist_dir = config.codegen_js_config.dist_dir;
@@ -63,6 +106,9 @@ pub fn generate_build_ninja(
     // Paths to serialized warnings, so the caller can replay them
     let mut checker_warnings_paths: Vec<PathBuf> = Vec::new();
 
+    // Package-level builds, if we're in batch mode
+    let mut batch_builds: HashMap<Option<PackageName>, Build> = HashMap::new();
+
     for (node_index, node) in graph_nodes.clone() {
         let node_string = node.to_string();
         let ast_path = mk_ast_path(
@@ -79,7 +125,10 @@ pub fn generate_build_ninja(
             common::EXTENSION_AST_EXPORTS,
         );
 
-        let checker_warnings_path = if node.package_name.is_none() {
+        let is_project_module = node.package_name.as_ref().map_or(true, |package_name| {
+            workspace_members.contains(package_name)
+        });
+        let checker_warnings_path = if is_project_module {
             let checker_warnings_path = mk_ast_path(
                 build_dir.clone(),
                 &node.package_name,
@@ -92,29 +141,21 @@ pub fn generate_build_ninja(
             None
         };
 
-        let dependency_ast_export_paths = graph
+        let dependency_nodes = graph
             .neighbors(node_index)
-            .map(|idx| {
-                let dep_node = graph_nodes.get(&idx).unwrap();
-                mk_ast_path(
-                    build_dir.clone(),
-                    &dep_node.package_name,
-                    &dep_node.module_name,
-                    common::EXTENSION_AST_EXPORTS,
-                )
-            })
+            .map(|idx| graph_nodes.get(&idx).unwrap())
             .collect::<Vec<_>>();
 
         if let Some((ref dist_dir, ref packages_dir)) = js_dirs {
-            let js_path = if let Some(package_name) = node.package_name {
+            let js_path = if let Some(ref package_name) = node.package_name {
                 let mut js_path = packages_dir.clone();
                 js_path.push(package_name.as_str());
-                js_path.push(common::module_name_to_file_stem(node.module_name));
+                js_path.push(common::module_name_to_file_stem(node.module_name.clone()));
                 js_path.set_extension(common::EXTENSION_JS);
                 js_path
             } else {
                 let mut js_path = dist_dir.clone();
-                js_path.push(common::module_name_to_file_stem(node.module_name));
+                js_path.push(common::module_name_to_file_stem(node.module_name.clone()));
                 js_path.set_extension(common::EXTENSION_JS);
                 js_path
             };
@@ -125,17 +166,70 @@ pub fn generate_build_ninja(
             ));
         }
 
-        build_ninja.builds.push(Build::new_ast(
-            node_string,
-            ast_path,
-            ast_exports_path,
-            checker_warnings_path,
-            node.source_path,
-            dependency_ast_export_paths,
-        ));
+        match compile_mode {
+            CompileMode::PerModule => {
+                let dependency_ast_export_paths = dependency_nodes
+                    .into_iter()
+                    .map(|dep_node| {
+                        mk_ast_path(
+                            build_dir.clone(),
+                            &dep_node.package_name,
+                            &dep_node.module_name,
+                            common::EXTENSION_AST_EXPORTS,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                build_ninja.builds.push(Build::new_ast(
+                    node_string,
+                    ast_path,
+                    ast_exports_path,
+                    checker_warnings_path,
+                    node.source_path,
+                    dependency_ast_export_paths,
+                ));
+            }
+            CompileMode::Batch => {
+                let batch_build = batch_builds
+                    .entry(node.package_name.clone())
+                    .or_insert_with(|| {
+                        let package_descriptor = node
+                            .package_name
+                            .as_ref()
+                            .map_or(config.name.as_str(), |package_name| package_name.as_str());
+                        Build::new_ast_batch(package_descriptor)
+                    });
+                batch_build.outputs.push(ast_path.clone());
+                batch_build.outputs.push(ast_exports_path);
+                batch_build.outputs.extend(checker_warnings_path);
+                batch_build.inputs.push(node.source_path.clone());
+                // Dependencies within the same package are handled by the batch itself
+                batch_build.inputs.extend(
+                    dependency_nodes
+                        .iter()
+                        .filter(|dep_node| dep_node.package_name != node.package_name)
+                        .map(|dep_node| {
+                            mk_ast_path(
+                                build_dir.clone(),
+                                &dep_node.package_name,
+                                &dep_node.module_name,
+                                common::EXTENSION_AST_EXPORTS,
+                            )
+                        }),
+                );
+            }
+        }
     }
 
-    // Callback to get all warnings for the current package
+    build_ninja
+        .builds
+        .extend(batch_builds.into_values().map(|mut batch_build| {
+            batch_build.inputs.sort();
+            batch_build.inputs.dedup();
+            batch_build
+        }));
+
+    // Callback to get all warnings for the current package (and workspace members)
     let get_warnings = move || {
         let mut warnings = Vec::new();
         for warnings_path in checker_warnings_paths {
@@ -161,6 +255,47 @@ pub fn generate_build_ninja(
     Ok((build_ninja, get_warnings))
 }
 
+/// Arguments passed to the `ast` rules to configure warning levels.
+///
+/// These are passed as arguments (rather than, say, environment variables) so that
+/// ninja reruns checks when the levels change.
+fn warning_level_args(
+    config: &Config,
+    deny_warnings: bool,
+    workspace_members: &HashSet<PackageName>,
+) -> Result<Vec<String>> {
+    let mut levels = BTreeMap::new();
+    for (name_or_code, level) in config.warnings.iter() {
+        let code = checker::warning_code(name_or_code).ok_or_else(|| {
+            miette::miette!(
+                help = "refer to warnings by name (e.g. `unused-import`) or code (e.g. `W0013`)",
+                "unknown warning `{}` in {}",
+                name_or_code,
+                ditto_config::CONFIG_FILE_NAME,
+            )
+        })?;
+        levels.insert(code, *level);
+    }
+    let mut args = Vec::new();
+    for (code, level) in levels {
+        if level != WarningLevel::Warn {
+            args.push(format!("--{}", compile::ARG_WARNING_LEVEL));
+            args.push(format!("{}={}", code, level));
+        }
+    }
+    if deny_warnings {
+        args.push(format!("--{}", compile::ARG_DENY_WARNINGS));
+    }
+    // Workspace members are part of the project, so the same levels apply
+    let mut workspace_members = workspace_members.iter().collect::<Vec<_>>();
+    workspace_members.sort();
+    for package_name in workspace_members {
+        args.push(format!("--{}", compile::ARG_WORKSPACE_MEMBER));
+        args.push(package_name.as_str().to_owned());
+    }
+    Ok(args)
+}
+
 fn mk_ast_path(
     mut base: PathBuf,
     package_name: &Option<PackageName>,
@@ -176,14 +311,14 @@ fn mk_ast_path(
 }
 
 // REVIEW do we need to duplicate the nodes like this?
-type BuildGraph = petgraph::Graph<BuildGraphNode, &'static str>;
-type BuildGraphNodes = HashMap<petgraph::graph::NodeIndex, BuildGraphNode>;
+pub(crate) type BuildGraph = petgraph::Graph<BuildGraphNode, &'static str>;
+pub(crate) type BuildGraphNodes = HashMap<petgraph::graph::NodeIndex, BuildGraphNode>;
 
 #[derive(Clone)]
-struct BuildGraphNode {
-    package_name: Option<PackageName>,
-    module_name: ast::ModuleName,
-    source_path: PathBuf,
+pub(crate) struct BuildGraphNode {
+    pub(crate) package_name: Option<PackageName>,
+    pub(crate) module_name: ast::ModuleName,
+    pub(crate) source_path: PathBuf,
     imports: Vec<cst::ImportLine>,
 }
 
@@ -196,7 +331,7 @@ impl fmt::Display for BuildGraphNode {
     }
 }
 
-fn prepare_build_graph(
+pub(crate) fn prepare_build_graph(
     sources: Sources,
     package_sources: PackageSources,
     ditto_version: &semver::Version,
@@ -220,24 +355,13 @@ fn prepare_build_graph(
         };
 
         // Check ditto version requirement
-        if let Some(required_ditto_version) = config.required_ditto_version {
-            let version = semver::Version {
-                pre: Default::default(),
-                build: Default::default(),
-                ..ditto_version.clone()
-            };
-            if !required_ditto_version.matches(&version) {
-                bail!(
-                    "ditto version requirement not met for {}: current version = {}, wanted = {}",
-                    package_name.map_or("current package".into(), |package_name| format!(
-                        "{:?}",
-                        package_name
-                    )),
-                    ditto_version,
-                    required_ditto_version
-                );
-            }
-        }
+        config
+            .check_ditto_version(ditto_version)
+            .into_diagnostic()
+            .wrap_err_with(|| match package_name {
+                Some(ref package_name) => format!("can't build package {:?}", package_name),
+                None => "can't build the current package".to_string(),
+            })?;
 
         // Check target compatibility
         if let Some(ref package_name) = package_name {
@@ -369,27 +493,70 @@ fn check_for_cycles(build_graph: &BuildGraph) -> Result<()> {
                 }
             }
             node_indexes => {
-                let mut module_names = node_indexes
-                    .iter()
-                    .map(|idx| format!("`{}`", build_graph[*idx]))
+                let cycle = find_cycle(build_graph, node_indexes)
+                    .into_iter()
+                    .map(|idx| format!("`{}`", build_graph[idx]))
                     .collect::<Vec<_>>();
 
-                // Sort for determinism
-                module_names.sort();
-
-                bail!("modules form a cycle: {}", module_names.join(", "))
+                bail!("modules form a cycle: {}", cycle.join(" -> "))
             }
         }
     }
     Ok(())
 }
 
+/// Find the shortest import path from a module in a strongly connected component back to itself.
+///
+/// The returned path starts and ends with the same node. Starts from the alphabetically
+/// first module, for determinism.
+fn find_cycle(
+    build_graph: &BuildGraph,
+    scc: &[petgraph::graph::NodeIndex],
+) -> Vec<petgraph::graph::NodeIndex> {
+    let sort_key = |idx: &petgraph::graph::NodeIndex| build_graph[*idx].to_string();
+    let start = *scc.iter().min_by_key(|idx| sort_key(idx)).unwrap();
+
+    // Breadth-first search, so we find the shortest cycle
+    let mut parents = HashMap::new();
+    let mut queue = std::collections::VecDeque::from([start]);
+    while let Some(current) = queue.pop_front() {
+        let mut neighbors = build_graph
+            .neighbors(current)
+            .filter(|idx| scc.contains(idx))
+            .collect::<Vec<_>>();
+        neighbors.sort_by_key(sort_key);
+        for neighbor in neighbors {
+            if neighbor == start {
+                let mut cycle = vec![start, current];
+                let mut node = current;
+                while let Some(parent) = parents.get(&node) {
+                    cycle.push(*parent);
+                    node = *parent;
+                }
+                cycle.push(start);
+                cycle.dedup();
+                cycle.reverse();
+                return cycle;
+            }
+            if !parents.contains_key(&neighbor) {
+                parents.insert(neighbor, current);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+    // Shouldn't happen for a strongly connected component,
+    // but fall back to listing the modules
+    let mut scc = scc.to_vec();
+    scc.sort_by_key(sort_key);
+    scc
+}
+
 /// A representation of the [ninja file syntax](https://github.com/ninja-build/ninja/blob/master/misc/ninja_syntax.py).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BuildNinja {
     variables: HashMap<String, String>,
-    rules: Vec<Rule>,
-    builds: Vec<Build>,
+    pub(crate) rules: Vec<Rule>,
+    pub(crate) builds: Vec<Build>,
 }
 
 impl BuildNinja {
@@ -397,6 +564,8 @@ impl BuildNinja {
         build_dir: &Path,
         ditto_bin: &Path,
         compile_subcommand: &'static str,
+        compile_mode: CompileMode,
+        warning_args: Vec<String>,
         config: &Config,
     ) -> Self {
         let build_dir_variable = (
@@ -404,7 +573,14 @@ impl BuildNinja {
             build_dir.to_string_lossy().into_owned(),
         );
         let variables = HashMap::from_iter(vec![(build_dir_variable)]);
-        let mut rules = vec![Rule::new_ast(build_dir, ditto_bin, compile_subcommand)];
+        let mut rules = vec![match compile_mode {
+            CompileMode::PerModule => {
+                Rule::new_ast(build_dir, ditto_bin, compile_subcommand, warning_args)
+            }
+            CompileMode::Batch => {
+                Rule::new_ast_batch(build_dir, ditto_bin, compile_subcommand, warning_args)
+            }
+        }];
 
         if config.targets_js() {
             rules.push(Rule::new_js(ditto_bin, compile_subcommand));
@@ -417,6 +593,28 @@ impl BuildNinja {
             builds: Vec::new(),
         }
     }
+    /// Only keep the rules and builds for checking modules, i.e. skip code generation.
+    ///
+    /// The remaining builds are identical to those of the full build, so the
+    /// artifacts they produce are reused by a subsequent full build.
+    pub fn check_only(self) -> Self {
+        let is_check_rule =
+            |rule_name: &str| rule_name == RULE_NAME_AST || rule_name == RULE_NAME_AST_BATCH;
+        Self {
+            variables: self.variables,
+            rules: self
+                .rules
+                .into_iter()
+                .filter(|rule| is_check_rule(&rule.name))
+                .collect(),
+            builds: self
+                .builds
+                .into_iter()
+                .filter(|build| is_check_rule(&build.rule_name))
+                .collect(),
+        }
+    }
+
     /// Render to `build.ninja` file syntax.
     pub fn into_syntax(self) -> String {
         self.into_syntax_with(|path| path.to_string_lossy().into_owned())
@@ -475,58 +673,148 @@ impl BuildNinja {
 }
 
 static RULE_NAME_AST: &str = "ast";
+static RULE_NAME_AST_BATCH: &str = "ast_batch";
 static RULE_NAME_JS: &str = "js";
 static RULE_NAME_PACKAGE_JSON: &str = "package_json";
 
-#[derive(Debug)]
-struct Rule {
-    name: String,
-    command: String,
+/// Placeholder for a build's inputs in [Rule] arguments, i.e. ninja's `$in`.
+pub(crate) static RULE_ARG_IN: &str = "${in}";
+/// Placeholder for a build's outputs in [Rule] arguments, i.e. ninja's `$out`.
+pub(crate) static RULE_ARG_OUT: &str = "${out}";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Rule {
+    pub(crate) name: String,
+    pub(crate) program: String,
+    /// May contain [RULE_ARG_IN] and [RULE_ARG_OUT] placeholders.
+    pub(crate) args: Vec<String>,
+    /// Should outputs be re-stat'd after running?
+    ///
+    /// I.e. the command might leave some outputs untouched, in which case
+    /// builds depending on those outputs can be skipped.
+    pub(crate) restat: bool,
 }
 
 impl Rule {
-    fn new_ast(build_dir: &Path, ditto_bin: &Path, compile: &str) -> Self {
+    fn new_ast(
+        build_dir: &Path,
+        ditto_bin: &Path,
+        compile: &str,
+        warning_args: Vec<String>,
+    ) -> Self {
         use compile::{ARG_BUILD_DIR, ARG_INPUTS as i, ARG_OUTPUTS as o, SUBCOMMAND_AST as ast};
-        let ditto = ditto_bin.to_string_lossy();
-        let build_dir = build_dir.to_string_lossy();
+        let mut args = vec![
+            compile.to_string(),
+            ast.to_string(),
+            format!("--{ARG_BUILD_DIR}"),
+            build_dir.to_string_lossy().into_owned(),
+        ];
+        args.extend(warning_args);
+        args.extend([
+            format!("-{i}"),
+            RULE_ARG_IN.to_string(),
+            format!("-{o}"),
+            RULE_ARG_OUT.to_string(),
+        ]);
         Self {
             name: RULE_NAME_AST.to_string(),
-            command: format!(
-                "{ditto} {compile} {ast} --{ARG_BUILD_DIR} {build_dir} -{i} ${{in}} -{o} ${{out}}"
-            ),
+            program: ditto_bin.to_string_lossy().into_owned(),
+            args,
+            // `.ast-exports` files are only rewritten if the exports changed
+            restat: true,
+        }
+    }
+
+    fn new_ast_batch(
+        build_dir: &Path,
+        ditto_bin: &Path,
+        compile: &str,
+        warning_args: Vec<String>,
+    ) -> Self {
+        use compile::{
+            ARG_BUILD_DIR, ARG_INPUTS as i, ARG_OUTPUTS as o, SUBCOMMAND_AST_BATCH as ast_batch,
+        };
+        let mut args = vec![
+            compile.to_string(),
+            ast_batch.to_string(),
+            format!("--{ARG_BUILD_DIR}"),
+            build_dir.to_string_lossy().into_owned(),
+        ];
+        args.extend(warning_args);
+        args.extend([
+            format!("-{i}"),
+            RULE_ARG_IN.to_string(),
+            format!("-{o}"),
+            RULE_ARG_OUT.to_string(),
+        ]);
+        Self {
+            name: RULE_NAME_AST_BATCH.to_string(),
+            program: ditto_bin.to_string_lossy().into_owned(),
+            args,
+            restat: true,
         }
     }
 
     fn new_js(ditto_bin: &Path, compile: &str) -> Self {
         use compile::{ARG_INPUTS as i, ARG_OUTPUTS as o, SUBCOMMAND_JS as js};
-        let ditto = ditto_bin.to_string_lossy();
         Self {
             name: RULE_NAME_JS.to_string(),
-            command: format!("{ditto} {compile} {js} -{i} ${{in}} -{o} ${{out}}"),
+            program: ditto_bin.to_string_lossy().into_owned(),
+            args: vec![
+                compile.to_string(),
+                js.to_string(),
+                format!("-{i}"),
+                RULE_ARG_IN.to_string(),
+                format!("-{o}"),
+                RULE_ARG_OUT.to_string(),
+            ],
+            restat: false,
         }
     }
 
     fn new_package_json(ditto_bin: &Path, compile: &str) -> Self {
         use compile::{ARG_INPUTS as i, ARG_OUTPUTS as o, SUBCOMMAND_PACKAGE_JSON as package_json};
-        let ditto = ditto_bin.to_string_lossy();
         Self {
             name: RULE_NAME_PACKAGE_JSON.to_string(),
-            command: format!("{ditto} {compile} {package_json} -{i} ${{in}} -{o} ${{out}}"),
+            program: ditto_bin.to_string_lossy().into_owned(),
+            args: vec![
+                compile.to_string(),
+                package_json.to_string(),
+                format!("-{i}"),
+                RULE_ARG_IN.to_string(),
+                format!("-{o}"),
+                RULE_ARG_OUT.to_string(),
+            ],
+            restat: false,
         }
     }
 
+    /// The command as it appears in a `build.ninja` file.
+    pub(crate) fn command(&self) -> String {
+        std::iter::once(&self.program)
+            .chain(self.args.iter())
+            .map(|arg| arg.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     fn into_syntax(self) -> String {
-        let Self { name, command } = self;
-        format!("rule {name}\n  command = {command}")
+        let command = self.command();
+        let Self { name, restat, .. } = self;
+        if restat {
+            format!("rule {name}\n  command = {command}\n  restat = 1")
+        } else {
+            format!("rule {name}\n  command = {command}")
+        }
     }
 }
 
-#[derive(Debug)]
-struct Build {
-    outputs: Vec<PathBuf>,
-    rule_name: String,
-    inputs: Vec<PathBuf>,
-    variables: HashMap<String, String>,
+#[derive(Debug, Clone)]
+pub(crate) struct Build {
+    pub(crate) outputs: Vec<PathBuf>,
+    pub(crate) rule_name: String,
+    pub(crate) inputs: Vec<PathBuf>,
+    pub(crate) variables: HashMap<String, String>,
 }
 
 impl Build {
@@ -557,6 +845,19 @@ impl Build {
         }
     }
 
+    /// Outputs and inputs are added as modules are discovered.
+    fn new_ast_batch(package_descriptor: &str) -> Self {
+        Self {
+            outputs: Vec::new(),
+            rule_name: String::from(RULE_NAME_AST_BATCH),
+            inputs: Vec::new(),
+            variables: HashMap::from_iter(vec![(
+                String::from("description"),
+                format!("Checking package {}", package_descriptor),
+            )]),
+        }
+    }
+
     fn new_js(
         module_descriptor: String,
         js_path: PathBuf,
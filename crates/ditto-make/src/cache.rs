@@ -0,0 +1,331 @@
+use crate::common;
+use ditto_ast as ast;
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::debug;
+
+/// Environment variable pointing to a directory that should be used as a
+/// shared build cache by `ditto compile`.
+///
+/// Cache entries aren't versioned, so this directory should be specific to the
+/// current ditto version (see [gc_build_cache] for the expected layout).
+/// An empty value disables the cache.
+pub static ENV_BUILD_CACHE_DIR: &str = "DITTO_BUILD_CACHE_DIR";
+
+/// Touched whenever a cache entry is used, so we know what can be collected.
+static LAST_USED: &str = "last-used";
+
+/// The (serialized) outputs of checking a module.
+pub(crate) struct AstOutputs {
+    pub(crate) ast: Vec<u8>,
+    pub(crate) ast_exports: Vec<u8>,
+    /// Serialized `Option<WarningsBundle>`.
+    pub(crate) checker_warnings: Vec<u8>,
+}
+
+/// A content-addressed store of [AstOutputs] and generated JavaScript.
+pub(crate) struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub(crate) fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Returns `None` if [ENV_BUILD_CACHE_DIR] isn't set, or is empty.
+    pub(crate) fn from_env() -> Option<Self> {
+        let dir = std::env::var_os(ENV_BUILD_CACHE_DIR).filter(|dir| !dir.is_empty())?;
+        Some(Self::new(PathBuf::from(dir)))
+    }
+
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<AstOutputs> {
+        let entry = self.entry_path(key);
+        let read = |extension: &str| fs::read(entry.join(extension)).ok();
+        let outputs = AstOutputs {
+            ast: read(common::EXTENSION_AST)?,
+            ast_exports: read(common::EXTENSION_AST_EXPORTS)?,
+            checker_warnings: read(common::EXTENSION_CHECKER_WARNINGS)?,
+        };
+        touch_entry(key, &entry);
+        Some(outputs)
+    }
+
+    pub(crate) fn get_js(&self, key: &CacheKey) -> Option<Vec<u8>> {
+        let entry = self.entry_path(key);
+        let js = fs::read(entry.join(common::EXTENSION_JS)).ok()?;
+        touch_entry(key, &entry);
+        Some(js)
+    }
+
+    pub(crate) fn put(&self, key: &CacheKey, outputs: &AstOutputs) -> Result<()> {
+        self.put_entry(
+            key,
+            &[
+                (common::EXTENSION_AST, outputs.ast.as_slice()),
+                (
+                    common::EXTENSION_AST_EXPORTS,
+                    outputs.ast_exports.as_slice(),
+                ),
+                (
+                    common::EXTENSION_CHECKER_WARNINGS,
+                    outputs.checker_warnings.as_slice(),
+                ),
+            ],
+        )
+    }
+
+    pub(crate) fn put_js(&self, key: &CacheKey, js: &[u8]) -> Result<()> {
+        self.put_entry(key, &[(common::EXTENSION_JS, js)])
+    }
+
+    fn put_entry(&self, key: &CacheKey, files: &[(&str, &[u8])]) -> Result<()> {
+        let entry = self.entry_path(key);
+        if entry.exists() {
+            return Ok(());
+        }
+        // Write to a temporary directory first,
+        // so that concurrent builds never see a partial entry
+        let tmp = self
+            .dir
+            .join(format!("{}.{}.tmp", key.0, std::process::id()));
+        fs::create_dir_all(&tmp)
+            .into_diagnostic()
+            .wrap_err(format!("error creating build cache entry {:?}", tmp))?;
+        for (extension, contents) in files {
+            fs::write(tmp.join(extension), contents)
+                .into_diagnostic()
+                .wrap_err(format!("error writing build cache entry {:?}", tmp))?;
+        }
+        fs::write(tmp.join(LAST_USED), [])
+            .into_diagnostic()
+            .wrap_err(format!("error writing build cache entry {:?}", tmp))?;
+        if fs::rename(&tmp, &entry).is_err() {
+            // Someone beat us to it, which is fine
+            let _ = fs::remove_dir_all(&tmp);
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(&key.0)
+    }
+}
+
+fn touch_entry(key: &CacheKey, entry: &Path) {
+    debug!("Build cache hit for {}", key.0);
+    if let Err(err) = fs::write(entry.join(LAST_USED), []) {
+        debug!("Error touching build cache entry {:?}: {:?}", entry, err);
+    }
+}
+
+/// Identifies the cached outputs of a module.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct CacheKey(String);
+
+impl CacheKey {
+    /// The outputs of checking a module depend on its source, the package it
+    /// belongs to, and the exports of the modules it imports.
+    ///
+    /// The source name is included because it ends up in the `.ast` output.
+    pub(crate) fn new(
+        source_name: &str,
+        source: &str,
+        package_name: &Option<ast::PackageName>,
+        mut dependency_exports: Vec<(Option<ast::PackageName>, &[u8])>,
+    ) -> Self {
+        dependency_exports.sort();
+        let mut hasher = DefaultHasher::new();
+        (source_name, source, package_name, dependency_exports).hash(&mut hasher);
+        Self(format!("{:016x}", hasher.finish()))
+    }
+
+    /// Generated JavaScript depends on the (serialized) `.ast` and where the
+    /// foreign module lives relative to the output.
+    pub(crate) fn js(ast: &[u8], foreign_module_path: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        (common::EXTENSION_JS, ast, foreign_module_path).hash(&mut hasher);
+        Self(format!("{:016x}", hasher.finish()))
+    }
+}
+
+/// Summary of a [gc_build_cache] run.
+#[derive(Debug, Default)]
+pub struct GcStats {
+    /// The number of cache entries that were removed.
+    pub removed: usize,
+    /// The number of cache entries that were kept.
+    pub kept: usize,
+}
+
+/// Remove build cache entries that haven't been used within `max_age`.
+///
+/// `cache_dir` is expected to contain a directory per ditto version,
+/// each of which can be passed as [ENV_BUILD_CACHE_DIR].
+pub fn gc_build_cache(cache_dir: &Path, max_age: Duration) -> Result<GcStats> {
+    let mut stats = GcStats::default();
+    if !cache_dir.exists() {
+        return Ok(stats);
+    }
+    let now = SystemTime::now();
+    for version_dir in read_dirs(cache_dir)? {
+        for entry in read_dirs(&version_dir)? {
+            // Partially written entries won't have been touched,
+            // so they'll fall back to the directory's modification time
+            let last_used = fs::metadata(entry.join(LAST_USED))
+                .or_else(|_| fs::metadata(&entry))
+                .and_then(|metadata| metadata.modified())
+                .into_diagnostic()
+                .wrap_err(format!("error reading build cache entry {:?}", entry))?;
+            let age = now.duration_since(last_used).unwrap_or_default();
+            if age > max_age {
+                debug!("Removing build cache entry {:?}", entry);
+                fs::remove_dir_all(&entry)
+                    .into_diagnostic()
+                    .wrap_err(format!("error removing build cache entry {:?}", entry))?;
+                stats.removed += 1;
+            } else {
+                stats.kept += 1;
+            }
+        }
+        if read_dirs(&version_dir)?.is_empty() {
+            // Likely an old ditto version
+            let _ = fs::remove_dir(&version_dir);
+        }
+    }
+    Ok(stats)
+}
+
+fn read_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)
+        .into_diagnostic()
+        .wrap_err(format!("error reading {:?}", dir))?
+    {
+        let path = entry.into_diagnostic()?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ast_outputs(ast: &str) -> AstOutputs {
+        AstOutputs {
+            ast: ast.as_bytes().to_vec(),
+            ast_exports: b"exports".to_vec(),
+            checker_warnings: b"null".to_vec(),
+        }
+    }
+
+    fn key(source: &str) -> CacheKey {
+        CacheKey::new("./ditto-src/A.ditto", source, &None, vec![])
+    }
+
+    #[test]
+    fn it_misses_then_hits() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BuildCache::new(dir.path().to_path_buf());
+
+        assert!(cache.get(&key("five = 5")).is_none());
+        cache.put(&key("five = 5"), &ast_outputs("A")).unwrap();
+        let cached = cache.get(&key("five = 5")).unwrap();
+        assert_eq!(cached.ast, b"A".as_slice());
+        assert_eq!(cached.ast_exports, b"exports".as_slice());
+        assert_eq!(cached.checker_warnings, b"null".as_slice());
+
+        // Existing entries are left alone
+        cache.put(&key("five = 5"), &ast_outputs("B")).unwrap();
+        assert_eq!(cache.get(&key("five = 5")).unwrap().ast, b"A".as_slice());
+
+        let js_key = CacheKey::js(b"A", "./A.js");
+        assert!(cache.get_js(&js_key).is_none());
+        cache.put_js(&js_key, b"export {};".as_slice()).unwrap();
+        assert_eq!(cache.get_js(&js_key).unwrap(), b"export {};".as_slice());
+        // JavaScript entries aren't mistaken for AST entries
+        assert!(cache.get(&js_key).is_none());
+    }
+
+    #[test]
+    fn it_invalidates_keys() {
+        let dep = Some(ast::PackageName(String::from("dep")));
+        let key_with = |source_name, source, package_name, b_exports: &'static str| {
+            CacheKey::new(
+                source_name,
+                source,
+                package_name,
+                vec![(None, b_exports.as_bytes()), (dep.clone(), b"C".as_slice())],
+            )
+        };
+        let base = key_with("./ditto-src/A.ditto", "five = 5", &None, "B");
+
+        assert_eq!(
+            base,
+            key_with("./ditto-src/A.ditto", "five = 5", &None, "B")
+        );
+        assert_ne!(
+            base,
+            key_with("./ditto-src/Z.ditto", "five = 5", &None, "B")
+        );
+        assert_ne!(
+            base,
+            key_with("./ditto-src/A.ditto", "five = 6", &None, "B")
+        );
+        assert_ne!(base, key_with("./ditto-src/A.ditto", "five = 5", &dep, "B"));
+        assert_ne!(
+            base,
+            key_with("./ditto-src/A.ditto", "five = 5", &None, "B2")
+        );
+        // Dependency order doesn't matter, but the dependencies do
+        let dependency_exports = vec![(dep.clone(), b"C".as_slice()), (None, b"B".as_slice())];
+        assert_eq!(
+            base,
+            CacheKey::new("./ditto-src/A.ditto", "five = 5", &None, dependency_exports)
+        );
+        let dependency_exports = vec![(None, b"B".as_slice())];
+        assert_ne!(
+            base,
+            CacheKey::new("./ditto-src/A.ditto", "five = 5", &None, dependency_exports)
+        );
+
+        assert_eq!(CacheKey::js(b"A", "./A.js"), CacheKey::js(b"A", "./A.js"));
+        assert_ne!(CacheKey::js(b"A", "./A.js"), CacheKey::js(b"B", "./A.js"));
+        assert_ne!(CacheKey::js(b"A", "./A.js"), CacheKey::js(b"A", "../A.js"));
+    }
+
+    #[test]
+    fn it_collects_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            gc_build_cache(&dir.path().join("nope"), Duration::ZERO)
+                .unwrap()
+                .removed,
+            0
+        );
+
+        let cache = BuildCache::new(dir.path().join("0.0.1-abc"));
+        cache.put(&key("five = 5"), &ast_outputs("A")).unwrap();
+        cache.put(&key("six = 6"), &ast_outputs("B")).unwrap();
+
+        let stats = gc_build_cache(dir.path(), Duration::from_secs(60 * 60)).unwrap();
+        assert_eq!((stats.removed, stats.kept), (0, 2));
+        assert!(cache.get(&key("five = 5")).is_some());
+
+        std::thread::sleep(Duration::from_millis(10));
+        let stats = gc_build_cache(dir.path(), Duration::ZERO).unwrap();
+        assert_eq!((stats.removed, stats.kept), (2, 0));
+        assert!(cache.get(&key("five = 5")).is_none());
+        // Empty version directories are removed too
+        assert!(!dir.path().join("0.0.1-abc").exists());
+    }
+}
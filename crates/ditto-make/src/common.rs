@@ -32,6 +32,16 @@ pub fn serialize<W: Write, T: Serialize>(writer: W, value: &T) -> Result<()> {
     }
 }
 
+/// Like [deserialize], but for bytes that have already been read.
+#[tracing::instrument(level = "trace", skip_all)]
+pub fn deserialize_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    if cfg!(debug_assertions) {
+        serde_json::from_slice(bytes).into_diagnostic()
+    } else {
+        ciborium::de::from_reader(bytes).into_diagnostic()
+    }
+}
+
 /// Deserialize a value using a JSON if this is a debug build, and CBOR otherwise.
 #[tracing::instrument(level = "trace")]
 pub fn deserialize<T: DeserializeOwned>(path: &Path) -> Result<T> {
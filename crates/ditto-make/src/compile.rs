@@ -1,26 +1,36 @@
-use clap::{arg, Arg, ArgMatches, Command};
+use clap::{arg, Arg, ArgAction, ArgMatches, Command};
 use ditto_ast as ast;
 use ditto_checker as checker;
 use ditto_codegen_js as js;
-use ditto_config::read_config;
+use ditto_config::{read_config, WarningLevel};
 use ditto_cst as cst;
-use miette::{miette, IntoDiagnostic, NamedSource, Report, Result};
+use miette::{miette, Diagnostic, IntoDiagnostic, NamedSource, Report, Result};
+use rayon::prelude::*;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::File,
     io::{Read, Write},
     path::{Path, PathBuf},
 };
 
-use crate::common;
+use crate::{
+    cache::{AstOutputs, BuildCache, CacheKey},
+    common,
+    message_format::{DiagnosticRecord, MessageFormat},
+    timings::{self, Phase},
+};
 
 pub static SUBCOMMAND_AST: &str = "ast";
+pub static SUBCOMMAND_AST_BATCH: &str = "ast-batch";
 pub static SUBCOMMAND_JS: &str = "js";
 pub static SUBCOMMAND_PACKAGE_JSON: &str = "package-json";
 
 pub static ARG_BUILD_DIR: &str = "build-dir";
 pub static ARG_INPUTS: char = 'i';
 pub static ARG_OUTPUTS: char = 'o';
+pub static ARG_WARNING_LEVEL: &str = "warning-level";
+pub static ARG_DENY_WARNINGS: &str = "deny-warnings";
+pub static ARG_WORKSPACE_MEMBER: &str = "workspace-member";
 
 /// The internal compile CLI.
 pub fn command(name: impl Into<clap::builder::Str>) -> Command {
@@ -28,6 +38,17 @@ pub fn command(name: impl Into<clap::builder::Str>) -> Command {
         .subcommand(
             Command::new(SUBCOMMAND_AST)
                 .arg(arg!(--"build-dir" <DIR>).required(true))
+                .arg(arg_warning_level())
+                .arg(arg_deny_warnings())
+                .arg(arg_workspace_member())
+                .arg(arg_inputs())
+                .arg(arg_outputs()),
+        )
+        .subcommand(
+            Command::new(SUBCOMMAND_AST_BATCH)
+                .arg(arg!(--"build-dir" <DIR>).required(true))
+                .arg(arg_warning_level())
+                .arg(arg_deny_warnings())
                 .arg(arg_inputs())
                 .arg(arg_outputs()),
         )
@@ -42,6 +63,25 @@ pub fn command(name: impl Into<clap::builder::Str>) -> Command {
                 .arg(arg_output()),
         );
 
+    fn arg_warning_level() -> Arg {
+        Arg::new(ARG_WARNING_LEVEL)
+            .long(ARG_WARNING_LEVEL)
+            .value_name("CODE=LEVEL")
+            .num_args(1)
+            .action(ArgAction::Append)
+    }
+    fn arg_deny_warnings() -> Arg {
+        Arg::new(ARG_DENY_WARNINGS)
+            .long(ARG_DENY_WARNINGS)
+            .action(ArgAction::SetTrue)
+    }
+    fn arg_workspace_member() -> Arg {
+        Arg::new(ARG_WORKSPACE_MEMBER)
+            .long(ARG_WORKSPACE_MEMBER)
+            .value_name("PACKAGE")
+            .num_args(1)
+            .action(ArgAction::Append)
+    }
     fn arg_input() -> Arg {
         Arg::new("input")
             .short(ARG_INPUTS)
@@ -69,12 +109,72 @@ pub fn command(name: impl Into<clap::builder::Str>) -> Command {
 }
 
 /// Run the program given matches from [compile].
+///
+/// If a machine readable [MessageFormat] was requested (via [ENV_MESSAGE_FORMAT](crate::ENV_MESSAGE_FORMAT))
+/// then errors are printed to stdout as [DiagnosticRecord]s.
+///
+/// If [ENV_TIMINGS_DIR](crate::ENV_TIMINGS_DIR) is set then timings for this
+/// process are recorded there.
 pub fn run(matches: &ArgMatches) -> Result<()> {
+    let start_ms = timings::now_ms();
+    let result = run_subcommand(matches);
+    if let Some((subcommand, matches)) = matches.subcommand() {
+        // Not every subcommand has the same arguments
+        let strings = |id: &str| {
+            matches
+                .try_get_many::<String>(id)
+                .ok()
+                .flatten()
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let inputs = [strings("inputs"), strings("input")].concat();
+        let outputs = [strings("outputs"), strings("output")].concat();
+        let description = describe_edge(subcommand, &inputs, &outputs);
+        if let Err(err) =
+            timings::write_edge_timings(subcommand, description, inputs, outputs, start_ms)
+        {
+            // Not fatal
+            tracing::warn!("error writing timings: {:?}", err);
+        }
+    }
+    match result {
+        Err(err) if MessageFormat::from_env().is_machine_readable() => {
+            println!("{}", DiagnosticRecord::from_diagnostic(&*err).to_json());
+            std::process::exit(1)
+        }
+        result => result,
+    }
+}
+
+/// Summarise what a compile subcommand is doing, for timing reports.
+fn describe_edge(subcommand: &str, inputs: &[String], outputs: &[String]) -> String {
+    let ditto_inputs = inputs
+        .iter()
+        .filter(|input| full_extension(Path::new(input)) == Some(common::EXTENSION_DITTO))
+        .collect::<Vec<_>>();
+    match ditto_inputs.as_slice() {
+        [ditto_input] => format!("{} {}", subcommand, ditto_input),
+        [] => format!("{} {}", subcommand, outputs.join(" ")),
+        ditto_inputs => format!("{} ({} modules)", subcommand, ditto_inputs.len()),
+    }
+}
+
+fn run_subcommand(matches: &ArgMatches) -> Result<()> {
     if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_AST) {
         let build_dir = matches.get_one::<String>("build-dir").unwrap();
+        let warning_levels = WarningLevels::from_matches(matches)?;
+        let inputs = matches.get_many("inputs").unwrap().cloned().collect();
+        let outputs = matches.get_many("outputs").unwrap().cloned().collect();
+        run_ast(build_dir, &warning_levels, inputs, outputs)
+    } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_AST_BATCH) {
+        let build_dir = matches.get_one::<String>("build-dir").unwrap();
+        let warning_levels = WarningLevels::from_matches(matches)?;
         let inputs = matches.get_many("inputs").unwrap().cloned().collect();
         let outputs = matches.get_many("outputs").unwrap().cloned().collect();
-        run_ast(build_dir, inputs, outputs)
+        run_ast_batch(build_dir, &warning_levels, inputs, outputs)
     } else if let Some(matches) = matches.subcommand_matches(SUBCOMMAND_JS) {
         let inputs = matches.get_many("inputs").unwrap().cloned().collect();
         let outputs = matches.get_many("outputs").unwrap().cloned().collect();
@@ -96,16 +196,393 @@ pub struct WarningsBundle {
     pub warnings: Vec<checker::WarningReport>,
 }
 
+/// How warnings for the current package (and its workspace members) should be treated.
+#[derive(Default)]
+struct WarningLevels {
+    /// Configured levels, keyed by warning code.
+    levels: HashMap<String, WarningLevel>,
+    /// Treat [WarningLevel::Warn] as [WarningLevel::Deny].
+    deny_warnings: bool,
+    /// Packages that are part of the current project.
+    workspace_members: HashSet<String>,
+}
+
+impl WarningLevels {
+    fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut levels = HashMap::new();
+        for arg in matches
+            .get_many::<String>(ARG_WARNING_LEVEL)
+            .into_iter()
+            .flatten()
+        {
+            let (code, level) = arg
+                .split_once('=')
+                .and_then(|(code, level)| Some((code, WarningLevel::parse(level)?)))
+                .ok_or_else(|| miette!("invalid warning level: {}", arg))?;
+            levels.insert(code.to_owned(), level);
+        }
+        Ok(Self {
+            levels,
+            deny_warnings: matches.get_flag(ARG_DENY_WARNINGS),
+            workspace_members: matches
+                .get_many::<String>(ARG_WORKSPACE_MEMBER)
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Warning levels only apply to the current package and its workspace members,
+    /// not to dependencies.
+    fn applies_to(&self, package_name: &Option<ast::PackageName>) -> bool {
+        package_name.as_ref().map_or(true, |package_name| {
+            self.workspace_members.contains(&package_name.0)
+        })
+    }
+
+    fn get(&self, warning: &checker::WarningReport) -> WarningLevel {
+        let level = warning
+            .code()
+            .and_then(|code| self.levels.get(&code.to_string()).copied())
+            .unwrap_or(WarningLevel::Warn);
+        if level == WarningLevel::Warn && self.deny_warnings {
+            WarningLevel::Deny
+        } else {
+            level
+        }
+    }
+
+    /// Drop allowed warnings, and fail if there are any denied warnings.
+    fn apply(&self, ast_outputs: AstOutputs) -> Result<AstOutputs> {
+        if self.levels.is_empty() && !self.deny_warnings {
+            return Ok(ast_outputs);
+        }
+        let warnings_bundle =
+            common::deserialize_slice::<Option<WarningsBundle>>(&ast_outputs.checker_warnings)?;
+        let WarningsBundle {
+            name,
+            source,
+            warnings,
+        } = if let Some(warnings_bundle) = warnings_bundle {
+            warnings_bundle
+        } else {
+            return Ok(ast_outputs);
+        };
+
+        let mut kept = Vec::new();
+        let mut denied = Vec::new();
+        for warning in warnings {
+            match self.get(&warning) {
+                WarningLevel::Allow => {}
+                WarningLevel::Warn => kept.push(warning),
+                WarningLevel::Deny => denied.push(warning),
+            }
+        }
+
+        if !denied.is_empty() {
+            let source = std::sync::Arc::new(source);
+            let message_format = MessageFormat::from_env();
+            for warning in denied.iter() {
+                let report = Report::from(warning.clone())
+                    .with_source_code(NamedSource::new(&name, source.clone()));
+                if message_format.is_machine_readable() {
+                    let mut record = DiagnosticRecord::from_diagnostic(&*report);
+                    record.severity = String::from("error");
+                    println!("{}", record.to_json());
+                } else {
+                    eprintln!("{:?}", report);
+                }
+            }
+            if message_format.is_machine_readable() {
+                // Warnings aren't collected for failed builds,
+                // so report the rest of them now
+                for warning in kept {
+                    let report = Report::from(warning)
+                        .with_source_code(NamedSource::new(&name, source.clone()));
+                    println!("{}", DiagnosticRecord::from_diagnostic(&*report).to_json());
+                }
+            }
+            return Err(miette!(
+                help = "warning levels can be configured in the [warnings] table of ditto.toml",
+                "{} denied warning{} in {}",
+                denied.len(),
+                if denied.len() == 1 { "" } else { "s" },
+                name
+            ));
+        }
+
+        let warnings_bundle = if kept.is_empty() {
+            None
+        } else {
+            Some(WarningsBundle {
+                name,
+                source,
+                warnings: kept,
+            })
+        };
+        let mut checker_warnings = Vec::new();
+        common::serialize(&mut checker_warnings, &warnings_bundle)?;
+        Ok(AstOutputs {
+            checker_warnings,
+            ..ast_outputs
+        })
+    }
+}
+
 #[tracing::instrument(level = "trace", skip_all)]
-fn run_ast(build_dir: &str, inputs: Vec<String>, outputs: Vec<String>) -> Result<()> {
+fn run_ast(
+    build_dir: &str,
+    warning_levels: &WarningLevels,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+) -> Result<()> {
     let mut ditto_input = None;
     let mut everything = checker::Everything::default();
+    let mut dependency_exports = Vec::new();
+
+    let output_package_name = get_output_package_name(build_dir, &outputs);
+
+    let default_warning_levels = WarningLevels::default();
+    let warning_levels = if warning_levels.applies_to(&output_package_name) {
+        warning_levels
+    } else {
+        &default_warning_levels
+    };
+
+    for input in inputs {
+        let path = Path::new(&input);
+        match full_extension(path) {
+            Some(common::EXTENSION_DITTO) => {
+                ditto_input = Some(read_ditto_input(path)?);
+            }
+            Some(common::EXTENSION_AST_EXPORTS) => {
+                let (package_name, _module_name, serialized) =
+                    add_ast_exports(build_dir, path, &output_package_name, &mut everything)?;
+                dependency_exports.push((package_name, serialized));
+            }
+            other => panic!("unexpected input extension {:#?}: {}", other, input),
+        }
+    }
+
+    let (ditto_input_name, ditto_input_source) = ditto_input.unwrap();
+
+    let cache = BuildCache::from_env().map(|cache| {
+        let key = CacheKey::new(
+            &ditto_input_name,
+            &ditto_input_source,
+            &output_package_name,
+            dependency_exports
+                .iter()
+                .map(|(package_name, serialized)| (package_name.clone(), serialized.as_slice()))
+                .collect(),
+        );
+        (cache, key)
+    });
+    if let Some((ref cache, ref key)) = cache {
+        if let Some(ast_outputs) = cache.get(key) {
+            return write_ast_outputs(outputs.iter(), &warning_levels.apply(ast_outputs)?);
+        }
+    }
+
+    let cst = parse_cst(&ditto_input_source, &ditto_input_name)?;
+    let (ast, warnings) = check_module(&everything, cst, &ditto_input_name, &ditto_input_source)?;
+    let ast_outputs = serialize_ast_outputs(ditto_input_name, ditto_input_source, &ast, warnings)?;
+
+    if let Some((cache, key)) = cache {
+        if let Err(err) = cache.put(&key, &ast_outputs) {
+            // Not fatal
+            tracing::warn!("error writing to build cache: {:?}", err);
+        }
+    }
+
+    write_ast_outputs(outputs.iter(), &warning_levels.apply(ast_outputs)?)
+}
+
+/// Like [run_ast], but for many modules (typically a whole package) at once.
+///
+/// Modules are checked in dependency order, with independent modules being
+/// checked in parallel. Exports are kept in memory rather than being
+/// re-read from `.ast-exports` files.
+#[tracing::instrument(level = "trace", skip_all)]
+fn run_ast_batch(
+    build_dir: &str,
+    warning_levels: &WarningLevels,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+) -> Result<()> {
+    let mut ditto_inputs = Vec::new();
+    let mut everything = checker::Everything::default();
+    // Serialized exports, for computing cache keys
+    let mut serialized_exports: HashMap<(Option<ast::PackageName>, ast::ModuleName), Vec<u8>> =
+        HashMap::new();
 
-    // Need to figure out if we're compiling a "package module",
-    // and if so what the name of the package is.
-    //
-    // This is so that imports referencing other modules in the same package
-    // resolve correctly.
+    let output_package_name = get_output_package_name(build_dir, &outputs);
+
+    let default_warning_levels = WarningLevels::default();
+    let warning_levels = if warning_levels.applies_to(&output_package_name) {
+        warning_levels
+    } else {
+        &default_warning_levels
+    };
+
+    for input in inputs {
+        let path = Path::new(&input);
+        match full_extension(path) {
+            Some(common::EXTENSION_DITTO) => {
+                ditto_inputs.push(read_ditto_input(path)?);
+            }
+            Some(common::EXTENSION_AST_EXPORTS) => {
+                let (package_name, module_name, serialized) =
+                    add_ast_exports(build_dir, path, &output_package_name, &mut everything)?;
+                serialized_exports.insert((package_name, module_name), serialized);
+            }
+            other => panic!("unexpected input extension {:#?}: {}", other, input),
+        }
+    }
+
+    struct BatchModule {
+        name: String,
+        source: String,
+        module_name: ast::ModuleName,
+        cst: cst::Module,
+    }
+
+    let mut remaining = ditto_inputs
+        .into_par_iter()
+        .map(|(name, source)| {
+            let cst = parse_cst(&source, &name)?;
+            let module_name = ast::ModuleName::from(cst.header.module_name.clone());
+            Ok(BatchModule {
+                name,
+                source,
+                module_name,
+                cst,
+            })
+        })
+        .collect::<Vec<Result<_>>>()
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+
+    let cache = BuildCache::from_env();
+
+    while !remaining.is_empty() {
+        // Modules that don't import anything we've yet to check
+        let unchecked = remaining
+            .iter()
+            .map(|module| module.module_name.clone())
+            .collect::<HashSet<_>>();
+        let (layer, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|module| {
+            module.cst.imports.iter().all(|import_line| {
+                import_line.package.is_some()
+                    || !unchecked.contains(&ast::ModuleName::from(import_line.module_name.clone()))
+            })
+        });
+        if layer.is_empty() {
+            let mut module_names = blocked
+                .iter()
+                .map(|module| format!("`{}`", module.module_name))
+                .collect::<Vec<_>>();
+            module_names.sort();
+            return Err(miette!("modules form a cycle: {}", module_names.join(", ")));
+        }
+        remaining = blocked;
+
+        let results = layer
+            .into_par_iter()
+            .map(|module| {
+                let BatchModule {
+                    name,
+                    source,
+                    module_name,
+                    cst,
+                } = module;
+                let key = cache.as_ref().map(|_| {
+                    let dependency_exports = cst
+                        .imports
+                        .iter()
+                        .filter_map(|import_line| {
+                            let package_name = import_line
+                                .package
+                                .as_ref()
+                                .map(|parens| ast::PackageName::from(parens.value.clone()))
+                                .or_else(|| output_package_name.clone());
+                            let module_name =
+                                ast::ModuleName::from(import_line.module_name.clone());
+                            let serialized =
+                                serialized_exports.get(&(package_name.clone(), module_name))?;
+                            Some((package_name, serialized.as_slice()))
+                        })
+                        .collect();
+                    CacheKey::new(&name, &source, &output_package_name, dependency_exports)
+                });
+                if let (Some(cache), Some(key)) = (cache.as_ref(), key.as_ref()) {
+                    if let Some(ast_outputs) = cache.get(key) {
+                        let exports = common::deserialize_slice::<(
+                            ast::ModuleName,
+                            ast::ModuleExports,
+                        )>(&ast_outputs.ast_exports)
+                        .map(|(_, exports)| exports);
+                        return (module_name, exports.map(|exports| (exports, ast_outputs)));
+                    }
+                }
+                let result =
+                    check_module(&everything, cst, &name, &source).and_then(|(ast, warnings)| {
+                        let ast_outputs = serialize_ast_outputs(name, source, &ast, warnings)?;
+                        if let (Some(cache), Some(key)) = (cache.as_ref(), key.as_ref()) {
+                            if let Err(err) = cache.put(key, &ast_outputs) {
+                                // Not fatal
+                                tracing::warn!("error writing to build cache: {:?}", err);
+                            }
+                        }
+                        Ok((ast.exports, ast_outputs))
+                    });
+                (module_name, result)
+            })
+            .collect::<Vec<_>>();
+
+        let mut errors = Vec::new();
+        for (module_name, result) in results {
+            let result = result.and_then(|(exports, ast_outputs)| {
+                let ast_outputs = warning_levels.apply(ast_outputs)?;
+                Ok((exports, ast_outputs))
+            });
+            match result {
+                Ok((exports, ast_outputs)) => {
+                    let module_outputs = outputs.iter().filter(|output| {
+                        output_file_stem(Path::new(output))
+                            == Some(common::module_name_to_file_stem(module_name.clone()).as_path())
+                    });
+                    write_ast_outputs(module_outputs, &ast_outputs)?;
+                    serialized_exports.insert(
+                        (output_package_name.clone(), module_name.clone()),
+                        ast_outputs.ast_exports,
+                    );
+                    everything.modules.insert(module_name, exports);
+                }
+                Err(err) => errors.push(err),
+            }
+        }
+
+        // Report all the errors in this layer, but stop there
+        let mut errors = errors.into_iter();
+        if let Some(first) = errors.next() {
+            for err in errors {
+                print_report(&err);
+            }
+            return Err(first);
+        }
+    }
+
+    Ok(())
+}
+
+/// Need to figure out if we're compiling a "package module",
+/// and if so what the name of the package is.
+///
+/// This is so that imports referencing other modules in the same package
+/// resolve correctly.
+fn get_output_package_name(build_dir: &str, outputs: &[String]) -> Option<ast::PackageName> {
     let mut output_package_name = None;
     for output in outputs.iter() {
         let path = Path::new(&output);
@@ -123,57 +600,70 @@ fn run_ast(build_dir: &str, inputs: Vec<String>, outputs: Vec<String>) -> Result
             }
         }
     }
+    output_package_name
+}
 
-    for input in inputs {
-        let path = Path::new(&input);
-        match full_extension(path) {
-            Some(common::EXTENSION_DITTO) => {
-                let mut file = std::io::BufReader::new(File::open(path).into_diagnostic()?);
-                let mut contents = String::new();
-                file.read_to_string(&mut contents).into_diagnostic()?;
-                ditto_input = Some((path.to_string_lossy().into_owned(), contents));
-            }
-            Some(common::EXTENSION_AST_EXPORTS) => {
-                let (module_name, module_exports) = common::deserialize(path)?;
+fn read_ditto_input(path: &Path) -> Result<(String, String)> {
+    let mut file = std::io::BufReader::new(File::open(path).into_diagnostic()?);
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).into_diagnostic()?;
+    Ok((path.to_string_lossy().into_owned(), contents))
+}
 
-                let mut package_name = None;
-                if let Some(parent) = path.parent() {
-                    if parent.to_str() != Some(build_dir) {
-                        let dir = parent
-                            .file_name()
-                            .and_then(|file_name| file_name.to_str())
-                            .unwrap();
+/// Reads an `.ast-exports` file into `everything`.
+///
+/// Returns the package the exports belong to, along with the serialized exports.
+fn add_ast_exports(
+    build_dir: &str,
+    path: &Path,
+    output_package_name: &Option<ast::PackageName>,
+    everything: &mut checker::Everything,
+) -> Result<(Option<ast::PackageName>, ast::ModuleName, Vec<u8>)> {
+    let serialized = std::fs::read(path).into_diagnostic()?;
+    let (module_name, module_exports): (ast::ModuleName, ast::ModuleExports) =
+        common::deserialize_slice(&serialized)?;
 
-                        package_name = Some(ditto_ast::PackageName(dir.to_owned()));
-                    }
-                }
-                if output_package_name == package_name {
-                    // This is a "package module" importing another module from
-                    // the same package, so it needs to be added to the typing
-                    // environment _without_ a package qualifier.
-                    everything.modules.insert(module_name, module_exports);
-                } else if let Some(package_name) = package_name {
-                    // UPSERT the package
-                    if let Some(package) = everything.packages.get_mut(&package_name) {
-                        package.insert(module_name, module_exports);
-                    } else {
-                        let mut package = HashMap::new();
-                        package.insert(module_name, module_exports);
-                        everything.packages.insert(package_name, package);
-                    }
-                } else {
-                    everything.modules.insert(module_name, module_exports);
-                }
-            }
-            other => panic!("unexpected input extension {:#?}: {}", other, input),
+    let mut package_name = None;
+    if let Some(parent) = path.parent() {
+        if parent.to_str() != Some(build_dir) {
+            let dir = parent
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .unwrap();
+
+            package_name = Some(ditto_ast::PackageName(dir.to_owned()));
         }
     }
+    if *output_package_name == package_name {
+        // This is a "package module" importing another module from
+        // the same package, so it needs to be added to the typing
+        // environment _without_ a package qualifier.
+        everything
+            .modules
+            .insert(module_name.clone(), module_exports);
+    } else if let Some(ref package_name) = package_name {
+        // UPSERT the package
+        if let Some(package) = everything.packages.get_mut(package_name) {
+            package.insert(module_name.clone(), module_exports);
+        } else {
+            let mut package = HashMap::new();
+            package.insert(module_name.clone(), module_exports);
+            everything.packages.insert(package_name.clone(), package);
+        }
+    } else {
+        everything
+            .modules
+            .insert(module_name.clone(), module_exports);
+    }
+    Ok((package_name, module_name, serialized))
+}
 
-    let (ditto_input_name, ditto_input_source) = ditto_input.unwrap();
-
-    let cst = parse_cst(&ditto_input_source, &ditto_input_name)?;
-    let (ast, warnings) = check_module(everything, cst, &ditto_input_name, &ditto_input_source)?;
-
+fn serialize_ast_outputs(
+    ditto_input_name: String,
+    ditto_input_source: String,
+    ast: &ast::Module,
+    warnings: checker::Warnings,
+) -> Result<AstOutputs> {
     let warnings = warnings
         .into_iter()
         .map(|warning| {
@@ -182,69 +672,126 @@ fn run_ast(build_dir: &str, inputs: Vec<String>, outputs: Vec<String>) -> Result
         })
         .collect::<Vec<_>>();
 
+    let mut serialized_ast = Vec::new();
+    common::serialize(&mut serialized_ast, &(&ditto_input_name, ast))?;
+
+    let mut serialized_ast_exports = Vec::new();
+    common::serialize(
+        &mut serialized_ast_exports,
+        &(&ast.module_name, &ast.exports),
+    )?;
+
+    let warnings_bundle = if warnings.is_empty() {
+        None
+    } else {
+        Some(WarningsBundle {
+            name: ditto_input_name,
+            source: ditto_input_source,
+            warnings,
+        })
+    };
+    let mut serialized_checker_warnings = Vec::new();
+    common::serialize(&mut serialized_checker_warnings, &warnings_bundle)?;
+
+    Ok(AstOutputs {
+        ast: serialized_ast,
+        ast_exports: serialized_ast_exports,
+        checker_warnings: serialized_checker_warnings,
+    })
+}
+
+fn write_ast_outputs<'a>(
+    outputs: impl Iterator<Item = &'a String>,
+    ast_outputs: &AstOutputs,
+) -> Result<()> {
     let mut print_warnings = true;
     for output in outputs {
         let path = Path::new(&output);
         match full_extension(path) {
             Some(common::EXTENSION_AST) => {
-                let file = File::create(path).into_diagnostic()?;
-                let writer = std::io::BufWriter::new(file);
-                common::serialize(writer, &(&ditto_input_name, &ast))?;
+                write_if_changed(path, &ast_outputs.ast)?;
             }
             Some(common::EXTENSION_AST_EXPORTS) => {
-                let file = File::create(path).into_diagnostic()?;
-                let writer = std::io::BufWriter::new(file);
-                common::serialize(writer, &(&ast.module_name, &ast.exports))?;
+                write_if_changed(path, &ast_outputs.ast_exports)?;
             }
             Some(common::EXTENSION_CHECKER_WARNINGS) => {
                 let file = File::create(path).into_diagnostic()?;
-                let writer = std::io::BufWriter::new(file);
-                let warnings_bundle = if warnings.is_empty() {
-                    None
-                } else {
-                    Some(WarningsBundle {
-                        name: ditto_input_name.clone(),
-                        source: ditto_input_source.clone(),
-                        warnings: warnings.clone(),
-                    })
-                };
-                common::serialize(writer, &warnings_bundle)?;
+                let mut writer = std::io::BufWriter::new(file);
+                writer
+                    .write_all(&ast_outputs.checker_warnings)
+                    .into_diagnostic()?;
                 print_warnings = false;
             }
             other => panic!("unexpected output extension: {:#?}", other),
         }
     }
 
-    if print_warnings && !warnings.is_empty() {
-        let source = std::sync::Arc::new(ditto_input_source);
-        for warning in warnings {
-            eprintln!(
-                "{:#?}",
-                Report::from(warning)
-                    .with_source_code(NamedSource::new(&ditto_input_name, source.clone()))
-            );
+    if print_warnings {
+        let warnings_bundle =
+            common::deserialize_slice::<Option<WarningsBundle>>(&ast_outputs.checker_warnings)?;
+        if let Some(WarningsBundle {
+            name,
+            source,
+            warnings,
+        }) = warnings_bundle
+        {
+            let source = std::sync::Arc::new(source);
+            let message_format = MessageFormat::from_env();
+            for warning in warnings {
+                let report =
+                    Report::from(warning).with_source_code(NamedSource::new(&name, source.clone()));
+                if message_format.is_machine_readable() {
+                    println!("{}", DiagnosticRecord::from_diagnostic(&*report).to_json());
+                } else {
+                    eprintln!("{:#?}", report);
+                }
+            }
         }
     }
 
     Ok(())
 }
+
+/// Print an error in the requested [MessageFormat].
+fn print_report(report: &Report) {
+    if MessageFormat::from_env().is_machine_readable() {
+        println!("{}", DiagnosticRecord::from_diagnostic(&**report).to_json());
+    } else {
+        eprintln!("{:?}", report);
+    }
+}
+
+/// Only touch the file if the contents have actually changed,
+/// that way dependent builds can be skipped (see `restat`).
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result<()> {
+    let unchanged = std::fs::read(path).map_or(false, |existing| existing == contents);
+    if !unchanged {
+        std::fs::write(path, contents).into_diagnostic()?;
+    }
+    Ok(())
+}
+
 #[tracing::instrument(level = "trace", skip(source))]
 fn parse_cst(source: &str, source_name: &str) -> Result<cst::Module> {
-    let module = cst::Module::parse(source).map_err(|err| {
-        tracing::error!("{:#?}", err);
-        err.into_report(source_name, source.to_owned())
-    })?;
+    let module = timings::record(source_name, Phase::Parse, || cst::Module::parse(source))
+        .map_err(|err| {
+            tracing::error!("{:#?}", err);
+            err.into_report(source_name, source.to_owned())
+        })?;
     Ok(module)
 }
 
 #[tracing::instrument(level = "trace", skip(everything, cst, source))]
 fn check_module(
-    everything: checker::Everything,
+    everything: &checker::Everything,
     cst: cst::Module,
     source_name: &str,
     source: &str,
 ) -> Result<(ast::Module, checker::Warnings)> {
-    let (module, warnings) = checker::check_module(&everything, cst).map_err(|err| {
+    let (module, warnings) = timings::record(source_name, Phase::Check, || {
+        checker::check_module(everything, cst)
+    })
+    .map_err(|err| {
         tracing::error!("{:#?}", err);
         err.into_report(source_name, source.to_owned())
     })?;
@@ -255,6 +802,8 @@ fn check_module(
 fn run_js(inputs: Vec<String>, outputs: Vec<String>) -> Result<()> {
     let mut ditto_input_path = None;
     let mut ast = None;
+    // Serialized AST, for computing cache keys
+    let mut serialized_ast = Vec::new();
     let mut js_output_path = None;
     //let mut dts_output_path = None;
 
@@ -262,8 +811,9 @@ fn run_js(inputs: Vec<String>, outputs: Vec<String>) -> Result<()> {
         let path = Path::new(&input);
         match full_extension(path) {
             Some(common::EXTENSION_AST) => {
+                serialized_ast = std::fs::read(path).into_diagnostic()?;
                 let (deserialized_path, deserialized_ast) =
-                    common::deserialize::<(String, ast::Module)>(path)?;
+                    common::deserialize_slice::<(String, ast::Module)>(&serialized_ast)?;
                 ditto_input_path = Some(deserialized_path);
                 ast = Some(deserialized_ast);
             }
@@ -291,7 +841,7 @@ fn run_js(inputs: Vec<String>, outputs: Vec<String>) -> Result<()> {
     //let dts_output_path =
     //    dts_output_path.ok_or_else(|| miette!("TypeScript declaration output not specified"))?;
 
-    let mut foreign_module_path = PathBuf::from(ditto_input_path);
+    let mut foreign_module_path = PathBuf::from(&ditto_input_path);
     foreign_module_path.set_extension(common::EXTENSION_JS);
     let foreign_module_path =
         pathdiff::diff_paths(foreign_module_path, js_output_path.parent().unwrap()).unwrap();
@@ -322,10 +872,28 @@ fn run_js(inputs: Vec<String>, outputs: Vec<String>) -> Result<()> {
         }),
     };
 
-    let js = generate_javascript(&codegen_config, ast);
+    let cache = BuildCache::from_env().map(|cache| {
+        let key = CacheKey::js(&serialized_ast, &codegen_config.foreign_module_path);
+        (cache, key)
+    });
+    let cached_js = cache.as_ref().and_then(|(cache, key)| cache.get_js(key));
+    let js = if let Some(js) = cached_js {
+        js
+    } else {
+        let js = timings::record(&ditto_input_path, Phase::Codegen, || {
+            generate_javascript(&codegen_config, ast)
+        });
+        if let Some((cache, key)) = cache {
+            if let Err(err) = cache.put_js(&key, js.as_bytes()) {
+                // Not fatal
+                tracing::warn!("error writing to build cache: {:?}", err);
+            }
+        }
+        js.into_bytes()
+    };
 
     let mut js_file = std::io::BufWriter::new(File::create(&js_output_path).into_diagnostic()?);
-    js_file.write_all(js.as_bytes()).into_diagnostic()?;
+    js_file.write_all(&js).into_diagnostic()?;
 
     Ok(())
 }
@@ -348,8 +916,15 @@ fn run_package_json(input: &str, output: &str) -> Result<()> {
         "type": "module",
         "dependencies": config
             .dependencies
-            .into_iter()
-            .map(|name| (name.into_string(), String::from("*")))
+            .iter_with_version_reqs()
+            .map(|(name, version_req)| {
+                // Version ranges only mean something for versioned packages,
+                // workspace members and local packages match anything
+                let range = version_req
+                    .filter(|_| config.version.is_some())
+                    .map_or_else(|| String::from("*"), npm_version_range);
+                (name.as_str().to_string(), range)
+            })
             .collect::<HashMap<_, _>>(),
     });
 
@@ -360,6 +935,10 @@ fn run_package_json(input: &str, output: &str) -> Result<()> {
         unreachable!()
     };
 
+    if let Some(version) = config.version {
+        object.insert(String::from("version"), Value::String(version.to_string()));
+    }
+
     if let Some(additions) = config.codegen_js_config.package_json_additions {
         // NOTE "name" and "type" can't be overriden
         object = merge_objects(additions, object)
@@ -407,6 +986,31 @@ fn run_package_json(input: &str, output: &str) -> Result<()> {
     }
 }
 
+/// Translates a version requirement to npm's range syntax.
+///
+/// Comparators mean the same thing, but npm separates them with spaces rather than commas.
+fn npm_version_range(version_req: &semver::VersionReq) -> String {
+    if version_req.comparators.is_empty() {
+        return String::from("*");
+    }
+    version_req
+        .comparators
+        .iter()
+        .map(|comparator| comparator.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns everything before the first dot in a path's file name.
+///
+/// The counterpart to [full_extension].
+fn output_file_stem(path: &Path) -> Option<&Path> {
+    path.file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(|str| str.split_once('.'))
+        .map(|parts| Path::new(parts.0))
+}
+
 /// Returns everything after the first dot in a path.
 ///
 /// Useful for extensions like `.d.ts` where `path.extension` would return `.ts`.
@@ -416,3 +1020,18 @@ fn full_extension(path: &Path) -> Option<&str> {
         .and_then(|str| str.split_once('.'))
         .map(|parts| parts.1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::npm_version_range;
+
+    #[test]
+    fn it_translates_version_requirements_for_npm() {
+        let range = |version_req: &str| npm_version_range(&version_req.parse().unwrap());
+        assert_eq!(range("*"), "*");
+        assert_eq!(range("^1.2"), "^1.2");
+        assert_eq!(range(">=1.2, <2"), ">=1.2 <2");
+        assert_eq!(range("=1.2.3"), "=1.2.3");
+        assert_eq!(range("~0.3, 0.3.*"), "~0.3 0.3.*");
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{
+    build_ninja::{Build, RULE_ARG_IN, RULE_ARG_OUT},
+    common, BuildNinja,
+};
+use miette::{miette, IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    fs,
+    hash::Hasher,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process,
+    sync::mpsc,
+    time::SystemTime,
+};
+use tracing::{debug, trace};
+
+/// Something that happened while executing a [BuildNinja].
+pub enum Event<'a> {
+    /// A build command has been started.
+    Started {
+        /// The `description` of the build, e.g. "Checking Foo".
+        description: &'a str,
+        /// The number of build commands that have finished so far.
+        finished: usize,
+        /// The (current best estimate of) the number of build commands that will run.
+        total: usize,
+    },
+    /// A build command finished and printed something.
+    Output {
+        /// Combined stdout and stderr of the command.
+        output: &'a str,
+    },
+}
+
+/// The result of executing a [BuildNinja].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Everything was up to date, no commands were run.
+    ///
+    /// Equivalent to "ninja: no work to do".
+    NoWorkToDo,
+    /// At least one command was run, and they all succeeded.
+    Success,
+    /// A command failed. Its output will have been passed to the event handler.
+    Failed,
+}
+
+/// Execute a [BuildNinja] without shelling out to `ninja`.
+///
+/// Builds are run in dependency order, in parallel where possible.
+/// A build is skipped if its outputs exist and are newer than its inputs,
+/// or if its inputs are unchanged (by content) since it last ran. The latter
+/// check means that a rebuilt dependency with unchanged outputs doesn't
+/// trigger a rebuild of its dependents.
+///
+/// What was run is persisted to `build_log_path` between executions.
+#[tracing::instrument(level = "trace", skip_all)]
+pub fn execute_build_ninja(
+    build_ninja: &BuildNinja,
+    build_log_path: &Path,
+    envs: &[(String, String)],
+    mut on_event: impl FnMut(Event),
+) -> Result<Outcome> {
+    let jobs = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let builds = &build_ninja.builds;
+
+    // Map outputs to the build that produces them
+    let mut producers: HashMap<&Path, usize> = HashMap::new();
+    for (index, build) in builds.iter().enumerate() {
+        for output in build.outputs.iter() {
+            producers.insert(output.as_path(), index);
+        }
+    }
+
+    // Work out the dependency edges between builds
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); builds.len()];
+    let mut pending_dependencies: Vec<usize> = vec![0; builds.len()];
+    for (index, build) in builds.iter().enumerate() {
+        let mut dependencies = build
+            .inputs
+            .iter()
+            .filter_map(|input| producers.get(input.as_path()).copied())
+            .collect::<Vec<_>>();
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        pending_dependencies[index] = dependencies.len();
+        for dependency in dependencies {
+            dependents[dependency].push(index);
+        }
+    }
+
+    let mut ready: VecDeque<usize> = (0..builds.len())
+        .filter(|index| pending_dependencies[*index] == 0)
+        .collect();
+
+    let previous_log = read_build_log(build_log_path);
+    let mut next_log = BuildLog::default();
+    let mut in_flight: HashMap<usize, (String, BuildLogEntry)> = HashMap::new();
+
+    let (sender, receiver) = mpsc::channel::<(usize, std::io::Result<process::Output>)>();
+
+    let mut running = 0;
+    let mut finished = 0;
+    let mut total = builds.len();
+    let mut ran_something = false;
+    let mut failed = false;
+    // Stop scheduling on error, but let running builds finish so that
+    // they make it into the build log
+    let mut error: Option<miette::Report> = None;
+    let mut started: Vec<bool> = vec![false; builds.len()];
+
+    let mut release_dependents = |index: usize, ready: &mut VecDeque<usize>| {
+        for dependent in dependents[index].iter() {
+            pending_dependencies[*dependent] -= 1;
+            if pending_dependencies[*dependent] == 0 {
+                ready.push_back(*dependent);
+            }
+        }
+    };
+
+    loop {
+        while !failed && error.is_none() && running < jobs {
+            let index = match ready.pop_front() {
+                Some(index) => index,
+                None => break,
+            };
+            let build = &builds[index];
+            let (program, args) = match expand_command(build_ninja, build) {
+                Ok(command) => command,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            };
+            let command = std::iter::once(&program)
+                .chain(args.iter())
+                .map(|arg| arg.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let key = build_log_key(build);
+
+            let previous_entry = previous_log
+                .entries
+                .get(&key)
+                .filter(|entry| entry.command == command);
+
+            let input_hashes = match is_up_to_date(build, previous_entry) {
+                Ok(UpToDate::Yes) => {
+                    trace!("{} is up to date", key);
+                    next_log
+                        .entries
+                        .insert(key, previous_entry.cloned().unwrap());
+                    total -= 1;
+                    release_dependents(index, &mut ready);
+                    continue;
+                }
+                Ok(UpToDate::No(input_hashes)) => input_hashes,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            };
+
+            if let Err(err) = build
+                .outputs
+                .iter()
+                .filter_map(|output| output.parent())
+                .try_for_each(|parent| {
+                    fs::create_dir_all(parent)
+                        .into_diagnostic()
+                        .wrap_err(format!("error creating {:?}", parent))
+                })
+            {
+                error = Some(err);
+                break;
+            }
+
+            let description = build
+                .variables
+                .get("description")
+                .map_or(command.as_str(), |description| description.as_str());
+            on_event(Event::Started {
+                description,
+                finished,
+                total,
+            });
+
+            debug!("Running {}", command);
+            started[index] = true;
+            in_flight.insert(
+                index,
+                (
+                    key,
+                    BuildLogEntry {
+                        command,
+                        input_hashes,
+                    },
+                ),
+            );
+            let envs = envs.to_vec();
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let output = process::Command::new(program)
+                    .args(args)
+                    .envs(envs)
+                    .output();
+                // The receiver only goes away if we bailed
+                let _ = sender.send((index, output));
+            });
+            running += 1;
+            ran_something = true;
+        }
+
+        if running == 0 {
+            break;
+        }
+
+        let (index, result) = match receiver.recv().into_diagnostic() {
+            Ok(received) => received,
+            Err(err) => {
+                error = Some(err);
+                break;
+            }
+        };
+        running -= 1;
+        finished += 1;
+        let (key, entry) = in_flight.remove(&index).unwrap();
+        match result {
+            Ok(output) => {
+                let mut combined = String::from_utf8_lossy(&output.stdout).into_owned();
+                combined.push_str(&String::from_utf8_lossy(&output.stderr));
+                if !combined.trim().is_empty() {
+                    on_event(Event::Output { output: &combined });
+                }
+                if output.status.success() {
+                    next_log.entries.insert(key, entry);
+                    release_dependents(index, &mut ready);
+                } else {
+                    failed = true;
+                }
+            }
+            Err(err) => {
+                let output = format!("error running {}: {}", entry.command, err);
+                on_event(Event::Output { output: &output });
+                failed = true;
+            }
+        }
+    }
+
+    // Builds that didn't get to run (because something failed) are
+    // as up to date as they were last time
+    for (index, build) in builds.iter().enumerate() {
+        if started[index] {
+            continue;
+        }
+        let key = build_log_key(build);
+        if let Some(previous_entry) = previous_log.entries.get(&key) {
+            next_log
+                .entries
+                .entry(key)
+                .or_insert_with(|| previous_entry.clone());
+        }
+    }
+
+    write_build_log(build_log_path, &next_log)?;
+
+    if let Some(err) = error {
+        return Err(err);
+    }
+
+    Ok(if failed {
+        Outcome::Failed
+    } else if ran_something {
+        Outcome::Success
+    } else {
+        Outcome::NoWorkToDo
+    })
+}
+
+/// Resolve the program and arguments for a build, expanding `$in` and `$out`.
+fn expand_command(build_ninja: &BuildNinja, build: &Build) -> Result<(String, Vec<String>)> {
+    let rule = build_ninja
+        .rules
+        .iter()
+        .find(|rule| rule.name == build.rule_name)
+        .ok_or_else(|| miette!("unknown rule: {}", build.rule_name))?;
+
+    let mut args = Vec::new();
+    for arg in rule.args.iter() {
+        if arg == RULE_ARG_IN {
+            args.extend(paths_to_strings(&build.inputs));
+        } else if arg == RULE_ARG_OUT {
+            args.extend(paths_to_strings(&build.outputs));
+        } else {
+            args.push(arg.clone());
+        }
+    }
+    Ok((rule.program.clone(), args))
+}
+
+fn paths_to_strings(paths: &[PathBuf]) -> impl Iterator<Item = String> + '_ {
+    paths.iter().map(|path| path.to_string_lossy().into_owned())
+}
+
+enum UpToDate {
+    Yes,
+    /// Includes the current input hashes.
+    No(Vec<(String, u64)>),
+}
+
+fn is_up_to_date(build: &Build, previous_entry: Option<&BuildLogEntry>) -> Result<UpToDate> {
+    let previous_entry = match previous_entry {
+        Some(previous_entry) => previous_entry,
+        // Never run, or the command changed
+        None => return hash_inputs(build).map(UpToDate::No),
+    };
+
+    let mut oldest_output: Option<SystemTime> = None;
+    for output in build.outputs.iter() {
+        match fs::metadata(output).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => {
+                oldest_output = Some(oldest_output.map_or(modified, |oldest| oldest.min(modified)))
+            }
+            Err(_) => return hash_inputs(build).map(UpToDate::No),
+        }
+    }
+
+    let mut inputs_are_older = true;
+    for input in build.inputs.iter() {
+        let modified = fs::metadata(input)
+            .and_then(|metadata| metadata.modified())
+            .into_diagnostic()
+            .wrap_err(format!("error reading modification time of {:?}", input))?;
+        if Some(modified) > oldest_output {
+            inputs_are_older = false;
+            break;
+        }
+    }
+    if inputs_are_older {
+        return Ok(UpToDate::Yes);
+    }
+
+    // Something is newer, but it might not have actually changed
+    let input_hashes = hash_inputs(build)?;
+    if input_hashes == previous_entry.input_hashes {
+        Ok(UpToDate::Yes)
+    } else {
+        Ok(UpToDate::No(input_hashes))
+    }
+}
+
+fn hash_inputs(build: &Build) -> Result<Vec<(String, u64)>> {
+    build
+        .inputs
+        .iter()
+        .map(|input| {
+            let contents = fs::read(input)
+                .into_diagnostic()
+                .wrap_err(format!("error reading build input {:?}", input))?;
+            let mut hasher = DefaultHasher::new();
+            hasher.write(&contents);
+            Ok((input.to_string_lossy().into_owned(), hasher.finish()))
+        })
+        .collect()
+}
+
+fn build_log_key(build: &Build) -> String {
+    paths_to_strings(&build.outputs)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Record of the builds that have run successfully, keyed by their outputs.
+#[derive(Default, Serialize, Deserialize)]
+struct BuildLog {
+    entries: HashMap<String, BuildLogEntry>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct BuildLogEntry {
+    /// The fully expanded command that was run.
+    command: String,
+    /// Content hashes of the inputs when the command was run.
+    input_hashes: Vec<(String, u64)>,
+}
+
+fn read_build_log(path: &Path) -> BuildLog {
+    if !path.exists() {
+        return BuildLog::default();
+    }
+    common::deserialize(path).unwrap_or_else(|err| {
+        // Not the end of the world, everything will just be rebuilt
+        debug!("Error reading build log at {:?}: {:?}", path, err);
+        BuildLog::default()
+    })
+}
+
+fn write_build_log(path: &Path, build_log: &BuildLog) -> Result<()> {
+    let file = fs::File::create(path)
+        .into_diagnostic()
+        .wrap_err(format!("error creating build log {:?}", path))?;
+    common::serialize(file, build_log).wrap_err(format!("error writing build log {:?}", path))
+}
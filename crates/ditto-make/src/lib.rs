@@ -3,10 +3,27 @@
 #![warn(missing_docs)]
 
 mod build_ninja;
+mod cache;
 mod common;
 mod compile;
+mod execute;
+mod message_format;
+mod module_graph;
+mod timings;
 mod utils;
 
-pub use build_ninja::{generate_build_ninja, BuildNinja, GetWarnings, PackageSources, Sources};
+pub use build_ninja::{
+    generate_build_ninja, BuildNinja, CompileMode, GetWarnings, PackageSources, Sources,
+};
+pub use cache::{gc_build_cache, GcStats, ENV_BUILD_CACHE_DIR};
 pub use compile::{command as command_compile, run as run_compile};
+pub use execute::{execute_build_ninja, Event, Outcome};
+pub use message_format::{
+    to_sarif, DiagnosticLabel, DiagnosticRecord, MessageFormat, Position, ENV_MESSAGE_FORMAT,
+};
+pub use module_graph::{generate_module_graph, ModuleGraph, ModuleGraphNode};
+pub use timings::{
+    collect_timings, CriticalPath, EdgeTimings, ModuleTimings, Phase, PhaseTiming, TimingsReport,
+    ENV_TIMINGS_DIR,
+};
 pub use utils::find_ditto_files;
@@ -0,0 +1,234 @@
+use miette::{Diagnostic, Severity, SourceSpan};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Environment variable used to tell `ditto compile` which [MessageFormat] to use.
+pub static ENV_MESSAGE_FORMAT: &str = "DITTO_MESSAGE_FORMAT";
+
+/// How diagnostics should be printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Pretty miette reports.
+    #[default]
+    Human,
+    /// A [DiagnosticRecord] per line.
+    Json,
+    /// A single [SARIF](https://sarifweb.azurewebsites.net/) log.
+    Sarif,
+}
+
+impl MessageFormat {
+    /// All the supported formats, as CLI values.
+    pub const VALUES: [&'static str; 3] = ["human", "json", "sarif"];
+
+    /// Parse a value from [MessageFormat::VALUES].
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "human" => Some(Self::Human),
+            "json" => Some(Self::Json),
+            "sarif" => Some(Self::Sarif),
+            _ => None,
+        }
+    }
+
+    /// The inverse of [MessageFormat::parse].
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Human => "human",
+            Self::Json => "json",
+            Self::Sarif => "sarif",
+        }
+    }
+
+    /// Read the format from [ENV_MESSAGE_FORMAT], defaulting to [MessageFormat::Human].
+    pub fn from_env() -> Self {
+        std::env::var(ENV_MESSAGE_FORMAT)
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    /// Should diagnostics be printed as [DiagnosticRecord]s?
+    pub fn is_machine_readable(&self) -> bool {
+        !matches!(self, Self::Human)
+    }
+}
+
+/// A structured representation of a parse error, type error or warning.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticRecord {
+    /// The file the diagnostic refers to, if any.
+    pub file: Option<String>,
+    /// `"error"`, `"warning"` or `"advice"`.
+    pub severity: String,
+    /// Stable identifier for this kind of diagnostic.
+    pub code: Option<String>,
+    /// The headline message.
+    pub message: String,
+    /// Additional help text.
+    pub help: Option<String>,
+    /// Labelled source locations, the first being the primary location.
+    pub labels: Vec<DiagnosticLabel>,
+}
+
+/// A labelled range within a [DiagnosticRecord::file].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiagnosticLabel {
+    /// What's happening here.
+    pub message: Option<String>,
+    /// Inclusive start position.
+    pub start: Position,
+    /// Exclusive end position.
+    pub end: Position,
+}
+
+/// A position within a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    /// Byte offset, starting from zero.
+    pub offset: usize,
+    /// Line number, starting from one.
+    pub line: usize,
+    /// Column number, starting from one.
+    pub column: usize,
+}
+
+impl DiagnosticRecord {
+    /// Convert a miette diagnostic (which should have source code attached).
+    pub fn from_diagnostic(diagnostic: &dyn Diagnostic) -> Self {
+        let severity = match diagnostic.severity().unwrap_or(Severity::Error) {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Advice => "advice",
+        };
+        let mut file = None;
+        let mut labels = Vec::new();
+        if let (Some(source_code), Some(diagnostic_labels)) =
+            (diagnostic.source_code(), diagnostic.labels())
+        {
+            for label in diagnostic_labels {
+                let start_span = SourceSpan::from((label.offset(), 0));
+                let end_span = SourceSpan::from((label.offset() + label.len(), 0));
+                let start = match source_code.read_span(&start_span, 0, 0) {
+                    Ok(contents) => {
+                        if file.is_none() {
+                            file = contents.name().map(|name| name.to_owned());
+                        }
+                        Position {
+                            offset: label.offset(),
+                            line: contents.line() + 1,
+                            column: contents.column() + 1,
+                        }
+                    }
+                    Err(_) => continue,
+                };
+                let end = match source_code.read_span(&end_span, 0, 0) {
+                    Ok(contents) => Position {
+                        offset: label.offset() + label.len(),
+                        line: contents.line() + 1,
+                        column: contents.column() + 1,
+                    },
+                    Err(_) => start.clone(),
+                };
+                labels.push(DiagnosticLabel {
+                    message: label.label().map(|label| label.to_owned()),
+                    start,
+                    end,
+                });
+            }
+        }
+        Self {
+            file,
+            severity: severity.to_owned(),
+            code: diagnostic.code().map(|code| code.to_string()),
+            message: diagnostic.to_string(),
+            help: diagnostic.help().map(|help| help.to_string()),
+            labels,
+        }
+    }
+
+    /// A diagnostic that didn't originate from miette, e.g. a panic message.
+    pub fn from_message(message: String) -> Self {
+        Self {
+            file: None,
+            severity: String::from("error"),
+            code: None,
+            message,
+            help: None,
+            labels: Vec::new(),
+        }
+    }
+
+    /// Render as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Parse a line of output, if it was printed by [DiagnosticRecord::to_json].
+    pub fn from_json(line: &str) -> Option<Self> {
+        serde_json::from_str(line).ok()
+    }
+}
+
+/// Render records as a [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html) log.
+pub fn to_sarif(records: &[DiagnosticRecord], tool_version: &str) -> serde_json::Value {
+    let results = records
+        .iter()
+        .map(|record| {
+            let level = match record.severity.as_str() {
+                "error" => "error",
+                "warning" => "warning",
+                _ => "note",
+            };
+            let locations = record
+                .labels
+                .iter()
+                .take(1)
+                .filter_map(|label| {
+                    Some(json!({
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": record.file.as_ref()? },
+                            "region": {
+                                "startLine": label.start.line,
+                                "startColumn": label.start.column,
+                                "endLine": label.end.line,
+                                "endColumn": label.end.column,
+                                "byteOffset": label.start.offset,
+                                "byteLength": label.end.offset - label.start.offset,
+                            }
+                        }
+                    }))
+                })
+                .collect::<Vec<_>>();
+            let mut message = record.message.clone();
+            if let Some(ref help) = record.help {
+                message.push_str("\n\n");
+                message.push_str(help);
+            }
+            let mut result = json!({
+                "level": level,
+                "message": { "text": message },
+                "locations": locations,
+            });
+            if let Some(ref code) = record.code {
+                result["ruleId"] = json!(code);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ditto",
+                    "version": tool_version,
+                    "informationUri": "https://github.com/ditto-lang/ditto",
+                }
+            },
+            "results": results,
+        }]
+    })
+}
@@ -0,0 +1,241 @@
+use crate::build_ninja::{prepare_build_graph, PackageSources, Sources};
+use ditto_config::read_config;
+use miette::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The import graph of a package and its dependencies.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ModuleGraph {
+    /// Modules, ordered by [ModuleGraphNode::id].
+    pub modules: Vec<ModuleGraphNode>,
+    /// `(importer, imported)` pairs of indexes into `modules`.
+    pub imports: Vec<(usize, usize)>,
+}
+
+/// A module in a [ModuleGraph].
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ModuleGraphNode {
+    /// Unique identifier, e.g. `Data.Thing` or `some-package:Data.Thing`
+    /// for modules that aren't part of the current package.
+    pub id: String,
+    /// The package this module belongs to.
+    pub package: String,
+    /// The module name, e.g. `Data.Thing`.
+    pub module: String,
+    /// Path to the module's source file.
+    pub path: String,
+}
+
+/// Generates the [ModuleGraph] for some sources.
+///
+/// This does the same validation as [generate_build_ninja](crate::generate_build_ninja),
+/// so will fail if modules form a cycle.
+#[tracing::instrument(level = "trace", skip_all)]
+pub fn generate_module_graph(
+    ditto_version: &semver::Version,
+    sources: Sources,
+    package_sources: PackageSources,
+) -> Result<ModuleGraph> {
+    let config = read_config(&sources.config)?;
+    let (graph, graph_nodes) = prepare_build_graph(sources, package_sources, ditto_version)?;
+
+    let mut modules = graph_nodes
+        .iter()
+        .map(|(node_index, node)| {
+            let module_graph_node = ModuleGraphNode {
+                id: node.to_string(),
+                package: node
+                    .package_name
+                    .as_ref()
+                    .map_or(config.name.as_str(), |name| name.as_str())
+                    .to_owned(),
+                module: node.module_name.to_string(),
+                path: path_slash::PathExt::to_slash_lossy(node.source_path.as_path()).into_owned(),
+            };
+            (*node_index, module_graph_node)
+        })
+        .collect::<Vec<_>>();
+    modules.sort_by(|a, b| a.1.id.cmp(&b.1.id));
+
+    let indexes = modules
+        .iter()
+        .enumerate()
+        .map(|(i, (node_index, _))| (*node_index, i))
+        .collect::<HashMap<_, _>>();
+
+    let mut imports = graph
+        .edge_indices()
+        .filter_map(|edge_index| {
+            let (from, to) = graph.edge_endpoints(edge_index)?;
+            Some((indexes[&from], indexes[&to]))
+        })
+        .collect::<Vec<_>>();
+    imports.sort_unstable();
+    imports.dedup();
+
+    Ok(ModuleGraph {
+        modules: modules.into_iter().map(|(_, module)| module).collect(),
+        imports,
+    })
+}
+
+impl ModuleGraph {
+    /// Only keep modules belonging to the given package.
+    pub fn filter_package(&self, package: &str) -> Self {
+        self.retain(|module| module.package == package)
+    }
+
+    /// Only keep the given module and everything it (transitively) imports.
+    ///
+    /// Returns `None` if the module can't be found.
+    pub fn filter_closure(&self, id: &str) -> Option<Self> {
+        let root = self.modules.iter().position(|module| module.id == id)?;
+        let mut reachable = HashSet::from([root]);
+        let mut stack = vec![root];
+        while let Some(current) = stack.pop() {
+            for (_, imported) in self
+                .imports
+                .iter()
+                .filter(|(importer, _)| *importer == current)
+            {
+                if reachable.insert(*imported) {
+                    stack.push(*imported);
+                }
+            }
+        }
+        let reachable = reachable
+            .into_iter()
+            .map(|i| self.modules[i].id.as_str())
+            .collect::<HashSet<_>>();
+        Some(self.retain(|module| reachable.contains(module.id.as_str())))
+    }
+
+    fn retain(&self, keep: impl Fn(&ModuleGraphNode) -> bool) -> Self {
+        let mut new_indexes = HashMap::new();
+        let mut modules = Vec::new();
+        for (i, module) in self.modules.iter().enumerate() {
+            if keep(module) {
+                new_indexes.insert(i, modules.len());
+                modules.push(module.clone());
+            }
+        }
+        let imports = self
+            .imports
+            .iter()
+            .filter_map(|(importer, imported)| {
+                Some((*new_indexes.get(importer)?, *new_indexes.get(imported)?))
+            })
+            .collect();
+        Self { modules, imports }
+    }
+
+    /// Render as [Graphviz DOT](https://graphviz.org/doc/info/lang.html),
+    /// with a cluster per package.
+    pub fn to_dot(&self) -> String {
+        let mut packages: BTreeMap<&str, Vec<&ModuleGraphNode>> = BTreeMap::new();
+        for module in self.modules.iter() {
+            packages.entry(&module.package).or_default().push(module);
+        }
+
+        let mut dot = String::from("digraph modules {\n");
+        for (package, modules) in packages {
+            dot.push_str(&format!(
+                "  subgraph {:?} {{\n",
+                format!("cluster_{}", package)
+            ));
+            dot.push_str(&format!("    label = {:?};\n", package));
+            for module in modules {
+                dot.push_str(&format!(
+                    "    {:?} [label = {:?}];\n",
+                    module.id, module.module
+                ));
+            }
+            dot.push_str("  }\n");
+        }
+        for (importer, imported) in self.imports.iter() {
+            dot.push_str(&format!(
+                "  {:?} -> {:?};\n",
+                self.modules[*importer].id, self.modules[*imported].id
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Render as pretty JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(package: &str, module: &str) -> ModuleGraphNode {
+        ModuleGraphNode {
+            id: if package == "test" {
+                module.to_owned()
+            } else {
+                format!("{}:{}", package, module)
+            },
+            package: package.to_owned(),
+            module: module.to_owned(),
+            path: format!("{}.ditto", module),
+        }
+    }
+
+    fn graph() -> ModuleGraph {
+        // A -> B -> dep:C
+        // D -> dep:C
+        ModuleGraph {
+            modules: vec![
+                node("test", "A"),
+                node("test", "B"),
+                node("test", "D"),
+                node("dep", "C"),
+            ],
+            imports: vec![(0, 1), (1, 3), (2, 3)],
+        }
+    }
+
+    #[test]
+    fn it_filters_by_package() {
+        let filtered = graph().filter_package("test");
+        assert_eq!(
+            filtered.modules,
+            vec![node("test", "A"), node("test", "B"), node("test", "D")]
+        );
+        assert_eq!(filtered.imports, vec![(0, 1)]);
+    }
+
+    #[test]
+    fn it_filters_by_closure() {
+        let filtered = graph().filter_closure("B").unwrap();
+        assert_eq!(filtered.modules, vec![node("test", "B"), node("dep", "C")]);
+        assert_eq!(filtered.imports, vec![(0, 1)]);
+        assert!(graph().filter_closure("Nope").is_none());
+    }
+
+    #[test]
+    fn it_renders_dot() {
+        similar_asserts::assert_eq!(
+            graph().filter_closure("A").unwrap().to_dot(),
+            r#"digraph modules {
+  subgraph "cluster_dep" {
+    label = "dep";
+    "dep:C" [label = "C"];
+  }
+  subgraph "cluster_test" {
+    label = "test";
+    "A" [label = "A"];
+    "B" [label = "B"];
+  }
+  "A" -> "B";
+  "B" -> "dep:C";
+}
+"#
+        );
+    }
+}
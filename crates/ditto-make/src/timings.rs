@@ -0,0 +1,410 @@
+use miette::{IntoDiagnostic, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Instant, SystemTime},
+};
+
+/// Environment variable pointing to a directory where `ditto compile` should
+/// record [EdgeTimings].
+///
+/// The directory should be emptied before each build, see [collect_timings].
+pub static ENV_TIMINGS_DIR: &str = "DITTO_TIMINGS_DIR";
+
+/// A stage of compiling a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    /// Parsing source to a CST.
+    Parse,
+    /// Type-checking a CST.
+    Check,
+    /// Generating code for a target.
+    Codegen,
+}
+
+/// How long a [Phase] took for a module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    /// Path to the module's source file.
+    pub module: String,
+    /// What was being done.
+    pub phase: Phase,
+    /// How long it took.
+    pub duration_ms: f64,
+}
+
+/// Timings recorded by a single `ditto compile` process, i.e. a single build edge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EdgeTimings {
+    /// The compile subcommand, e.g. `"ast"`.
+    pub subcommand: String,
+    /// Human readable summary of what was built.
+    pub description: String,
+    /// Input files.
+    pub inputs: Vec<String>,
+    /// Output files.
+    pub outputs: Vec<String>,
+    /// Milliseconds since the unix epoch.
+    pub start_ms: f64,
+    /// How long the whole process took.
+    pub duration_ms: f64,
+    /// Per-module breakdown.
+    pub phases: Vec<PhaseTiming>,
+}
+
+/// Phases recorded by the current process.
+static PHASES: Mutex<Vec<PhaseTiming>> = Mutex::new(Vec::new());
+
+/// Run `f`, recording how long it took.
+pub(crate) fn record<T>(module: &str, phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    if let Ok(mut phases) = PHASES.lock() {
+        phases.push(PhaseTiming {
+            module: module.to_owned(),
+            phase,
+            duration_ms,
+        });
+    }
+    result
+}
+
+/// Milliseconds since the unix epoch.
+pub(crate) fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0.0, |duration| duration.as_secs_f64() * 1000.0)
+}
+
+/// Write everything recorded by this process to [ENV_TIMINGS_DIR], if it's set.
+pub(crate) fn write_edge_timings(
+    subcommand: &str,
+    description: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    start_ms: f64,
+) -> Result<()> {
+    let dir = if let Some(dir) = std::env::var_os(ENV_TIMINGS_DIR) {
+        PathBuf::from(dir)
+    } else {
+        return Ok(());
+    };
+    let phases = PHASES
+        .lock()
+        .map(|mut phases| std::mem::take(&mut *phases))
+        .unwrap_or_default();
+    let edge_timings = EdgeTimings {
+        subcommand: subcommand.to_owned(),
+        description,
+        inputs,
+        outputs,
+        start_ms,
+        duration_ms: now_ms() - start_ms,
+        phases,
+    };
+    fs::create_dir_all(&dir).into_diagnostic()?;
+    let path = dir.join(format!(
+        "{}-{}.json",
+        std::process::id(),
+        start_ms.to_bits()
+    ));
+    let json = serde_json::to_vec(&edge_timings).into_diagnostic()?;
+    fs::write(&path, json)
+        .into_diagnostic()
+        .wrap_err(format!("error writing timings to {:?}", path))
+}
+
+/// Timings for a whole build.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingsReport {
+    /// Wall-clock time from the first edge starting to the last edge finishing.
+    pub total_ms: f64,
+    /// Every edge that ran, ordered by start time.
+    ///
+    /// Note that `start_ms` is relative to the start of the first edge.
+    pub edges: Vec<EdgeTimings>,
+    /// Per-module totals, slowest first.
+    pub modules: Vec<ModuleTimings>,
+    /// The chain of dependent edges that took the longest.
+    pub critical_path: CriticalPath,
+}
+
+/// Total time spent on each [Phase] for a module.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModuleTimings {
+    /// Path to the module's source file.
+    pub module: String,
+    /// Time spent parsing.
+    pub parse_ms: f64,
+    /// Time spent type-checking.
+    pub check_ms: f64,
+    /// Time spent generating code.
+    pub codegen_ms: f64,
+}
+
+impl ModuleTimings {
+    /// Time spent on all phases.
+    pub fn total_ms(&self) -> f64 {
+        self.parse_ms + self.check_ms + self.codegen_ms
+    }
+}
+
+/// See [TimingsReport::critical_path].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CriticalPath {
+    /// Sum of the edge durations.
+    pub duration_ms: f64,
+    /// Edge descriptions, in build order.
+    pub edges: Vec<String>,
+}
+
+/// Read the [EdgeTimings] written to `dir` (see [ENV_TIMINGS_DIR]) and summarise them.
+///
+/// Only edges that actually ran are included, up-to-date edges aren't rebuilt.
+pub fn collect_timings(dir: &Path) -> Result<TimingsReport> {
+    let mut edges = Vec::new();
+    if dir.exists() {
+        for entry in fs::read_dir(dir).into_diagnostic()? {
+            let path = entry.into_diagnostic()?.path();
+            let contents = fs::read(&path).into_diagnostic()?;
+            let edge_timings = serde_json::from_slice::<EdgeTimings>(&contents)
+                .into_diagnostic()
+                .wrap_err(format!("error reading timings from {:?}", path))?;
+            edges.push(edge_timings);
+        }
+    }
+    Ok(TimingsReport::new(edges))
+}
+
+impl TimingsReport {
+    fn new(mut edges: Vec<EdgeTimings>) -> Self {
+        edges.sort_by(|a, b| a.start_ms.total_cmp(&b.start_ms));
+
+        let first_start = edges.first().map_or(0.0, |edge| edge.start_ms);
+        let last_end = edges
+            .iter()
+            .map(|edge| edge.start_ms + edge.duration_ms)
+            .fold(first_start, f64::max);
+        for edge in edges.iter_mut() {
+            edge.start_ms -= first_start;
+        }
+
+        let mut modules: HashMap<&str, ModuleTimings> = HashMap::new();
+        for phase_timing in edges.iter().flat_map(|edge| edge.phases.iter()) {
+            let module_timings =
+                modules
+                    .entry(&phase_timing.module)
+                    .or_insert_with(|| ModuleTimings {
+                        module: phase_timing.module.clone(),
+                        ..ModuleTimings::default()
+                    });
+            match phase_timing.phase {
+                Phase::Parse => module_timings.parse_ms += phase_timing.duration_ms,
+                Phase::Check => module_timings.check_ms += phase_timing.duration_ms,
+                Phase::Codegen => module_timings.codegen_ms += phase_timing.duration_ms,
+            }
+        }
+        let mut modules = modules.into_values().collect::<Vec<_>>();
+        modules.sort_by(|a, b| {
+            b.total_ms()
+                .total_cmp(&a.total_ms())
+                .then_with(|| a.module.cmp(&b.module))
+        });
+
+        let critical_path = critical_path(&edges);
+
+        Self {
+            total_ms: last_end - first_start,
+            edges,
+            modules,
+            critical_path,
+        }
+    }
+
+    /// Render as a standalone HTML page.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str(HTML_HEAD);
+        html.push_str(&format!(
+            "<h1>ditto build timings</h1>\n<p>{} edges ran in {}.</p>\n",
+            self.edges.len(),
+            format_ms(self.total_ms)
+        ));
+
+        html.push_str(&format!(
+            "<h2>Critical path ({})</h2>\n<ol>\n",
+            format_ms(self.critical_path.duration_ms)
+        ));
+        for description in self.critical_path.edges.iter() {
+            html.push_str(&format!("<li>{}</li>\n", escape_html(description)));
+        }
+        html.push_str("</ol>\n");
+
+        html.push_str("<h2>Edges</h2>\n<table>\n");
+        html.push_str("<tr><th>Edge</th><th>Start</th><th>Duration</th><th></th></tr>\n");
+        for edge in self.edges.iter() {
+            let (left, width) = if self.total_ms > 0.0 {
+                (
+                    edge.start_ms / self.total_ms * 100.0,
+                    edge.duration_ms / self.total_ms * 100.0,
+                )
+            } else {
+                (0.0, 100.0)
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"timeline\"><div style=\"margin-left: {:.2}%; width: {:.2}%\"></div></td></tr>\n",
+                escape_html(&edge.description),
+                format_ms(edge.start_ms),
+                format_ms(edge.duration_ms),
+                left,
+                width.max(0.1)
+            ));
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h2>Modules</h2>\n<table>\n");
+        html.push_str(
+            "<tr><th>Module</th><th>Parse</th><th>Check</th><th>Codegen</th><th>Total</th></tr>\n",
+        );
+        for module in self.modules.iter() {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(&module.module),
+                format_ms(module.parse_ms),
+                format_ms(module.check_ms),
+                format_ms(module.codegen_ms),
+                format_ms(module.total_ms())
+            ));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+/// Find the longest chain of edges, where an edge depends on another if
+/// it consumes one of its outputs.
+fn critical_path(edges: &[EdgeTimings]) -> CriticalPath {
+    let producers = edges
+        .iter()
+        .enumerate()
+        .flat_map(|(i, edge)| edge.outputs.iter().map(move |output| (output.as_str(), i)))
+        .collect::<HashMap<_, _>>();
+
+    // (finish time, previous edge) for each edge, edges are ordered by start time
+    // so dependencies will always have been visited first.
+    let mut finishes: Vec<(f64, Option<usize>)> = Vec::with_capacity(edges.len());
+    for edge in edges.iter() {
+        let slowest_dependency = edge
+            .inputs
+            .iter()
+            .filter_map(|input| producers.get(input.as_str()))
+            .filter(|i| **i < finishes.len())
+            .map(|i| (finishes[*i].0, *i))
+            .max_by(|a, b| a.0.total_cmp(&b.0));
+        finishes.push(match slowest_dependency {
+            Some((finish, i)) => (finish + edge.duration_ms, Some(i)),
+            None => (edge.duration_ms, None),
+        });
+    }
+
+    let last = finishes
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1 .0.total_cmp(&b.1 .0))
+        .map(|(i, _)| i);
+
+    let mut critical_path = CriticalPath::default();
+    let mut current = last;
+    while let Some(i) = current {
+        critical_path.edges.push(edges[i].description.clone());
+        current = finishes[i].1;
+    }
+    critical_path.edges.reverse();
+    critical_path.duration_ms = last.map_or(0.0, |i| finishes[i].0);
+    critical_path
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else {
+        format!("{:.1}ms", ms)
+    }
+}
+
+fn escape_html(string: &str) -> String {
+    string
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+static HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>ditto build timings</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: 0.2em 0.6em; border-bottom: 1px solid #ddd; }
+td.timeline { width: 40%; }
+td.timeline div { height: 0.8em; background: #7aa6da; }
+</style>
+</head>
+<body>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(
+        description: &str,
+        inputs: &[&str],
+        outputs: &[&str],
+        start_ms: f64,
+        duration_ms: f64,
+    ) -> EdgeTimings {
+        EdgeTimings {
+            subcommand: String::from("ast"),
+            description: description.to_owned(),
+            inputs: inputs.iter().map(|input| input.to_string()).collect(),
+            outputs: outputs.iter().map(|output| output.to_string()).collect(),
+            start_ms,
+            duration_ms,
+            phases: vec![PhaseTiming {
+                module: description.to_owned(),
+                phase: Phase::Check,
+                duration_ms,
+            }],
+        }
+    }
+
+    #[test]
+    fn it_finds_the_critical_path() {
+        let report = TimingsReport::new(vec![
+            edge(
+                "C",
+                &["C.ditto", "A.ast-exports", "B.ast-exports"],
+                &["C.ast-exports"],
+                1030.0,
+                10.0,
+            ),
+            edge("A", &["A.ditto"], &["A.ast-exports"], 1000.0, 20.0),
+            edge("B", &["B.ditto"], &["B.ast-exports"], 1000.0, 30.0),
+            edge("D", &["D.ditto"], &["D.ast-exports"], 1000.0, 35.0),
+        ]);
+        assert_eq!(report.total_ms, 40.0);
+        assert_eq!(report.critical_path.edges, vec!["B", "C"]);
+        assert_eq!(report.critical_path.duration_ms, 40.0);
+        assert_eq!(report.modules[0].module, "D");
+        assert_eq!(report.edges[0].start_ms, 0.0);
+    }
+}